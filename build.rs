@@ -0,0 +1,20 @@
+//! Exposes the version of Bend the server is built with as `BEND_VERSION`,
+//! read from the lock file, as it may differ from the version of this crate.
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let version = lock
+        .split("[[package]]")
+        .find(|package| package.contains("\nname = \"bend-lang\"\n"))
+        .and_then(|package| {
+            package
+                .lines()
+                .find_map(|line| line.strip_prefix("version = "))
+        })
+        .map(|version| version.trim_matches('"').to_string())
+        .unwrap_or_else(|| "unknown".into());
+
+    println!("cargo:rustc-env=BEND_VERSION={version}");
+}
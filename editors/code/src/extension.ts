@@ -66,6 +66,34 @@ export async function activate(context: ExtensionContext) {
   });
   context.subscriptions.push(stopCommand);
 
+  const explainCommand = commands.registerCommand("bend.commands.explainDiagnostic", async () => {
+    const editor = window.activeTextEditor;
+    if (!editor) {
+      return;
+    }
+
+    // Offer the codes of the Bend diagnostics under the cursor.
+    const position = editor.selection.active;
+    const codes = languages
+      .getDiagnostics(editor.document.uri)
+      .filter(diag => diag.source === "bend" && diag.range.contains(position))
+      .map(diag => (typeof diag.code === "object" ? diag.code.value : diag.code))
+      .filter((code): code is string => typeof code === "string");
+    const unique = [...new Set(codes)];
+
+    const code = unique.length === 1 ? unique[0] : await window.showQuickPick(unique, {
+      placeHolder: "Diagnostic code to explain",
+    });
+    if (!code) {
+      return;
+    }
+
+    const result = (await client.sendRequest("bend/explain", { code })) as { explanation: string };
+    const document = await workspace.openTextDocument({ language: "markdown", content: result.explanation });
+    await commands.executeCommand("markdown.showPreview", document.uri);
+  });
+  context.subscriptions.push(explainCommand);
+
   // We have to check if `bend-language-server` is installed, and if it's not, try to install it.
   const command = process.env.BEND_LS_PATH || pipe(
     await findLanguageServer(context, logger),
@@ -91,6 +119,8 @@ export async function activate(context: ExtensionContext) {
     // Register the server for plain text documents
     documentSelector: [{ scheme: "file", language: "bend" }],
     synchronize: {
+      // Notify the server when the `bend.*` settings change
+      configurationSection: "bend",
       // Notify the server about file changes to '.clientrc files contained in the workspace
       // (we don't care about this for now, it was part of the boilerplate)
       fileEvents: workspace.createFileSystemWatcher("**/.clientrc"),
@@ -19,6 +19,9 @@ async fn main() {
         return;
     }
 
-    let (service, socket) = LspService::new(Backend::new);
+    let (service, socket) = LspService::build(Backend::new)
+        .custom_method("bend/explain", Backend::explain)
+        .custom_method("window/workDoneProgress/cancel", Backend::cancel_progress)
+        .finish();
     Server::new(stdin, stdout, socket).serve(service).await;
 }
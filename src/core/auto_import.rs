@@ -0,0 +1,277 @@
+//! Names defined in other modules that a document can import, and the
+//! import statements that bring them into scope.
+//!
+//! Bend resolves imports relative to the directory of the importing file, so
+//! the modules are the Bend files of the workspace inside that directory. The
+//! files and the names they define are indexed, and read again only when they
+//! change.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tower_lsp::lsp_types as lsp;
+
+use super::definitions::{definitions, DefinitionKind};
+use super::document::Document;
+use super::imports;
+use super::workspace::bend_files;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+/// A top-level definition of another module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Importable {
+    /// Module path of the file defining the name, like `lib/util`.
+    pub module: String,
+    pub name: String,
+    pub kind: DefinitionKind,
+}
+
+/// An import statement that would bind a name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportStatement {
+    /// `from Module import name`.
+    From { module: String, name: String },
+    /// `import Module`, used as `Module/name`.
+    Module(String),
+}
+
+impl ImportStatement {
+    pub fn text(&self) -> String {
+        match self {
+            ImportStatement::From { module, name } => format!("from {module} import {name}"),
+            ImportStatement::Module(module) => format!("import {module}"),
+        }
+    }
+}
+
+/// Names and kinds of the top-level definitions of a module.
+pub type ModuleNames = Vec<(String, DefinitionKind)>;
+
+/// Index of the Bend files of the workspace folders and of the names they
+/// define, kept until the files change.
+#[derive(Default)]
+pub struct ImportIndex {
+    /// Bend files of each workspace folder.
+    files: Mutex<HashMap<PathBuf, Arc<Vec<PathBuf>>>>,
+    /// Names defined by each file, as saved on the disk.
+    names: Mutex<HashMap<PathBuf, Arc<ModuleNames>>>,
+}
+
+impl ImportIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The definitions of the modules the document at `url` can import, other
+    /// than itself, from the workspace `folders`.
+    ///
+    /// The names of the documents in `open` are used instead of the ones of
+    /// their files, which may not be saved.
+    pub fn importables(
+        &self,
+        url: &lsp::Url,
+        folders: &[PathBuf],
+        open: &HashMap<PathBuf, ModuleNames>,
+    ) -> Vec<Importable> {
+        let Ok(own_file) = url.to_file_path() else {
+            return vec![];
+        };
+        let Some(base_dir) = own_file.parent() else {
+            return vec![];
+        };
+
+        let mut importables = vec![];
+        for folder in folders {
+            for file in self.folder_files(folder).iter() {
+                if *file == own_file || !file.starts_with(base_dir) {
+                    continue;
+                }
+                let Some(module) = imports::module_path(base_dir, file) else {
+                    continue;
+                };
+                let names = match open.get(file) {
+                    Some(names) => names,
+                    None => &*self.file_names(file),
+                };
+                importables.extend(names.iter().map(|(name, kind)| Importable {
+                    module: module.clone(),
+                    name: name.clone(),
+                    kind: *kind,
+                }));
+            }
+        }
+        importables
+    }
+
+    /// Forget the names of the file at `path`, like when it's saved.
+    pub fn file_changed(&self, path: &Path) {
+        self.names.lock().unwrap().remove(path);
+    }
+
+    /// Forget the files of the workspace, and the names of the files at
+    /// `path` or inside it, like when they're created, renamed or deleted.
+    pub fn files_changed(&self, path: &Path) {
+        self.files.lock().unwrap().clear();
+        self.names
+            .lock()
+            .unwrap()
+            .retain(|file, _| !file.starts_with(path));
+    }
+
+    fn folder_files(&self, folder: &Path) -> Arc<Vec<PathBuf>> {
+        if let Some(files) = self.files.lock().unwrap().get(folder) {
+            return files.clone();
+        }
+        // Searched without holding the lock, so other folders can be read meanwhile.
+        let files = Arc::new(bend_files(folder));
+        self.files
+            .lock()
+            .unwrap()
+            .insert(folder.to_path_buf(), files.clone());
+        files
+    }
+
+    fn file_names(&self, file: &Path) -> Arc<ModuleNames> {
+        if let Some(names) = self.names.lock().unwrap().get(file) {
+            return names.clone();
+        }
+        let names = lsp::Url::from_file_path(file)
+            .ok()
+            .and_then(Document::load)
+            .map(|doc| module_names(&doc))
+            .unwrap_or_default();
+        let names = Arc::new(names);
+        self.names
+            .lock()
+            .unwrap()
+            .insert(file.to_path_buf(), names.clone());
+        names
+    }
+}
+
+/// Names and kinds of the top-level definitions of `doc`.
+pub fn module_names(doc: &Document) -> ModuleNames {
+    definitions(doc)
+        .into_iter()
+        .map(|def| (def.name, def.kind))
+        .collect()
+}
+
+/// The imports that would bind `name`, which may be a definition of a module,
+/// a constructor of one of its types, or a definition accessed through its
+/// module, like `util/helper`.
+pub fn imports_for(importables: &[Importable], name: &str) -> Vec<ImportStatement> {
+    let mut statements = vec![];
+    let mut push = |statement: ImportStatement| {
+        if !statements.contains(&statement) {
+            statements.push(statement);
+        }
+    };
+
+    for importable in importables {
+        match importable.kind {
+            DefinitionKind::Constructor if importable.name == name => {
+                let (adt, _) = name.rsplit_once('/').unwrap_or_default();
+                push(ImportStatement::From {
+                    module: importable.module.clone(),
+                    name: adt.into(),
+                });
+            }
+            DefinitionKind::Constructor => {}
+            _ if importable.name == name => push(ImportStatement::From {
+                module: importable.module.clone(),
+                name: name.into(),
+            }),
+            _ => {}
+        }
+
+        let module_name = importable.module.rsplit('/').next().unwrap_or_default();
+        if name
+            .strip_prefix(module_name)
+            .and_then(|rest| rest.strip_prefix('/'))
+            == Some(&importable.name)
+        {
+            push(ImportStatement::Module(importable.module.clone()));
+        }
+    }
+    statements
+}
+
+/// Edit adding an import statement after the imports of a document, or at its
+/// start if it has none.
+pub fn import_edit(doc: &Document, statement: &ImportStatement) -> lsp::TextEdit {
+    let text = statement.text();
+    let insert = |position: lsp::Position, new_text: String| lsp::TextEdit {
+        range: lsp::Range::new(position, position),
+        new_text,
+    };
+
+    match imports::imports(doc).last() {
+        Some(last) if (last.range.end.line as usize) + 1 < doc.text.len_lines() => insert(
+            lsp::Position::new(last.range.end.line + 1, 0),
+            format!("{text}\n"),
+        ),
+        Some(last) => insert(last.range.end, format!("\n{text}")),
+        None if doc.text.len_chars() == 0 => insert(lsp::Position::new(0, 0), format!("{text}\n")),
+        None => insert(lsp::Position::new(0, 0), format!("{text}\n\n")),
+    }
+}
+
+#[test]
+fn auto_import_test() {
+    let root = TempDir::new("auto_import_test");
+    root.write(
+        "lib/util.bend",
+        "def helper(x):\n  return x\n\ntype Shape:\n  Circle { r }\n",
+    );
+    root.write("main.bend", "");
+
+    let code = "import Other\n\ndef main():\n  return helper(Shape/Circle(1))\n";
+    let doc = Document::new_with_text(root.url("main.bend"), code);
+    let index = ImportIndex::new();
+    let folders = [root.path().to_path_buf()];
+    let importables = index.importables(&doc.url, &folders, &HashMap::new());
+
+    let from = |name: &str| ImportStatement::From {
+        module: "lib/util".into(),
+        name: name.into(),
+    };
+    assert_eq!(imports_for(&importables, "helper"), [from("helper")]);
+    assert_eq!(imports_for(&importables, "Shape/Circle"), [from("Shape")]);
+    assert_eq!(
+        imports_for(&importables, "util/helper"),
+        [ImportStatement::Module("lib/util".into())]
+    );
+    assert_eq!(imports_for(&importables, "missing"), []);
+
+    let edit = import_edit(&doc, &from("helper"));
+    assert_eq!(edit.range.start, lsp::Position::new(1, 0));
+    assert_eq!(edit.new_text, "from lib/util import helper\n");
+
+    let names = |open: &HashMap<PathBuf, ModuleNames>| -> Vec<String> {
+        let importables = index.importables(&doc.url, &folders, open);
+        importables
+            .into_iter()
+            .map(|importable| importable.name)
+            .collect()
+    };
+
+    // Open documents are read from their buffers.
+    let util = root.join("lib/util.bend");
+    let buffer = Document::new_with_text(root.url("lib/util.bend"), "def unsaved():\n  return 1\n");
+    let open = HashMap::from([(util.clone(), module_names(&buffer))]);
+    assert_eq!(names(&open), ["unsaved"]);
+
+    // Files are read again only after they change.
+    root.write("lib/util.bend", "def saved():\n  return 1\n");
+    root.write("other.bend", "def other():\n  return 1\n");
+    assert_eq!(names(&HashMap::new()), ["helper", "Shape", "Shape/Circle"]);
+    index.file_changed(&util);
+    assert_eq!(names(&HashMap::new()), ["saved"]);
+    index.files_changed(&root.join("other.bend"));
+    let mut all = names(&HashMap::new());
+    all.sort();
+    assert_eq!(all, ["other", "saved"]);
+}
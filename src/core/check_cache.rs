@@ -0,0 +1,324 @@
+//! Cache of the compiler's checks, so files whose code and imports didn't
+//! change aren't checked again.
+//!
+//! A check's result is stored with the hashes of everything it depends on:
+//! the contents of the checked file and of every file it imported, or tried to
+//! import, the compiler's configuration and the version of Bend. Before being
+//! reused, the hashes are computed again from the files on disk.
+//!
+//! The imports loaded by a check are cached too, as the same modules are
+//! usually imported by many files.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use bend::diagnostics::Diagnostics;
+use bend::fun::Name;
+use bend::imports::{BoundSource, DefaultLoader, Import, PackageLoader, Sources};
+use tower_lsp::lsp_types as lsp;
+
+use super::diagnostics::{check_config, check_with_loader, file_path};
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+use crate::utils::panic::Panic;
+
+/// Version of Bend used to check files, set by the build script.
+const BEND_VERSION: &str = env!("BEND_VERSION");
+
+#[derive(Default)]
+pub struct CheckCache {
+    entries: Mutex<HashMap<PathBuf, Arc<CacheEntry>>>,
+    imports: Arc<Mutex<HashMap<ImportKey, Arc<LoadedImport>>>>,
+}
+
+struct CacheEntry {
+    /// Hash of the version of Bend and the compiler's configuration.
+    context: u64,
+    /// Hash of each file the check depended on, including the checked one,
+    /// when it was read.
+    dependencies: BTreeMap<PathBuf, u64>,
+    diagnostics: Diagnostics,
+}
+
+/// An import statement, by the path of the module it loads, the names it
+/// imports, and the hash of the module's file and directory.
+#[derive(PartialEq, Eq, Hash)]
+struct ImportKey {
+    path: PathBuf,
+    names: String,
+    hash: u64,
+}
+
+/// The modules loaded by an import statement.
+struct LoadedImport {
+    /// Hash of each file the import depended on, when it was read.
+    dependencies: BTreeMap<PathBuf, u64>,
+    src: BoundSource,
+    sources: Sources,
+}
+
+impl CheckCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check a Bend file, reusing the previous result if nothing it depends
+    /// on changed.
+    pub fn check(&self, url: &lsp::Url) -> Result<Diagnostics, Panic> {
+        let path = file_path(url);
+        // Files are hashed without holding the lock, so checks of other files
+        // don't wait for it.
+        let entry = self.entries.lock().unwrap().get(&path).cloned();
+        if let Some(entry) = entry.filter(|entry| entry.is_fresh()) {
+            return Ok(entry.diagnostics.clone());
+        }
+
+        // Hashed before checking, so changes made while checking invalidate the result.
+        let dependencies = BTreeMap::from([(path.clone(), file_hash(&path))]);
+        let dependencies = Arc::new(Mutex::new(dependencies));
+        let loader = CachingLoader {
+            entry_path: path.clone(),
+            local_path: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            loaded: HashSet::new(),
+            imports: self.imports.clone(),
+            dependencies: dependencies.clone(),
+        };
+        let diagnostics = check_with_loader(url, loader)?;
+
+        let dependencies = std::mem::take(&mut *dependencies.lock().unwrap());
+        let entry = CacheEntry {
+            context: context_hash(),
+            dependencies,
+            diagnostics: diagnostics.clone(),
+        };
+        self.entries.lock().unwrap().insert(path, Arc::new(entry));
+        Ok(diagnostics)
+    }
+
+    /// Whether the check of `url` is cached, and nothing it depends on changed
+    /// since, so checking it again gives the same result.
+    pub fn is_fresh(&self, url: &lsp::Url) -> bool {
+        let path = file_path(url);
+        let entry = self.entries.lock().unwrap().get(&path).cloned();
+        entry.is_some_and(|entry| entry.is_fresh())
+    }
+
+    /// Forget the check of the file at `path`, like when it's closed, keeping
+    /// the imports other checks share.
+    pub fn evict_check(&self, path: &Path) {
+        self.entries.lock().unwrap().remove(path);
+    }
+
+    /// Forget the checks of the files at `path`, or inside it if it's a
+    /// directory, and the imports of the modules there, like when they're
+    /// deleted.
+    pub fn evict(&self, path: &Path) {
+        let module = path.with_extension("");
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|file, _| !file.starts_with(path));
+        self.imports
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.path.starts_with(path) && !key.path.starts_with(&module));
+    }
+}
+
+impl CacheEntry {
+    fn is_fresh(&self) -> bool {
+        self.context == context_hash() && is_unchanged(&self.dependencies)
+    }
+}
+
+fn context_hash() -> u64 {
+    let mut hasher = DefaultHasher::new();
+    BEND_VERSION.hash(&mut hasher);
+    format!("{:?}", check_config()).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether the files still have the same hashes.
+fn is_unchanged(dependencies: &BTreeMap<PathBuf, u64>) -> bool {
+    dependencies
+        .iter()
+        .all(|(file, hash)| file_hash(file) == *hash)
+}
+
+/// Hash of the current contents of a file.
+///
+/// Directories are hashed by the files they contain, so new modules are
+/// noticed by imports of the whole directory.
+fn file_hash(path: &Path) -> u64 {
+    if path.is_dir() {
+        let mut files: Vec<_> = std::fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.file_name())
+            .collect();
+        files.sort();
+        let mut hasher = DefaultHasher::new();
+        files.hash(&mut hasher);
+        hasher.finish()
+    } else {
+        content_hash(std::fs::read_to_string(path).ok().as_deref())
+    }
+}
+
+fn content_hash(content: Option<&str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Package loader that reuses the imports loaded by previous checks, and
+/// records the files each check depends on.
+struct CachingLoader {
+    entry_path: PathBuf,
+    local_path: PathBuf,
+    /// Modules already given to the compiler, which must not be loaded twice.
+    loaded: HashSet<Name>,
+    imports: Arc<Mutex<HashMap<ImportKey, Arc<LoadedImport>>>>,
+    dependencies: Arc<Mutex<BTreeMap<PathBuf, u64>>>,
+}
+
+impl CachingLoader {
+    /// Load the modules of an import with a new loader, so that none of
+    /// them are left out for being loaded before.
+    fn load_import(&self, import: &Import) -> Result<LoadedImport, String> {
+        let path = self.local_path.join(import.path.as_ref());
+        // Whether the import is a file, a directory, or both, decides what is
+        // imported, so both are dependencies, even when they don't exist.
+        let mut dependencies: BTreeMap<PathBuf, u64> = [path.with_extension("bend"), path]
+            .into_iter()
+            .map(|dependency| {
+                let hash = file_hash(&dependency);
+                (dependency, hash)
+            })
+            .collect();
+
+        let mut import = import.clone();
+        let sources = DefaultLoader::new(&self.entry_path).load(&mut import)?;
+        // The loaded code is what the compiler sees, even if the file changed since.
+        for (name, code) in &sources {
+            let file = self.local_path.join(format!("{name}.bend"));
+            dependencies.insert(file, content_hash(Some(code)));
+        }
+        Ok(LoadedImport {
+            dependencies,
+            src: import.src,
+            sources,
+        })
+    }
+}
+
+impl PackageLoader for CachingLoader {
+    fn load(&mut self, import: &mut Import) -> Result<Sources, String> {
+        let path = self.local_path.join(import.path.as_ref());
+        let mut hasher = DefaultHasher::new();
+        file_hash(&path.with_extension("bend")).hash(&mut hasher);
+        file_hash(&path).hash(&mut hasher);
+        let key = ImportKey {
+            path,
+            names: format!("{:?}", import.imp_type),
+            hash: hasher.finish(),
+        };
+
+        // Imports of a module from the checked file itself are an error the
+        // loader reports.
+        let entry_point = Name::new(
+            self.entry_path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy(),
+        );
+        let cached = self.imports.lock().unwrap().get(&key).cloned();
+        let loaded = match cached.filter(|loaded| {
+            !loaded.sources.contains_key(&entry_point) && is_unchanged(&loaded.dependencies)
+        }) {
+            Some(loaded) => loaded,
+            None => {
+                let loaded = Arc::new(self.load_import(import)?);
+                let mut imports = self.imports.lock().unwrap();
+                imports.retain(|other, _| other.path != key.path || other.names != key.names);
+                imports.insert(key, loaded.clone());
+                loaded
+            }
+        };
+
+        let mut dependencies = self.dependencies.lock().unwrap();
+        for (file, hash) in &loaded.dependencies {
+            dependencies.entry(file.clone()).or_insert(*hash);
+        }
+        import.src = loaded.src.clone();
+        Ok(loaded
+            .sources
+            .iter()
+            .filter(|(name, _)| self.loaded.insert((*name).clone()))
+            .map(|(name, code)| (name.clone(), code.clone()))
+            .collect())
+    }
+}
+
+#[test]
+fn check_cache_test() {
+    let dir = TempDir::new("check_cache_test");
+    dir.write("Lib.bend", "def foo():\n  return 1\n");
+    dir.write(
+        "main.bend",
+        "from Lib import foo\n\ndef main():\n  return foo()\n",
+    );
+
+    let url = dir.url("main.bend");
+    let cache = CheckCache::new();
+    assert!(!cache.is_fresh(&url));
+    assert!(!cache.check(&url).unwrap().has_errors());
+    assert!(cache.is_fresh(&url));
+
+    let entries = cache.entries.lock().unwrap();
+    let dependencies = &entries[&dir.join("main.bend")].dependencies;
+    assert!(dependencies.contains_key(&dir.join("Lib.bend")));
+    drop(entries);
+
+    // Files importing the same module share its loaded import.
+    dir.write(
+        "other.bend",
+        "from Lib import foo\n\ndef main():\n  return foo()\n",
+    );
+    let other_url = dir.url("other.bend");
+    assert!(!cache.check(&other_url).unwrap().has_errors());
+    assert_eq!(cache.imports.lock().unwrap().len(), 1);
+
+    // Changing an import invalidates the cached check.
+    dir.write("Lib.bend", "def foo():\n  return x\n");
+    assert!(!cache.is_fresh(&url));
+    assert!(cache.check(&url).unwrap().has_errors());
+    assert_eq!(cache.imports.lock().unwrap().len(), 1);
+
+    // Closed files are forgotten, but not the imports other files share.
+    cache.evict_check(&dir.join("other.bend"));
+    assert!(!cache.is_fresh(&other_url));
+    assert!(cache.is_fresh(&url));
+    assert_eq!(cache.imports.lock().unwrap().len(), 1);
+
+    // Deleted modules are forgotten along with their imports.
+    cache.evict(&dir.join("Lib.bend"));
+    assert!(cache
+        .entries
+        .lock()
+        .unwrap()
+        .contains_key(&dir.join("main.bend")));
+    assert!(cache.imports.lock().unwrap().is_empty());
+
+    // Checks are stored by the decoded path of the file, as they're evicted.
+    let spaced = dir.write("my lib/main.bend", "def main():\n  return 1\n");
+    let spaced_url = dir.url("my lib/main.bend");
+    assert!(!cache.check(&spaced_url).unwrap().has_errors());
+    assert!(cache.is_fresh(&spaced_url));
+    cache.evict(&spaced);
+    assert!(!cache.is_fresh(&spaced_url));
+}
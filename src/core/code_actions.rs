@@ -0,0 +1,206 @@
+//! Code actions offered by the language server.
+
+use std::ops::Range;
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::auto_import::Importable;
+use super::document::Document;
+use super::{
+    convert_syntax, extract_function, fold_match, inline_function, missing_cases, organize_imports,
+    quick_fixes as fixes, suppression, with_blocks,
+};
+
+/// Code actions for the code at `range` and the diagnostics in a code action
+/// request.
+///
+/// Only the actions of the kinds in `only` are computed, if the client asks
+/// for some kinds. Names that don't exist may be imported from the
+/// `importables` of other modules.
+pub fn code_actions(
+    doc: &Document,
+    range: lsp::Range,
+    diagnostics: &[lsp::Diagnostic],
+    only: Option<&[lsp::CodeActionKind]>,
+    importables: &[Importable],
+) -> Vec<lsp::CodeActionOrCommand> {
+    let wants = |kind: lsp::CodeActionKind| is_requested(only, &kind);
+    let mut actions = vec![];
+    if wants(lsp::CodeActionKind::QUICKFIX) {
+        actions.extend(quick_fixes(doc, diagnostics, importables));
+        actions.extend(
+            missing_cases::add_missing_cases(doc, range, diagnostics)
+                .map(lsp::CodeActionOrCommand::CodeAction),
+        );
+    }
+    if wants(lsp::CodeActionKind::REFACTOR_REWRITE) {
+        actions
+            .extend(fold_match::fold_match(doc, range).map(lsp::CodeActionOrCommand::CodeAction));
+        actions
+            .extend(with_blocks::with_blocks(doc, range).map(lsp::CodeActionOrCommand::CodeAction));
+        actions.extend(
+            convert_syntax::convert_syntax(doc, range)
+                .into_iter()
+                .map(lsp::CodeActionOrCommand::CodeAction),
+        );
+    }
+    if wants(lsp::CodeActionKind::REFACTOR_EXTRACT) {
+        actions.extend(
+            extract_function::extract_function(doc, range)
+                .map(lsp::CodeActionOrCommand::CodeAction),
+        );
+    }
+    if wants(lsp::CodeActionKind::REFACTOR_INLINE) {
+        actions.extend(
+            inline_function::inline_function(doc, range)
+                .into_iter()
+                .map(lsp::CodeActionOrCommand::CodeAction),
+        );
+    }
+    if wants(lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS) {
+        actions.extend(
+            organize_imports::organize_imports(doc).map(lsp::CodeActionOrCommand::CodeAction),
+        );
+    }
+    actions
+}
+
+/// Whether the client asks for actions of `kind`, either by that kind or by a
+/// more general one, like `refactor` for `refactor.inline`.
+fn is_requested(only: Option<&[lsp::CodeActionKind]>, kind: &lsp::CodeActionKind) -> bool {
+    let Some(only) = only else {
+        return true;
+    };
+    only.iter().any(|requested| {
+        let requested = requested.as_str();
+        kind.as_str() == requested
+            || kind
+                .as_str()
+                .strip_prefix(requested)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// Quick fixes for the diagnostics in a code action request.
+///
+/// Fixes for each diagnostic come before the action to suppress it.
+pub fn quick_fixes(
+    doc: &Document,
+    diagnostics: &[lsp::Diagnostic],
+    importables: &[Importable],
+) -> Vec<lsp::CodeActionOrCommand> {
+    diagnostics
+        .iter()
+        .filter(|diag| diag.source.as_deref() == Some("bend"))
+        .flat_map(|diag| {
+            fixes::quick_fixes(doc, diag, importables)
+                .into_iter()
+                .chain(suppression::suppress_action(doc, diag))
+        })
+        .map(lsp::CodeActionOrCommand::CodeAction)
+        .collect()
+}
+
+/// The node spanning `range`.
+pub(crate) fn node_at(doc: &Document, range: lsp::Range) -> Option<ts::Node<'_>> {
+    let point = |position: lsp::Position| {
+        ts::Point::new(position.line as usize, position.character as usize)
+    };
+    doc.tree
+        .as_ref()?
+        .root_node()
+        .descendant_for_point_range(point(range.start), point(range.end))
+}
+
+/// Whitespace at the start of the line of a node.
+pub(crate) fn indentation(doc: &Document, node: ts::Node) -> String {
+    doc.text
+        .line(node.start_position().row)
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// The text of `node`, with the text of the descendants for which `replace`
+/// returns something replaced by it.
+pub(crate) fn rewrite(
+    doc: &Document,
+    node: ts::Node,
+    replace: &dyn Fn(ts::Node) -> Option<String>,
+) -> String {
+    if let Some(text) = replace(node) {
+        return text;
+    }
+    let slice = |range: Range<usize>| doc.text.byte_slice(range).to_string();
+    let mut text = String::new();
+    let mut end = node.start_byte();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        text += &slice(end..child.start_byte());
+        text += &rewrite(doc, child, replace);
+        end = child.end_byte();
+    }
+    text += &slice(end..node.end_byte());
+    text
+}
+
+/// Text of the document after applying the edits of a code action.
+#[cfg(test)]
+pub(crate) fn apply_fix(doc: &Document, action: &lsp::CodeAction) -> String {
+    let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+    // Applied from the end, keeping the order of inserts at the same position.
+    let mut edits: Vec<_> = changes[&doc.url].iter().cloned().enumerate().collect();
+    edits.sort_by_key(|(index, edit)| std::cmp::Reverse((edit.range.start, *index)));
+
+    let mut text = doc.text.clone();
+    let char_idx = |text: &ropey::Rope, position: lsp::Position| {
+        let line = (position.line as usize).min(text.len_lines());
+        if line == text.len_lines() {
+            return text.len_chars();
+        }
+        text.line_to_char(line) + position.character as usize
+    };
+    for (_, edit) in edits {
+        let start = char_idx(&text, edit.range.start);
+        let end = char_idx(&text, edit.range.end);
+        text.remove(start..end);
+        text.insert(start, &edit.new_text);
+    }
+    text.to_string()
+}
+
+#[test]
+fn code_action_kinds_test() {
+    let code = "import B\nimport A\n\ndef main():\n  return 1\n";
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let range = lsp::Range::new(lsp::Position::new(4, 9), lsp::Position::new(4, 9));
+    let kinds = |only: Option<&[lsp::CodeActionKind]>| -> Vec<lsp::CodeActionKind> {
+        code_actions(&doc, range, &[], only, &[])
+            .into_iter()
+            .filter_map(|action| match action {
+                lsp::CodeActionOrCommand::CodeAction(action) => action.kind,
+                lsp::CodeActionOrCommand::Command(_) => None,
+            })
+            .collect()
+    };
+
+    assert!(kinds(None).contains(&lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS));
+    assert_eq!(
+        kinds(Some(&[lsp::CodeActionKind::SOURCE])),
+        [lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS]
+    );
+    assert!(!kinds(Some(&[lsp::CodeActionKind::REFACTOR]))
+        .contains(&lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS));
+    assert!(kinds(Some(&[lsp::CodeActionKind::QUICKFIX])).is_empty());
+
+    let inline = lsp::CodeActionKind::REFACTOR_INLINE;
+    assert!(is_requested(
+        Some(&[lsp::CodeActionKind::REFACTOR]),
+        &inline
+    ));
+    assert!(!is_requested(
+        Some(&[lsp::CodeActionKind::new("refactor.in")]),
+        &inline
+    ));
+}
@@ -0,0 +1,118 @@
+//! Completion of the names in scope at a position, and of the names other
+//! modules define, which are imported when the completion is accepted.
+
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types as lsp;
+
+use super::auto_import::{self, ImportStatement, Importable};
+use super::definitions::{definitions, DefinitionKind};
+use super::document::Document;
+use super::imports;
+use super::lints::scopes;
+
+/// Completion items for the names that can be used at `position`, including
+/// the `importables` of other modules.
+pub fn completions(
+    doc: &Document,
+    position: lsp::Position,
+    importables: &[Importable],
+) -> Vec<lsp::CompletionItem> {
+    let mut items = vec![];
+    let mut bound = HashSet::new();
+    let mut push = |items: &mut Vec<lsp::CompletionItem>, label: String, kind| {
+        if bound.insert(label.clone()) {
+            items.push(lsp::CompletionItem {
+                label,
+                kind: Some(kind),
+                ..Default::default()
+            });
+        }
+    };
+
+    if let Some(byte) = byte_offset(doc, position) {
+        for binding in scopes::bindings(doc) {
+            if binding.scope.contains(&byte) {
+                push(&mut items, binding.name, lsp::CompletionItemKind::VARIABLE);
+            }
+        }
+    }
+    for def in definitions(doc) {
+        let kind = match def.kind {
+            DefinitionKind::Function | DefinitionKind::Hvm => lsp::CompletionItemKind::FUNCTION,
+            DefinitionKind::Type => lsp::CompletionItemKind::ENUM,
+            DefinitionKind::Constructor => lsp::CompletionItemKind::CONSTRUCTOR,
+            DefinitionKind::Object => lsp::CompletionItemKind::STRUCT,
+        };
+        push(&mut items, def.name, kind);
+    }
+    for import in imports::imports(doc) {
+        let kind = match import.from {
+            Some(_) => lsp::CompletionItemKind::REFERENCE,
+            None => lsp::CompletionItemKind::MODULE,
+        };
+        for item in import.items {
+            push(&mut items, item.bind().to_string(), kind);
+        }
+    }
+
+    // Names of other modules, with the import that binds them.
+    for importable in importables {
+        if bound.contains(&importable.name) {
+            continue;
+        }
+        let (name, kind) = match importable.kind {
+            DefinitionKind::Constructor => {
+                let (adt, _) = importable.name.rsplit_once('/').unwrap_or_default();
+                (adt.to_string(), lsp::CompletionItemKind::CONSTRUCTOR)
+            }
+            DefinitionKind::Type => (importable.name.clone(), lsp::CompletionItemKind::ENUM),
+            DefinitionKind::Object => (importable.name.clone(), lsp::CompletionItemKind::STRUCT),
+            _ => (importable.name.clone(), lsp::CompletionItemKind::FUNCTION),
+        };
+        let statement = ImportStatement::From {
+            module: importable.module.clone(),
+            name,
+        };
+        items.push(lsp::CompletionItem {
+            label: importable.name.clone(),
+            kind: Some(kind),
+            detail: Some(statement.text()),
+            additional_text_edits: Some(vec![auto_import::import_edit(doc, &statement)]),
+            ..Default::default()
+        });
+    }
+
+    items
+}
+
+/// Byte of the document at a position.
+fn byte_offset(doc: &Document, position: lsp::Position) -> Option<usize> {
+    let line = doc.text.try_line_to_char(position.line as usize).ok()?;
+    doc.text
+        .try_char_to_byte(line + position.character as usize)
+        .ok()
+}
+
+#[test]
+fn completions_test() {
+    let importable = |name: &str| Importable {
+        module: "util".into(),
+        name: name.into(),
+        kind: DefinitionKind::Function,
+    };
+    let importables = [importable("helper"), importable("local")];
+
+    let code = "def local(a):\n  b = a\n  return b\n\ndef main():\n  return 0\n";
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let items = completions(&doc, lsp::Position::new(2, 9), &importables);
+    let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+
+    // The `local` of the other module is shadowed by the document's own.
+    assert_eq!(labels, ["a", "b", "local", "main", "helper"]);
+    let helper = &items[4];
+    assert_eq!(helper.detail.as_deref(), Some("from util import helper"));
+    let edits = helper.additional_text_edits.as_ref().unwrap();
+    assert_eq!(edits[0].range.start, lsp::Position::new(0, 0));
+    assert_eq!(edits[0].new_text, "from util import helper\n\n");
+}
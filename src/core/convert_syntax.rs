@@ -0,0 +1,989 @@
+//! Code actions to convert definitions between Bend's imperative and
+//! functional syntaxes.
+//!
+//! Definitions are parsed with Bend's parser, and imperative ones are turned
+//! into functional terms by the compiler itself. Both syntaxes are then printed
+//! from those terms, so definitions with code that can't be printed back, like
+//! list comprehensions, are not converted.
+//!
+//! A `match` on a parameter of an imperative function becomes one pattern
+//! matching rule for each case, and the other way around. Comments inside a
+//! definition are kept above the converted one.
+
+use std::collections::HashMap;
+
+use bend::fun::parser::{FunParser, ParseBook};
+use bend::fun::{FanKind, Name, Num, Pattern, Rule, Tag, Term};
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::definitions::{definitions, DefinitionKind};
+use super::document::Document;
+use super::syntax_errors::valid_definitions;
+use super::types::{adt_of_constructor, adts, Adt};
+use crate::utils::panic::catch_panic;
+use crate::utils::tree::descendants;
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    Imperative,
+    Functional,
+}
+
+impl Syntax {
+    fn other(self) -> Syntax {
+        match self {
+            Syntax::Imperative => Syntax::Functional,
+            Syntax::Functional => Syntax::Imperative,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Syntax::Imperative => "imperative",
+            Syntax::Functional => "functional",
+        }
+    }
+}
+
+/// A function to convert: an imperative definition, or all the rules of a
+/// functional one.
+struct Function<'t> {
+    name: String,
+    syntax: Syntax,
+    nodes: Vec<ts::Node<'t>>,
+}
+
+/// Actions to convert the functions at `range`, and the whole file, to the
+/// other syntax.
+pub fn convert_syntax(doc: &Document, range: lsp::Range) -> Vec<lsp::CodeAction> {
+    let functions = functions(doc);
+    let adts = adts(doc);
+    let mut actions = vec![];
+
+    for function in &functions {
+        let selected = function.nodes.iter().any(|node| {
+            let node_range = doc.node_range(node);
+            node_range.start <= range.end && range.start <= node_range.end
+        });
+        if !selected {
+            continue;
+        }
+        if let Some(edits) = convert(doc, &adts, function) {
+            let title = format!(
+                "Convert '{}' to {} syntax",
+                function.name,
+                function.syntax.other().name()
+            );
+            actions.push(action(doc, title, edits));
+        }
+    }
+
+    for target in [Syntax::Functional, Syntax::Imperative] {
+        let edits: Vec<lsp::TextEdit> = functions
+            .iter()
+            .filter(|function| function.syntax != target)
+            .filter_map(|function| convert(doc, &adts, function))
+            .flatten()
+            .collect();
+        if !edits.is_empty() {
+            let title = format!("Convert file to {} syntax", target.name());
+            actions.push(action(doc, title, edits));
+        }
+    }
+    actions
+}
+
+fn action(doc: &Document, title: String, edits: Vec<lsp::TextEdit>) -> lsp::CodeAction {
+    lsp::CodeAction {
+        title,
+        kind: Some(lsp::CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// The functions of the document, without syntax errors.
+fn functions(doc: &Document) -> Vec<Function<'_>> {
+    let valid = valid_definitions(doc);
+    let mut functions: Vec<Function> = vec![];
+    for def in definitions(doc) {
+        if def.kind != DefinitionKind::Function {
+            continue;
+        }
+        let syntax = match def.node.kind() {
+            "imp_function_definition" => Syntax::Imperative,
+            _ => Syntax::Functional,
+        };
+        let rules = functions.iter_mut().find(|function| {
+            function.syntax == Syntax::Functional
+                && syntax == Syntax::Functional
+                && function.name == def.name
+        });
+        match rules {
+            Some(function) => function.nodes.push(def.node),
+            None => functions.push(Function {
+                name: def.name,
+                syntax,
+                nodes: vec![def.node],
+            }),
+        }
+    }
+    functions.retain(|function| function.nodes.iter().all(|node| valid.contains(node)));
+
+    // Tree-sitter may merge rules with broken code around them, so when Bend
+    // can parse the document, the rules it sees must be the same.
+    if let Some(book) = parse(&doc.text.to_string()) {
+        functions.retain(|function| match function.syntax {
+            Syntax::Imperative => book.imp_defs.contains_key(&Name::new(&function.name)),
+            Syntax::Functional => book
+                .fun_defs
+                .get(&Name::new(&function.name))
+                .is_some_and(|def| def.rules.len() == function.nodes.len()),
+        });
+    }
+    functions
+}
+
+/// Edits replacing a function with its conversion to the other syntax.
+fn convert(doc: &Document, adts: &[Adt], function: &Function) -> Option<Vec<lsp::TextEdit>> {
+    let text: Vec<String> = function
+        .nodes
+        .iter()
+        .map(|node| doc.node_text(node))
+        .collect();
+    let converted = match function.syntax {
+        Syntax::Imperative => imp_to_fun(&text.join("\n"), adts)?,
+        Syntax::Functional => fun_to_imp(&text.join("\n"), adts)?,
+    };
+
+    let comments: String = function
+        .nodes
+        .iter()
+        .flat_map(|node| descendants(*node))
+        .filter(|node| matches!(node.kind(), "comment" | "multiline_comment"))
+        .map(|node| format!("{}\n", doc.node_text(&node).trim_end()))
+        .collect();
+
+    let (first, rest) = function.nodes.split_first()?;
+    let mut edits = vec![lsp::TextEdit {
+        range: doc.node_range(first),
+        new_text: comments + &converted,
+    }];
+    // Other rules are removed with their lines.
+    for node in rest {
+        let range = doc.node_range(node);
+        edits.push(lsp::TextEdit {
+            range: lsp::Range::new(
+                lsp::Position::new(range.start.line, 0),
+                lsp::Position::new(range.end.line + 1, 0),
+            ),
+            new_text: String::new(),
+        });
+    }
+    Some(edits)
+}
+
+fn parse(code: &str) -> Option<ParseBook> {
+    FunParser::new(Name::new(""), code, false)
+        .parse_book(ParseBook::default())
+        .ok()
+}
+
+/// Convert an imperative definition to functional rules.
+fn imp_to_fun(code: &str, adts: &[Adt]) -> Option<String> {
+    let (name, mut def) = parse(code)?.imp_defs.pop()?;
+    // The desugaring asserts things that are only checked later by the compiler.
+    let def = catch_panic(move || {
+        def.gen_map_get();
+        def.to_fun()
+    })
+    .ok()?
+    .ok()?;
+    let rule = def.rules.into_iter().next()?;
+
+    let rules = match_to_rules(&rule, adts).unwrap_or_else(|| vec![rule]);
+    let rules: Option<Vec<String>> = rules
+        .iter()
+        .map(|rule| {
+            let pats: Option<Vec<String>> = rule.pats.iter().map(fun_pattern).collect();
+            let pats = pats?;
+            let head = match pats.is_empty() {
+                true => name.to_string(),
+                false => format!("({name} {})", pats.join(" ")),
+            };
+            let body = fun_term(&rule.body, 2)?;
+            Some(match body.contains('\n') {
+                true => format!("{head} =\n  {body}"),
+                false => format!("{head} = {body}"),
+            })
+        })
+        .collect();
+    Some(rules?.join("\n"))
+}
+
+/// Split a function whose body is a `match` on one of its parameters into one
+/// rule for each case, binding the fields of the constructors.
+fn match_to_rules(rule: &Rule, adts: &[Adt]) -> Option<Vec<Rule>> {
+    let Term::Mat {
+        bnd: Some(bnd),
+        arg,
+        with_bnd,
+        arms,
+        ..
+    } = &rule.body
+    else {
+        return None;
+    };
+    let param = rule
+        .pats
+        .iter()
+        .position(|pat| matches!(pat, Pattern::Var(Some(name)) if name == bnd))?;
+    if !matches!(&**arg, Term::Var { nam } if nam == bnd) || !with_bnd.is_empty() {
+        return None;
+    }
+
+    let mut rules = vec![];
+    for (i, (ctr, _, body)) in arms.iter().enumerate() {
+        let mut body = body.clone();
+        let pat = match ctr {
+            Some(ctr) => {
+                let fields = &adt_of_constructor(adts, ctr)?.constructor(ctr)?.fields;
+                let mut pats = vec![];
+                for field in fields {
+                    let name = Name::new(field.name.as_str());
+                    if mentions(&body, &name)
+                        || rule.pats.contains(&Pattern::Var(Some(name.clone())))
+                    {
+                        return None;
+                    }
+                    let dotted = Name::new(format!("{bnd}.{}", field.name));
+                    body.subst(&dotted, &Term::Var { nam: name.clone() });
+                    pats.push(Pattern::Var(Some(name)));
+                }
+                // Only the fields are bound by the rule.
+                if body.free_vars().contains_key(bnd) {
+                    return None;
+                }
+                Pattern::Ctr(ctr.clone(), pats)
+            }
+            None if i == arms.len() - 1 => Pattern::Var(Some(bnd.clone())),
+            None => return None,
+        };
+        let mut pats = rule.pats.clone();
+        pats[param] = pat;
+        rules.push(Rule { pats, body });
+    }
+    Some(rules)
+}
+
+/// Convert the rules of a functional definition to an imperative one.
+fn fun_to_imp(code: &str, adts: &[Adt]) -> Option<String> {
+    let (name, def) = parse(code)?.fun_defs.pop()?;
+    let (params, body) = rules_to_match(def.rules, adts)?;
+    let params: Option<Vec<String>> = params.iter().map(name_text).collect();
+    let body = imp_statements(&body, 2)?;
+    Some(format!(
+        "def {name}({}):\n{}",
+        params?.join(", "),
+        body.trim_end()
+    ))
+}
+
+/// Parameters and body of the imperative version of some functional rules.
+///
+/// Rules matching on the constructors of a parameter become a `match` on it,
+/// with the fields bound by the rules replaced by the fields of the match.
+fn rules_to_match(rules: Vec<Rule>, adts: &[Adt]) -> Option<(Vec<Name>, Term)> {
+    let is_constructor = |name: &Name| adt_of_constructor(adts, name).is_some();
+    let is_variable = |pat: &Pattern| {
+        matches!(pat, Pattern::Var(None))
+            || matches!(pat, Pattern::Var(Some(name)) if !is_constructor(name))
+    };
+
+    if let [rule] = rules.as_slice() {
+        if rule.pats.iter().all(is_variable) {
+            let mut params: Vec<Name> = rule
+                .pats
+                .iter()
+                .map(|pat| match pat {
+                    Pattern::Var(Some(name)) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect::<Option<_>>()?;
+            // Lambdas of a definition without patterns become parameters.
+            let mut body = &rule.body;
+            while let (true, Term::Lam { tag, pat, bod }) = (rule.pats.is_empty(), body) {
+                match (tag, &**pat) {
+                    (Tag::Static | Tag::Auto, Pattern::Var(Some(name))) => {
+                        params.push(name.clone())
+                    }
+                    _ => break,
+                }
+                body = bod;
+            }
+            return Some((params, body.clone()));
+        }
+    }
+
+    let arity = rules.first()?.pats.len();
+    if rules.iter().any(|rule| rule.pats.len() != arity) {
+        return None;
+    }
+    let mut matched = (0..arity).filter(|&i| rules.iter().any(|rule| !is_variable(&rule.pats[i])));
+    let column = matched.next()?;
+    if matched.next().is_some() {
+        return None;
+    }
+
+    let param_name = |i: usize| {
+        rules.iter().find_map(|rule| match &rule.pats[i] {
+            Pattern::Var(Some(name)) if !is_constructor(name) => Some(name.clone()),
+            _ => None,
+        })
+    };
+    let mut params: Vec<Name> = (0..arity)
+        .map(|i| param_name(i).unwrap_or_else(|| Name::new(format!("arg{}", i + 1))))
+        .collect();
+    if param_name(column).is_none() {
+        let ctr = rules.iter().find_map(|rule| match &rule.pats[column] {
+            Pattern::Ctr(name, _) | Pattern::Var(Some(name)) => Some(name),
+            _ => None,
+        })?;
+        let adt = adt_of_constructor(adts, ctr)?;
+        let mut name = adt.name.rsplit('/').next()?.to_lowercase();
+        while params.iter().any(|param| **param == *name) {
+            name.push('_');
+        }
+        params[column] = Name::new(name);
+    }
+
+    let rename = |body: &mut Term, from: &Name, to: &Name| {
+        if from != to {
+            if mentions(body, to) {
+                return None;
+            }
+            body.subst(from, &Term::Var { nam: to.clone() });
+        }
+        Some(())
+    };
+    let mut arms: Vec<(Option<Name>, Vec<Option<Name>>, Term)> = vec![];
+    for (i, rule) in rules.iter().enumerate() {
+        let mut body = rule.body.clone();
+        for (j, pat) in rule.pats.iter().enumerate() {
+            if let (false, Pattern::Var(Some(name))) = (j == column, pat) {
+                rename(&mut body, name, &params[j])?;
+            }
+        }
+
+        let param = &params[column];
+        let ctr = match &rule.pats[column] {
+            Pattern::Var(Some(name)) if is_constructor(name) => Some((name, &[][..])),
+            Pattern::Ctr(name, fields) => Some((name, &fields[..])),
+            _ => None,
+        };
+        let arm = match ctr {
+            Some((ctr, fields)) => {
+                let ctr_fields = &adt_of_constructor(adts, ctr)?.constructor(ctr)?.fields;
+                if fields.len() != ctr_fields.len()
+                    || arms.iter().any(|(name, _, _)| name.as_ref() == Some(ctr))
+                {
+                    return None;
+                }
+                for (field, ctr_field) in fields.iter().zip(ctr_fields) {
+                    match field {
+                        Pattern::Var(None) => {}
+                        Pattern::Var(Some(name)) => {
+                            let dotted = Name::new(format!("{param}.{}", ctr_field.name));
+                            rename(&mut body, name, &dotted)?;
+                        }
+                        _ => return None,
+                    }
+                }
+                Some(ctr.clone())
+            }
+            // A variable matches every remaining constructor.
+            None if i == rules.len() - 1 => {
+                if let Pattern::Var(Some(name)) = &rule.pats[column] {
+                    rename(&mut body, name, param)?;
+                }
+                None
+            }
+            None => return None,
+        };
+        arms.push((arm, vec![], body));
+    }
+
+    let body = Term::Mat {
+        bnd: Some(params[column].clone()),
+        arg: Box::new(Term::Var {
+            nam: params[column].clone(),
+        }),
+        with_bnd: vec![],
+        with_arg: vec![],
+        arms,
+    };
+    Some((params, body))
+}
+
+/// Whether a name is used or bound anywhere in a term.
+fn mentions(term: &Term, name: &Name) -> bool {
+    if let Term::Var { nam } = term {
+        if nam == name {
+            return true;
+        }
+    }
+    term.children_with_binds().any(|(child, mut binds)| {
+        binds.any(|bind| bind.as_ref() == Some(name)) || mentions(child, name)
+    })
+}
+
+/// A name, if it can be written in Bend code.
+///
+/// Names generated by the compiler's desugaring, like `%pred`, can't.
+fn name_text(name: &Name) -> Option<String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/'));
+    valid.then(|| name.to_string())
+}
+
+/// Whether the binder of a `match`, `switch` or `fold` was generated for an
+/// argument that isn't a variable, so it's not written.
+fn is_generated(bnd: &Option<Name>) -> bool {
+    bnd.as_ref().is_none_or(|bnd| bnd.starts_with('%'))
+}
+
+fn num_text(num: &Num) -> String {
+    match num {
+        Num::U24(val) => val.to_string(),
+        Num::I24(val) => format!("{}{}", if *val < 0 { "-" } else { "+" }, val.abs()),
+        Num::F24(val) => format!("{val:?}"),
+    }
+}
+
+fn is_plain(tag: &Tag) -> bool {
+    matches!(tag, Tag::Static | Tag::Auto)
+}
+
+/// Flatten an application into the applied term and its arguments.
+fn flatten_app(term: &Term) -> Option<(&Term, Vec<&Term>)> {
+    let mut args = vec![];
+    let mut head = term;
+    while let Term::App { tag, fun, arg } = head {
+        if !is_plain(tag) {
+            return None;
+        }
+        args.push(&**arg);
+        head = fun;
+    }
+    args.reverse();
+    Some((head, args))
+}
+
+/// The argument of a `match`, `switch` or `fold`, with its binder, and its
+/// `with` clause.
+fn match_arg(
+    bnd: &Option<Name>,
+    arg: &Term,
+    with_bnd: &[Option<Name>],
+    with_arg: &[Term],
+    term: impl Fn(&Term) -> Option<String>,
+) -> Option<String> {
+    let mut text = match (bnd, arg) {
+        _ if is_generated(bnd) => term(arg)?,
+        (Some(bnd), Term::Var { nam }) if nam == bnd => name_text(bnd)?,
+        (Some(bnd), arg) => format!("{} = {}", name_text(bnd)?, term(arg)?),
+        (None, _) => unreachable!(),
+    };
+    if !with_bnd.is_empty() {
+        let binds: Option<Vec<String>> = with_bnd
+            .iter()
+            .zip(with_arg)
+            .map(|(bnd, arg)| match (bnd, arg) {
+                (Some(bnd), Term::Var { nam }) if nam == bnd => name_text(bnd),
+                (Some(bnd), arg) => Some(format!("{} = {}", name_text(bnd)?, term(arg)?)),
+                (None, _) => None,
+            })
+            .collect();
+        text = format!("{text} with {}", binds?.join(", "));
+    }
+    Some(text)
+}
+
+fn fun_pattern(pat: &Pattern) -> Option<String> {
+    let join = |pats: &[Pattern], sep: &str| -> Option<String> {
+        let pats: Option<Vec<String>> = pats.iter().map(fun_pattern).collect();
+        Some(pats?.join(sep))
+    };
+    Some(match pat {
+        Pattern::Var(None) => "*".into(),
+        Pattern::Var(Some(name)) => name_text(name)?,
+        Pattern::Chn(name) => format!("${}", name_text(name)?),
+        Pattern::Ctr(name, pats) if pats.is_empty() => name_text(name)?,
+        Pattern::Ctr(name, pats) => format!("({} {})", name_text(name)?, join(pats, " ")?),
+        Pattern::Num(num) => num.to_string(),
+        Pattern::Fan(FanKind::Tup, tag, pats) if is_plain(tag) => {
+            format!("({})", join(pats, ", ")?)
+        }
+        Pattern::Fan(FanKind::Dup, tag, pats) if is_plain(tag) => {
+            format!("{{{}}}", join(pats, " ")?)
+        }
+        Pattern::Lst(pats) => format!("[{}]", join(pats, ", ")?),
+        Pattern::Str(val) => format!("{:?}", val.to_string()),
+        Pattern::Fan(..) => return None,
+    })
+}
+
+/// A term in functional syntax. Lines after the first are indented by `tab`.
+fn fun_term(term: &Term, tab: usize) -> Option<String> {
+    let indent = " ".repeat(tab);
+    let inner = " ".repeat(tab + 2);
+    let join = |terms: &[Term], sep: &str| -> Option<String> {
+        let terms: Option<Vec<String>> = terms.iter().map(|term| fun_term(term, tab)).collect();
+        Some(terms?.join(sep))
+    };
+    // An arm's body goes in its own line when it takes more than one.
+    let arm = |head: String, body: &Term| -> Option<String> {
+        let body = fun_term(body, tab + 4)?;
+        Some(match body.contains('\n') {
+            true => format!("\n{inner}{head}:\n{inner}  {body}"),
+            false => format!("\n{inner}{head}: {body}"),
+        })
+    };
+
+    Some(match term {
+        Term::Lam { tag, pat, bod } if is_plain(tag) => {
+            format!("@{} {}", fun_pattern(pat)?, fun_term(bod, tab)?)
+        }
+        Term::Var { nam } | Term::Ref { nam } => name_text(nam)?,
+        Term::Link { nam } => format!("${}", name_text(nam)?),
+        Term::Let { pat, val, nxt } => format!(
+            "let {} = {}\n{indent}{}",
+            fun_pattern(pat)?,
+            fun_term(val, tab)?,
+            fun_term(nxt, tab)?
+        ),
+        Term::Use {
+            nam: Some(nam),
+            val,
+            nxt,
+        } => format!(
+            "use {} = {}\n{indent}{}",
+            name_text(nam)?,
+            fun_term(val, tab)?,
+            fun_term(nxt, tab)?
+        ),
+        Term::Ask { pat, val, nxt } => format!(
+            "ask {} = {}\n{indent}{}",
+            fun_pattern(pat)?,
+            fun_term(val, tab)?,
+            fun_term(nxt, tab)?
+        ),
+        Term::With { typ, bod } => format!(
+            "with {} {{\n{inner}{}\n{indent}}}",
+            name_text(typ)?,
+            fun_term(bod, tab + 2)?
+        ),
+        Term::App { .. } => {
+            let (head, args) = flatten_app(term)?;
+            let args: Option<Vec<String>> = args.iter().map(|arg| fun_term(arg, tab)).collect();
+            format!("({} {})", fun_term(head, tab)?, args?.join(" "))
+        }
+        Term::Fan {
+            fan: FanKind::Tup,
+            tag,
+            els,
+        } if is_plain(tag) => format!("({})", join(els, ", ")?),
+        Term::Fan {
+            fan: FanKind::Dup,
+            tag,
+            els,
+        } if is_plain(tag) => format!("{{{}}}", join(els, " ")?),
+        Term::Num { val } => num_text(val),
+        Term::Nat { val } => format!("#{val}"),
+        Term::Str { val } => format!("{:?}", val.to_string()),
+        Term::List { els } => format!("[{}]", join(els, ", ")?),
+        Term::Oper { opr, fst, snd } => {
+            format!("({opr} {} {})", fun_term(fst, tab)?, fun_term(snd, tab)?)
+        }
+        Term::Mat {
+            bnd,
+            arg,
+            with_bnd,
+            with_arg,
+            arms,
+        }
+        | Term::Fold {
+            bnd,
+            arg,
+            with_bnd,
+            with_arg,
+            arms,
+        } => {
+            let keyword = match term {
+                Term::Mat { .. } => "match",
+                _ => "fold",
+            };
+            let arg = match_arg(bnd, arg, with_bnd, with_arg, |arg| fun_term(arg, tab))?;
+            let arms: Option<String> = arms
+                .iter()
+                .map(|(ctr, _, body)| {
+                    let ctr = match ctr {
+                        Some(ctr) => name_text(ctr)?,
+                        None => "_".into(),
+                    };
+                    arm(ctr, body)
+                })
+                .collect();
+            format!("{keyword} {arg} {{{}\n{indent}}}", arms?)
+        }
+        // The desugaring of `if`.
+        Term::Swt {
+            bnd,
+            arg,
+            with_bnd,
+            arms,
+            ..
+        } if is_generated(bnd) && with_bnd.is_empty() && arms.len() == 2 => format!(
+            "if {} {{\n{inner}{}\n{indent}}} else {{\n{inner}{}\n{indent}}}",
+            fun_term(arg, tab)?,
+            fun_term(&arms[1], tab + 2)?,
+            fun_term(&arms[0], tab + 2)?
+        ),
+        Term::Swt {
+            bnd,
+            arg,
+            with_bnd,
+            with_arg,
+            pred,
+            arms,
+        } => {
+            check_pred(bnd, pred, arms.len())?;
+            let arg = match_arg(bnd, arg, with_bnd, with_arg, |arg| fun_term(arg, tab))?;
+            let arms: Option<String> = arms
+                .iter()
+                .enumerate()
+                .map(|(i, body)| {
+                    let case = match i == arms.len() - 1 {
+                        true => "_".into(),
+                        false => i.to_string(),
+                    };
+                    arm(case, body)
+                })
+                .collect();
+            format!("switch {arg} {{{}\n{indent}}}", arms?)
+        }
+        Term::Bend {
+            bnd,
+            arg,
+            cond,
+            step,
+            base,
+        } => {
+            let binds: Option<Vec<String>> = bnd
+                .iter()
+                .zip(arg)
+                .map(|(bnd, arg)| {
+                    Some(format!(
+                        "{} = {}",
+                        name_text(bnd.as_ref()?)?,
+                        fun_term(arg, tab)?
+                    ))
+                })
+                .collect();
+            format!(
+                "bend {} {{\n{inner}when {}:\n{inner}  {}\n{inner}else:\n{inner}  {}\n{indent}}}",
+                binds?.join(", "),
+                fun_term(cond, tab + 2)?,
+                fun_term(step, tab + 4)?,
+                fun_term(base, tab + 4)?
+            )
+        }
+        Term::Era => "*".into(),
+        _ => return None,
+    })
+}
+
+/// Check that the predecessor of a `switch` has the name Bend gives it, as it
+/// can't be written.
+fn check_pred(bnd: &Option<Name>, pred: &Option<Name>, arms: usize) -> Option<()> {
+    match (bnd, pred) {
+        (_, None) => Some(()),
+        (Some(bnd), Some(pred)) if **pred == format!("{bnd}-{}", arms - 1) => Some(()),
+        _ => None,
+    }
+}
+
+fn imp_pattern(pat: &Pattern) -> Option<String> {
+    let join = |pats: &[Pattern]| -> Option<String> {
+        let pats: Option<Vec<String>> = pats.iter().map(imp_pattern).collect();
+        Some(pats?.join(", "))
+    };
+    Some(match pat {
+        Pattern::Var(None) => "*".into(),
+        Pattern::Var(Some(name)) => name_text(name)?,
+        Pattern::Chn(name) => format!("${}", name_text(name)?),
+        Pattern::Fan(FanKind::Tup, tag, pats) if is_plain(tag) => format!("({})", join(pats)?),
+        Pattern::Fan(FanKind::Dup, tag, pats) if is_plain(tag) => format!("{{{}}}", join(pats)?),
+        _ => return None,
+    })
+}
+
+/// An expression in imperative syntax. Lambdas and operations are wrapped in
+/// parentheses when `nested`.
+fn imp_expr(term: &Term, nested: bool) -> Option<String> {
+    let join = |terms: &[Term]| -> Option<String> {
+        let terms: Option<Vec<String>> = terms.iter().map(|term| imp_expr(term, false)).collect();
+        Some(terms?.join(", "))
+    };
+    let wrap = |text: String| match nested {
+        true => format!("({text})"),
+        false => text,
+    };
+
+    Some(match term {
+        Term::Lam { .. } => {
+            let mut names = vec![];
+            let mut body = term;
+            while let Term::Lam { tag, pat, bod } = body {
+                if !is_plain(tag) {
+                    return None;
+                }
+                names.push(match &**pat {
+                    Pattern::Var(Some(name)) => name_text(name)?,
+                    Pattern::Chn(name) => format!("${}", name_text(name)?),
+                    _ => return None,
+                });
+                body = bod;
+            }
+            wrap(format!(
+                "lambda {}: {}",
+                names.join(", "),
+                imp_expr(body, false)?
+            ))
+        }
+        Term::Var { nam } | Term::Ref { nam } => name_text(nam)?,
+        Term::Link { nam } => format!("${}", name_text(nam)?),
+        Term::App { .. } => {
+            let (head, args) = flatten_app(term)?;
+            if !matches!(head, Term::Var { .. } | Term::Ref { .. }) {
+                return None;
+            }
+            let args: Option<Vec<String>> = args.iter().map(|arg| imp_expr(arg, false)).collect();
+            format!("{}({})", imp_expr(head, true)?, args?.join(", "))
+        }
+        Term::Fan {
+            fan: FanKind::Tup,
+            tag,
+            els,
+        } if is_plain(tag) => format!("({})", join(els)?),
+        Term::Fan {
+            fan: FanKind::Dup,
+            tag,
+            els,
+        } if is_plain(tag) => format!("{{{}}}", join(els)?),
+        Term::Num { val } => num_text(val),
+        Term::Str { val } => format!("{:?}", val.to_string()),
+        Term::List { els } => format!("[{}]", join(els)?),
+        Term::Oper { opr, fst, snd } => wrap(format!(
+            "{} {opr} {}",
+            imp_expr(fst, true)?,
+            imp_expr(snd, true)?
+        )),
+        Term::Era => "*".into(),
+        _ => return None,
+    })
+}
+
+/// The statements of an imperative function's body, indented by `tab`.
+fn imp_statements(term: &Term, tab: usize) -> Option<String> {
+    let indent = " ".repeat(tab);
+    let inner = " ".repeat(tab + 2);
+    let case = |head: String, body: &Term| -> Option<String> {
+        Some(format!(
+            "{inner}{head}:\n{}",
+            imp_statements(body, tab + 4)?
+        ))
+    };
+
+    Some(match term {
+        Term::Let { pat, val, nxt } => format!(
+            "{indent}{} = {}\n{}",
+            imp_pattern(pat)?,
+            imp_expr(val, false)?,
+            imp_statements(nxt, tab)?
+        ),
+        Term::Use {
+            nam: Some(nam),
+            val,
+            nxt,
+        } => format!(
+            "{indent}use {} = {}\n{}",
+            name_text(nam)?,
+            imp_expr(val, false)?,
+            imp_statements(nxt, tab)?
+        ),
+        Term::Ask { pat, val, nxt } => format!(
+            "{indent}{} <- {}\n{}",
+            imp_pattern(pat)?,
+            imp_expr(val, false)?,
+            imp_statements(nxt, tab)?
+        ),
+        Term::With { typ, bod } => format!(
+            "{indent}with {}:\n{}",
+            name_text(typ)?,
+            imp_statements(bod, tab + 2)?
+        ),
+        Term::Mat {
+            bnd,
+            arg,
+            with_bnd,
+            with_arg,
+            arms,
+        }
+        | Term::Fold {
+            bnd,
+            arg,
+            with_bnd,
+            with_arg,
+            arms,
+        } => {
+            let keyword = match term {
+                Term::Mat { .. } => "match",
+                _ => "fold",
+            };
+            let arg = match_arg(bnd, arg, with_bnd, with_arg, |arg| imp_expr(arg, false))?;
+            let cases: Option<String> = arms
+                .iter()
+                .map(|(ctr, _, body)| {
+                    let ctr = match ctr {
+                        Some(ctr) => name_text(ctr)?,
+                        None => "_".into(),
+                    };
+                    case(format!("case {ctr}"), body)
+                })
+                .collect();
+            format!("{indent}{keyword} {arg}:\n{}", cases?)
+        }
+        // The desugaring of `if`.
+        Term::Swt {
+            bnd,
+            arg,
+            with_bnd,
+            arms,
+            ..
+        } if is_generated(bnd) && with_bnd.is_empty() && arms.len() == 2 => format!(
+            "{indent}if {}:\n{}{indent}else:\n{}",
+            imp_expr(arg, false)?,
+            imp_statements(&arms[1], tab + 2)?,
+            imp_statements(&arms[0], tab + 2)?
+        ),
+        Term::Swt {
+            bnd,
+            arg,
+            with_bnd,
+            with_arg,
+            pred,
+            arms,
+        } => {
+            check_pred(bnd, pred, arms.len())?;
+            let arg = match_arg(bnd, arg, with_bnd, with_arg, |arg| imp_expr(arg, false))?;
+            let cases: Option<String> = arms
+                .iter()
+                .enumerate()
+                .map(|(i, body)| match i == arms.len() - 1 {
+                    true => case("case _".into(), body),
+                    false => case(format!("case {i}"), body),
+                })
+                .collect();
+            format!("{indent}switch {arg}:\n{}", cases?)
+        }
+        Term::Bend {
+            bnd,
+            arg,
+            cond,
+            step,
+            base,
+        } => {
+            let binds: Option<Vec<String>> = bnd
+                .iter()
+                .zip(arg)
+                .map(|(bnd, arg)| {
+                    Some(format!(
+                        "{} = {}",
+                        name_text(bnd.as_ref()?)?,
+                        imp_expr(arg, false)?
+                    ))
+                })
+                .collect();
+            format!(
+                "{indent}bend {}:\n{}{}",
+                binds?.join(", "),
+                case(format!("when {}", imp_expr(cond, false)?), step)?,
+                case("else".into(), base)?
+            )
+        }
+        _ => format!("{indent}return {}\n", imp_expr(term, false)?),
+    })
+}
+
+#[test]
+fn convert_syntax_test() {
+    let code = r#"def length(list):
+  # Count the elements.
+  match list:
+    case List/Cons:
+      return 1 + length(list.tail)
+    case List/Nil:
+      return 0
+
+(Sum (List/Cons x xs)) = (+ x (Sum xs))
+(Sum List/Nil) = 0
+
+def evens(n):
+  return [x for x in range(n) if x % 2 == 0]
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let convert_at = |line: u32| -> Vec<(String, String)> {
+        let position = lsp::Position::new(line, 0);
+        convert_syntax(&doc, lsp::Range::new(position, position))
+            .iter()
+            .map(|action| (action.title.clone(), apply_fix(&doc, action)))
+            .collect()
+    };
+
+    let length = convert_at(0);
+    assert_eq!(length[0].0, "Convert 'length' to functional syntax");
+    assert!(length[0].1.starts_with(
+        "# Count the elements.\n(length (List/Cons head tail)) = (+ 1 (length tail))\n(length List/Nil) = 0\n\n(Sum"
+    ));
+
+    let sum = convert_at(8);
+    assert_eq!(sum[0].0, "Convert 'Sum' to imperative syntax");
+    assert!(sum[0].1.contains(
+        "def Sum(list):\n  match list:\n    case List/Cons:\n      return list.head + Sum(list.tail)\n    case List/Nil:\n      return 0\n\ndef evens"
+    ));
+
+    // List comprehensions can't be written in functional syntax.
+    let evens = convert_at(11);
+    assert!(evens
+        .iter()
+        .all(|(title, _)| title.starts_with("Convert file")));
+
+    let file: Vec<String> = evens.into_iter().map(|(title, _)| title).collect();
+    assert_eq!(
+        file,
+        [
+            "Convert file to functional syntax",
+            "Convert file to imperative syntax"
+        ]
+    );
+}
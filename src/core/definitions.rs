@@ -0,0 +1,146 @@
+//! Top-level definitions of a Bend document.
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use super::imports;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    /// A function, in either imperative or functional syntax.
+    Function,
+    /// A type declared with `type`.
+    Type,
+    /// A constructor of a type declared with `type`.
+    Constructor,
+    /// An object declared with `object`, which is both a type and its constructor.
+    Object,
+    /// A native HVM definition.
+    Hvm,
+}
+
+/// A top-level definition of a document.
+///
+/// Functions defined with multiple pattern matching rules have one
+/// `Definition` for each rule.
+#[derive(Debug, Clone)]
+pub struct Definition<'t> {
+    /// Full name of the definition, like `Tree/Node` for constructors.
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// The entire definition, including its body.
+    pub node: ts::Node<'t>,
+    /// The node with the name of the definition.
+    pub name_node: ts::Node<'t>,
+}
+
+impl Definition<'_> {
+    /// Range of the name of the definition.
+    pub fn name_range(&self) -> lsp::Range {
+        ts_range_to_lsp(self.name_node.range())
+    }
+}
+
+/// Collect every top-level definition of a document, in order.
+pub fn definitions(doc: &Document) -> Vec<Definition<'_>> {
+    let Some(tree) = doc.tree.as_ref() else {
+        return vec![];
+    };
+
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut definitions = vec![];
+
+    for node in root.named_children(&mut cursor) {
+        let kind = match node.kind() {
+            "imp_function_definition" | "fun_function_definition" => DefinitionKind::Function,
+            "imp_type_definition" | "fun_type_definition" => DefinitionKind::Type,
+            "object_definition" => DefinitionKind::Object,
+            "hvm_definition" => DefinitionKind::Hvm,
+            _ => continue,
+        };
+        let Some(name_node) = node.child_by_field_name("name") else {
+            continue;
+        };
+        let name = doc.node_text(&name_node);
+
+        if kind == DefinitionKind::Type {
+            let mut ctr_cursor = node.walk();
+            for ctr in node.named_children(&mut ctr_cursor) {
+                if !matches!(ctr.kind(), "imp_type_constructor" | "fun_type_constructor") {
+                    continue;
+                }
+                if let Some(ctr_name) = ctr.named_child(0) {
+                    definitions.push(Definition {
+                        name: format!("{}/{}", name, doc.node_text(&ctr_name)),
+                        kind: DefinitionKind::Constructor,
+                        node: ctr,
+                        name_node: ctr_name,
+                    });
+                }
+            }
+        }
+
+        definitions.push(Definition {
+            name,
+            kind,
+            node,
+            name_node,
+        });
+    }
+
+    // Keep document order, as constructors were pushed before their types.
+    definitions.sort_by_key(|def| def.node.start_byte());
+    definitions
+}
+
+/// Find the definitions with a given name.
+pub fn find_definitions<'a>(doc: &'a Document, name: &str) -> Vec<Definition<'a>> {
+    definitions(doc)
+        .into_iter()
+        .filter(|def| def.name == name)
+        .collect()
+}
+
+/// Find where a definition is located, looking into imported modules when it
+/// isn't defined in the document itself.
+///
+/// The compiler names imported definitions after their modules, so a
+/// definition `bar` from `Foo.bend` is called `Foo/bar`.
+pub fn locate_definitions(doc: &Document, name: &str) -> Vec<lsp::Location> {
+    let local = find_definitions(doc, name);
+    if !local.is_empty() {
+        return local
+            .iter()
+            .map(|def| lsp::Location::new(doc.url.clone(), def.name_range()))
+            .collect();
+    }
+
+    let Some(base_dir) = imports::base_dir(doc) else {
+        return vec![];
+    };
+    for (module, local_name) in module_splits(name) {
+        let file = imports::module_file(&base_dir, module);
+        let Some(module_doc) = lsp::Url::from_file_path(file).ok().and_then(Document::load) else {
+            continue;
+        };
+        let found = find_definitions(&module_doc, local_name);
+        if !found.is_empty() {
+            return found
+                .iter()
+                .map(|def| lsp::Location::new(module_doc.url.clone(), def.name_range()))
+                .collect();
+        }
+    }
+
+    vec![]
+}
+
+/// Possible ways to split a name into a module path and a name, like
+/// `A/B/c` into `(A/B, c)` and `(A, B/c)`.
+fn module_splits(name: &str) -> impl Iterator<Item = (&str, &str)> {
+    name.rmatch_indices('/')
+        .map(move |(i, _)| (&name[..i], &name[i + 1..]))
+}
@@ -0,0 +1,53 @@
+//! Builtins and syntax that were removed from Bend, but that users may still
+//! write out of habit or find in older examples.
+
+use super::diagnostic_code::{quoted_names, DiagnosticCode};
+
+/// Builtin functions that were renamed, with their new names.
+pub const RENAMED_BUILTINS: &[(&str, &str)] = &[
+    ("sleep", "IO/nanosleep"),
+    ("log", "Math/log"),
+    ("atan2", "Math/atan2"),
+    ("Bytes/decode_utf8", "String/decode_utf8"),
+    ("Bytes/decode_ascii", "String/decode_ascii"),
+];
+
+/// New name of a renamed builtin function.
+pub fn renamed_builtin(name: &str) -> Option<&'static str> {
+    RENAMED_BUILTINS
+        .iter()
+        .find(|(old, _)| *old == name)
+        .map(|(_, new)| *new)
+}
+
+/// Whether a parse error was caused by the old `data` keyword, which was
+/// replaced by `type`.
+///
+/// `message` must be the diagnostic message without color codes.
+pub fn is_data_keyword_error(message: &str) -> bool {
+    message
+        .trim_start()
+        .starts_with("- expected: top-level definition")
+        && message
+            .lines()
+            .filter_map(|line| line.split_once(" | ").map(|(_, code)| code))
+            .any(|code| code.starts_with("data "))
+}
+
+/// Note explaining how to replace deprecated code, to be added to the message
+/// of a diagnostic.
+///
+/// `message` must be the diagnostic message without color codes.
+pub fn note(code: DiagnosticCode, message: &str) -> Option<String> {
+    match code {
+        DiagnosticCode::DeprecatedBuiltin => {
+            let name = quoted_names(message).next()?;
+            let new = renamed_builtin(name)?;
+            Some(format!("'{name}' was renamed to '{new}'."))
+        }
+        DiagnosticCode::DeprecatedSyntax => {
+            Some("The 'data' keyword was replaced by 'type'.".into())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,330 @@
+//! Stable codes for the diagnostics reported by the language server.
+//!
+//! Bend does not attach any kind of identifier to its diagnostics, so we
+//! recover them from the diagnostic messages. Codes use the same names as
+//! the compiler's `-W`/`-A`/`-D` command line options where one exists.
+
+use bend::diagnostics::{Diagnostic, DiagnosticOrigin};
+use tower_lsp::lsp_types as lsp;
+
+use super::deprecated;
+use crate::utils::color_wrapper::treat_colors;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticCode {
+    // Compiler warnings
+    IrrefutableMatch,
+    RedundantMatch,
+    UnreachableMatch,
+    UnusedDefinition,
+    RepeatedBind,
+    RecursionCycle,
+    MissingMain,
+    ImportShadow,
+
+    // Compiler errors
+    ParseError,
+    DeprecatedSyntax,
+    DuplicateDefinition,
+    UnboundVariable,
+    DeprecatedBuiltin,
+    UnscopedVariable,
+    UndefinedReference,
+    UnboundConstructor,
+    NonExhaustiveMatch,
+    TypeMismatch,
+    TypeError,
+    InvalidEntrypoint,
+    InvalidWithBlock,
+    InvalidOpen,
+    ImportError,
+    ImportConflict,
+    DefinitionTooLarge,
+    ReadbackError,
+    /// Any compiler diagnostic we don't know about.
+    CompilerError,
+
+    // Language server diagnostics
+    UnusedSuppression,
+    UnusedImport,
+    UnusedVariable,
+    ShadowedVariable,
+    NumberOverflow,
+    MixedIndentation,
+    ArgumentCount,
+    SelfImport,
+    MissingModule,
+    UnknownImport,
+    ImportCycle,
+}
+
+impl DiagnosticCode {
+    pub const ALL: &'static [DiagnosticCode] = &[
+        DiagnosticCode::IrrefutableMatch,
+        DiagnosticCode::RedundantMatch,
+        DiagnosticCode::UnreachableMatch,
+        DiagnosticCode::UnusedDefinition,
+        DiagnosticCode::RepeatedBind,
+        DiagnosticCode::RecursionCycle,
+        DiagnosticCode::MissingMain,
+        DiagnosticCode::ImportShadow,
+        DiagnosticCode::ParseError,
+        DiagnosticCode::DeprecatedSyntax,
+        DiagnosticCode::DuplicateDefinition,
+        DiagnosticCode::UnboundVariable,
+        DiagnosticCode::DeprecatedBuiltin,
+        DiagnosticCode::UnscopedVariable,
+        DiagnosticCode::UndefinedReference,
+        DiagnosticCode::UnboundConstructor,
+        DiagnosticCode::NonExhaustiveMatch,
+        DiagnosticCode::TypeMismatch,
+        DiagnosticCode::TypeError,
+        DiagnosticCode::InvalidEntrypoint,
+        DiagnosticCode::InvalidWithBlock,
+        DiagnosticCode::InvalidOpen,
+        DiagnosticCode::ImportError,
+        DiagnosticCode::ImportConflict,
+        DiagnosticCode::DefinitionTooLarge,
+        DiagnosticCode::ReadbackError,
+        DiagnosticCode::CompilerError,
+        DiagnosticCode::UnusedSuppression,
+        DiagnosticCode::UnusedImport,
+        DiagnosticCode::UnusedVariable,
+        DiagnosticCode::ShadowedVariable,
+        DiagnosticCode::NumberOverflow,
+        DiagnosticCode::MixedIndentation,
+        DiagnosticCode::ArgumentCount,
+        DiagnosticCode::SelfImport,
+        DiagnosticCode::MissingModule,
+        DiagnosticCode::UnknownImport,
+        DiagnosticCode::ImportCycle,
+    ];
+
+    /// Identifier of the code as shown to the user.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagnosticCode::IrrefutableMatch => "irrefutable-match",
+            DiagnosticCode::RedundantMatch => "redundant-match",
+            DiagnosticCode::UnreachableMatch => "unreachable-match",
+            DiagnosticCode::UnusedDefinition => "unused-definition",
+            DiagnosticCode::RepeatedBind => "repeated-bind",
+            DiagnosticCode::RecursionCycle => "recursion-cycle",
+            DiagnosticCode::MissingMain => "missing-main",
+            DiagnosticCode::ImportShadow => "import-shadow",
+            DiagnosticCode::ParseError => "parse-error",
+            DiagnosticCode::DeprecatedSyntax => "deprecated-syntax",
+            DiagnosticCode::DuplicateDefinition => "duplicate-definition",
+            DiagnosticCode::UnboundVariable => "unbound-variable",
+            DiagnosticCode::DeprecatedBuiltin => "deprecated-builtin",
+            DiagnosticCode::UnscopedVariable => "unscoped-variable",
+            DiagnosticCode::UndefinedReference => "undefined-reference",
+            DiagnosticCode::UnboundConstructor => "unbound-constructor",
+            DiagnosticCode::NonExhaustiveMatch => "non-exhaustive-match",
+            DiagnosticCode::TypeMismatch => "type-mismatch",
+            DiagnosticCode::TypeError => "type-error",
+            DiagnosticCode::InvalidEntrypoint => "invalid-entrypoint",
+            DiagnosticCode::InvalidWithBlock => "invalid-with-block",
+            DiagnosticCode::InvalidOpen => "invalid-open",
+            DiagnosticCode::ImportError => "import-error",
+            DiagnosticCode::ImportConflict => "import-conflict",
+            DiagnosticCode::DefinitionTooLarge => "definition-too-large",
+            DiagnosticCode::ReadbackError => "readback-error",
+            DiagnosticCode::CompilerError => "compiler-error",
+            DiagnosticCode::UnusedSuppression => "unused-suppression",
+            DiagnosticCode::UnusedImport => "unused-import",
+            DiagnosticCode::UnusedVariable => "unused-variable",
+            DiagnosticCode::ShadowedVariable => "shadowed-variable",
+            DiagnosticCode::NumberOverflow => "number-overflow",
+            DiagnosticCode::MixedIndentation => "mixed-indentation",
+            DiagnosticCode::ArgumentCount => "argument-count",
+            DiagnosticCode::SelfImport => "self-import",
+            DiagnosticCode::MissingModule => "missing-module",
+            DiagnosticCode::UnknownImport => "unknown-import",
+            DiagnosticCode::ImportCycle => "import-cycle",
+        }
+    }
+
+    /// Find a code by its identifier.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|code| code.as_str() == name)
+    }
+
+    /// Long-form markdown explanation of the code, with examples.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            DiagnosticCode::IrrefutableMatch => include_str!("explanations/irrefutable-match.md"),
+            DiagnosticCode::RedundantMatch => include_str!("explanations/redundant-match.md"),
+            DiagnosticCode::UnreachableMatch => include_str!("explanations/unreachable-match.md"),
+            DiagnosticCode::UnusedDefinition => include_str!("explanations/unused-definition.md"),
+            DiagnosticCode::RepeatedBind => include_str!("explanations/repeated-bind.md"),
+            DiagnosticCode::RecursionCycle => include_str!("explanations/recursion-cycle.md"),
+            DiagnosticCode::MissingMain => include_str!("explanations/missing-main.md"),
+            DiagnosticCode::ImportShadow => include_str!("explanations/import-shadow.md"),
+            DiagnosticCode::ParseError => include_str!("explanations/parse-error.md"),
+            DiagnosticCode::DeprecatedSyntax => include_str!("explanations/deprecated-syntax.md"),
+            DiagnosticCode::DuplicateDefinition => {
+                include_str!("explanations/duplicate-definition.md")
+            }
+            DiagnosticCode::UnboundVariable => include_str!("explanations/unbound-variable.md"),
+            DiagnosticCode::DeprecatedBuiltin => {
+                include_str!("explanations/deprecated-builtin.md")
+            }
+            DiagnosticCode::UnscopedVariable => include_str!("explanations/unscoped-variable.md"),
+            DiagnosticCode::UndefinedReference => {
+                include_str!("explanations/undefined-reference.md")
+            }
+            DiagnosticCode::UnboundConstructor => {
+                include_str!("explanations/unbound-constructor.md")
+            }
+            DiagnosticCode::NonExhaustiveMatch => {
+                include_str!("explanations/non-exhaustive-match.md")
+            }
+            DiagnosticCode::TypeMismatch => include_str!("explanations/type-mismatch.md"),
+            DiagnosticCode::TypeError => include_str!("explanations/type-error.md"),
+            DiagnosticCode::InvalidEntrypoint => include_str!("explanations/invalid-entrypoint.md"),
+            DiagnosticCode::InvalidWithBlock => include_str!("explanations/invalid-with-block.md"),
+            DiagnosticCode::InvalidOpen => include_str!("explanations/invalid-open.md"),
+            DiagnosticCode::ImportError => include_str!("explanations/import-error.md"),
+            DiagnosticCode::ImportConflict => include_str!("explanations/import-conflict.md"),
+            DiagnosticCode::DefinitionTooLarge => {
+                include_str!("explanations/definition-too-large.md")
+            }
+            DiagnosticCode::ReadbackError => include_str!("explanations/readback-error.md"),
+            DiagnosticCode::CompilerError => include_str!("explanations/compiler-error.md"),
+            DiagnosticCode::UnusedSuppression => {
+                include_str!("explanations/unused-suppression.md")
+            }
+            DiagnosticCode::UnusedImport => include_str!("explanations/unused-import.md"),
+            DiagnosticCode::UnusedVariable => include_str!("explanations/unused-variable.md"),
+            DiagnosticCode::ShadowedVariable => include_str!("explanations/shadowed-variable.md"),
+            DiagnosticCode::NumberOverflow => include_str!("explanations/number-overflow.md"),
+            DiagnosticCode::MixedIndentation => include_str!("explanations/mixed-indentation.md"),
+            DiagnosticCode::ArgumentCount => include_str!("explanations/argument-count.md"),
+            DiagnosticCode::SelfImport => include_str!("explanations/self-import.md"),
+            DiagnosticCode::MissingModule => include_str!("explanations/missing-module.md"),
+            DiagnosticCode::UnknownImport => include_str!("explanations/unknown-import.md"),
+            DiagnosticCode::ImportCycle => include_str!("explanations/import-cycle.md"),
+        }
+    }
+
+    /// Tags that editors use to render diagnostics with this code, like
+    /// graying out unnecessary code.
+    pub fn tags(self) -> Option<Vec<lsp::DiagnosticTag>> {
+        match self {
+            DiagnosticCode::UnusedDefinition
+            | DiagnosticCode::UnusedImport
+            | DiagnosticCode::UnusedVariable
+            | DiagnosticCode::RedundantMatch
+            | DiagnosticCode::UnreachableMatch => Some(vec![lsp::DiagnosticTag::UNNECESSARY]),
+            DiagnosticCode::DeprecatedBuiltin | DiagnosticCode::DeprecatedSyntax => {
+                Some(vec![lsp::DiagnosticTag::DEPRECATED])
+            }
+            _ => None,
+        }
+    }
+
+    /// Finds out the code of a compiler diagnostic.
+    pub fn of(origin: &DiagnosticOrigin, diag: &Diagnostic) -> Self {
+        let message = treat_colors(&diag.message);
+        let message = message.trim_start();
+        let starts = |prefix: &str| message.starts_with(prefix);
+
+        if starts("Irrefutable 'match' expression") {
+            DiagnosticCode::IrrefutableMatch
+        } else if starts("Redundant arm in 'match' expression") {
+            DiagnosticCode::RedundantMatch
+        } else if starts("Unreachable arms in 'match' expression") {
+            DiagnosticCode::UnreachableMatch
+        } else if starts("Definition is unused") {
+            DiagnosticCode::UnusedDefinition
+        } else if starts("Repeated bind in pattern matching rule") {
+            DiagnosticCode::RepeatedBind
+        } else if starts("The following functions contain recursive cycles") {
+            DiagnosticCode::RecursionCycle
+        } else if starts("File has no '") {
+            DiagnosticCode::MissingMain
+        } else if starts("The import '") && message.contains("shadows") {
+            DiagnosticCode::ImportShadow
+        } else if starts("Redefinition of ")
+            || message.contains("has the same name as a previously defined")
+        {
+            DiagnosticCode::DuplicateDefinition
+        } else if starts("Unbound variable") {
+            match quoted_names(message)
+                .next()
+                .and_then(deprecated::renamed_builtin)
+            {
+                Some(_) => DiagnosticCode::DeprecatedBuiltin,
+                None => DiagnosticCode::UnboundVariable,
+            }
+        } else if starts("Unbound unscoped variable")
+            || starts("Unscoped variable")
+            || starts("Unscoped lambda")
+        {
+            DiagnosticCode::UnscopedVariable
+        } else if starts("Reference to undefined function") {
+            DiagnosticCode::UndefinedReference
+        } else if starts("Unbound constructor")
+            || starts("Found unknown type constructor")
+            || (starts("Constructor '") && message.contains("not found"))
+        {
+            DiagnosticCode::UnboundConstructor
+        } else if starts("Non-exhaustive") {
+            DiagnosticCode::NonExhaustiveMatch
+        } else if starts("Type mismatch in") {
+            DiagnosticCode::TypeMismatch
+        } else if starts("Type Error") || message.contains("Can't unify") {
+            DiagnosticCode::TypeError
+        } else if starts("File has both")
+            || starts("File has '")
+            || starts("Main definition can't have more than one rule")
+            || starts("Expected the entrypoint function")
+        {
+            DiagnosticCode::InvalidEntrypoint
+        } else if starts("Could not find definition") || starts("Monadic bind operation") {
+            DiagnosticCode::InvalidWithBlock
+        } else if starts("Type '") && message.contains("of an 'open'") {
+            DiagnosticCode::InvalidOpen
+        } else if starts("The imported ") && message.contains("conflicts with") {
+            DiagnosticCode::ImportConflict
+        } else if starts("Failed to import")
+            || starts("Package '")
+            || starts("Both file '")
+            || starts("Can not import the entry point")
+            || (starts("The file '") && message.contains("was not found"))
+        {
+            DiagnosticCode::ImportError
+        } else if starts("Definition is too large") {
+            DiagnosticCode::DefinitionTooLarge
+        } else if deprecated::is_data_keyword_error(message) {
+            DiagnosticCode::DeprecatedSyntax
+        } else {
+            match origin {
+                DiagnosticOrigin::Parsing => DiagnosticCode::ParseError,
+                DiagnosticOrigin::Readback => DiagnosticCode::ReadbackError,
+                _ => DiagnosticCode::CompilerError,
+            }
+        }
+    }
+}
+
+/// Names between single quotes in a diagnostic message, like the name of the
+/// variable in `Unbound variable 'x'`.
+pub fn quoted_names(message: &str) -> impl Iterator<Item = &str> {
+    message.split('\'').skip(1).step_by(2)
+}
+
+impl std::fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Every code must have an explanation starting with its title.
+#[test]
+fn explanations_test() {
+    for code in DiagnosticCode::ALL {
+        assert_eq!(DiagnosticCode::from_name(code.as_str()), Some(*code));
+        assert!(code.explanation().starts_with(&format!("# `{code}`")));
+    }
+}
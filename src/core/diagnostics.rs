@@ -1,99 +1,356 @@
-use std::path::Path;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub use bend::diagnostics::*;
-use bend::{check_book, imports::DefaultLoader, CompileOpts};
+use bend::fun::Name;
+use bend::imports::{DefaultLoader, PackageLoader};
+use bend::{check_book, CompileOpts};
 use tower_lsp::lsp_types::{self as lsp, Position};
 use tree_sitter as ts;
 
+use super::deprecated;
+use super::diagnostic_code::DiagnosticCode;
 use super::document::Document;
+use super::imports;
+use super::lints::{self, LintConfig};
+use super::ranges::{builtin_range, diagnostic_range};
+use super::related_information::related_information;
+use super::suppression;
+use super::syntax_errors;
 use crate::utils::color_wrapper::treat_colors;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+use crate::utils::panic::{catch_panic, Panic};
 
 /// Checks a Bend file and return its diagnostics.
-pub fn check(doc: &Document) -> Diagnostics {
-    let path = Path::new(doc.url.path());
-    let diagnostics_config = DiagnosticsConfig::new(Severity::Warning, true);
-    let compile_opts = CompileOpts::default();
+///
+/// The compiler reads the file and its imports from the file system. It runs
+/// with panic isolation, so a bug in it is returned as an error instead of
+/// crashing the server.
+pub fn check(url: &lsp::Url) -> Result<Diagnostics, Panic> {
+    check_with_loader(url, DefaultLoader::new(&file_path(url)))
+}
 
-    let package_loader = DefaultLoader::new(path);
+/// Checks a Bend file, loading its imports with `package_loader`.
+pub fn check_with_loader(
+    url: &lsp::Url,
+    package_loader: impl PackageLoader,
+) -> Result<Diagnostics, Panic> {
+    let path = file_path(url);
+    let (diagnostics_config, compile_opts) = check_config();
 
-    let diagnostics = bend::load_file_to_book(path, package_loader, diagnostics_config)
-        .and_then(|mut book| check_book(&mut book, diagnostics_config, compile_opts));
+    let diagnostics = catch_panic(|| {
+        bend::load_file_to_book(&path, package_loader, diagnostics_config)
+            .and_then(|mut book| check_book(&mut book, diagnostics_config, compile_opts))
+    })?;
 
     match diagnostics {
-        Ok(d) | Err(d) => d,
+        Ok(d) | Err(d) => Ok(d),
+    }
+}
+
+/// Path of the file at `url`, decoded from the URL, as the compiler reads it.
+pub fn file_path(url: &lsp::Url) -> PathBuf {
+    url.to_file_path()
+        .unwrap_or_else(|_| PathBuf::from(url.path()))
+}
+
+/// Configuration of the compiler used to check files.
+pub fn check_config() -> (DiagnosticsConfig, CompileOpts) {
+    (
+        DiagnosticsConfig::new(Severity::Warning, true),
+        CompileOpts::default(),
+    )
+}
+
+/// Limits on the checks run by the server, from the `bend.checkTimeout` and
+/// `bend.maxConcurrentChecks` settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckLimits {
+    /// Time after which a check is abandoned.
+    pub timeout: Duration,
+    /// Number of checks that may run at the same time.
+    pub max_concurrent: usize,
+}
+
+impl Default for CheckLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_concurrent: 2,
+        }
+    }
+}
+
+impl CheckLimits {
+    /// Read the limits from the `bend` settings section, using the defaults
+    /// for missing or invalid values.
+    pub fn from_settings(settings: &serde_json::Value) -> Self {
+        let default = Self::default();
+        let timeout = settings
+            .get("checkTimeout")
+            .and_then(|timeout| timeout.as_f64())
+            .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+            .map_or(default.timeout, Duration::from_secs_f64);
+        let max_concurrent = settings
+            .get("maxConcurrentChecks")
+            .and_then(|max| max.as_u64())
+            .filter(|max| *max > 0)
+            .map_or(default.max_concurrent, |max| max as usize);
+
+        Self {
+            timeout,
+            max_concurrent,
+        }
     }
 }
 
-pub fn lsp_diagnostics(doc: &Document, diagnostics: &Diagnostics) -> Vec<lsp::Diagnostic> {
+/// Diagnostics of a check where the compiler panicked: a single error with
+/// the panic's message.
+pub fn internal_compiler_error(panic: &Panic) -> Diagnostics {
+    let mut diagnostics = Diagnostics::new(check_config().0);
+    diagnostics.add_book_error(format!(
+        "Internal compiler error: {}\nThis is a bug in Bend, please report it.",
+        panic.message
+    ));
+    diagnostics
+}
+
+/// Diagnostics that are found without the compiler, so they can be reported
+/// while typing: syntax errors and lints.
+pub fn live_diagnostics(doc: &Document, lints: &LintConfig) -> Vec<lsp::Diagnostic> {
+    let mut diagnostics = syntax_errors::syntax_diagnostics(doc);
+    diagnostics.extend(lints::lint_diagnostics(doc, lints));
+    suppression::apply_suppressions(suppression::suppressions(doc), &mut diagnostics);
     diagnostics
+}
+
+/// Convert the diagnostics of a check into LSP diagnostics, grouped by the
+/// file they belong to.
+///
+/// Besides the diagnostics of `doc`, the compiler reports diagnostics located
+/// in the modules `doc` imports. `doc` itself is always included, even when it
+/// has no diagnostics, so that its previous ones can be cleared.
+pub fn lsp_diagnostics(
+    doc: &Document,
+    diagnostics: &Diagnostics,
+    lints: &LintConfig,
+) -> HashMap<lsp::Url, Vec<lsp::Diagnostic>> {
+    let mut by_file = HashMap::<lsp::Url, Vec<(DiagnosticOrigin, &Diagnostic)>>::new();
+    by_file.insert(doc.url.clone(), vec![]);
+
+    for (origin, diagnostic) in diagnostics
         .diagnostics
         // Iter<(DiagnosticOrigin, Vec<Diagnostic>)>
         .iter()
         // -> Iter<(DiagnosticOrigin, Diagnostic)>
         .flat_map(|(key, vals)| vals.iter().map(move |val| (key, val)))
-        // Ignore unwanted diagnostics
-        .filter_map(|(origin, diagnostic)| treat_diagnostic(doc, origin, diagnostic))
+    {
+        let (url, origin) = diagnostic_file(doc, origin, diagnostic)
+            .unwrap_or_else(|| (doc.url.clone(), origin.clone()));
+        by_file.entry(url).or_default().push((origin, diagnostic));
+    }
+
+    by_file
+        .into_iter()
+        .filter_map(|(url, diagnostics)| {
+            if url == doc.url {
+                return Some((url, document_diagnostics(doc, &diagnostics, true, lints)));
+            }
+
+            // Imported modules are read from the disk by the compiler, so we do the same.
+            let module = Document::load(url.clone())?;
+            let diagnostics = diagnostics
+                .into_iter()
+                // Modules are usually not entirely used by each file importing them.
+                .filter(|(origin, diag)| {
+                    DiagnosticCode::of(origin, diag) != DiagnosticCode::UnusedDefinition
+                })
+                .collect::<Vec<_>>();
+            Some((
+                url,
+                document_diagnostics(&module, &diagnostics, false, lints),
+            ))
+        })
         .collect()
 }
 
+/// Codes of the lints reporting the imports the compiler fails to load.
+const IMPORT_LINTS: &[DiagnosticCode] = &[
+    DiagnosticCode::MissingModule,
+    DiagnosticCode::UnknownImport,
+    DiagnosticCode::ImportCycle,
+    DiagnosticCode::SelfImport,
+];
+
+/// Convert the diagnostics located in `doc`.
+///
+/// Diagnostics that only make sense for the checked file, like lints and
+/// unused suppressions, are only reported if `checked` is set.
+fn document_diagnostics(
+    doc: &Document,
+    diagnostics: &[(DiagnosticOrigin, &Diagnostic)],
+    checked: bool,
+    lints: &LintConfig,
+) -> Vec<lsp::Diagnostic> {
+    // How many times we've seen each message, to tell repeated diagnostics apart.
+    let mut occurrences = HashMap::<String, usize>::new();
+
+    let mut lsp_diagnostics: Vec<_> = diagnostics
+        .iter()
+        // Ignore unwanted diagnostics
+        .filter_map(|(origin, diagnostic)| {
+            let count = occurrences
+                .entry(diagnostic.display_with_origin(origin).to_string())
+                .or_default();
+            *count += 1;
+            treat_diagnostic(doc, origin, diagnostic, *count - 1)
+        })
+        .collect();
+
+    if checked {
+        lsp_diagnostics.extend(lints::lint_diagnostics(doc, lints));
+
+        // The compiler reports failed imports as errors of the whole book, so
+        // they're dropped when we found the problem on the import statements.
+        let import_code = |diag: &lsp::Diagnostic, codes: &[DiagnosticCode]| {
+            codes
+                .iter()
+                .any(|code| diag.code == Some(lsp::NumberOrString::String(code.as_str().into())))
+        };
+        if lsp_diagnostics
+            .iter()
+            .any(|diag| import_code(diag, IMPORT_LINTS))
+        {
+            lsp_diagnostics.retain(|diag| !import_code(diag, &[DiagnosticCode::ImportError]));
+        }
+    }
+
+    // Remove diagnostics silenced by `# bend-ignore` comments.
+    let unused =
+        suppression::apply_suppressions(suppression::suppressions(doc), &mut lsp_diagnostics);
+
+    // If the check stopped at an error, some warnings may not have been
+    // generated, so we can't know whether a suppression is unused.
+    let has_errors = diagnostics
+        .iter()
+        .any(|(_, diag)| diag.severity == Severity::Error);
+    if checked && !has_errors {
+        lsp_diagnostics.extend(
+            unused
+                .iter()
+                .map(suppression::unused_suppression_diagnostic),
+        );
+    }
+
+    lsp_diagnostics
+}
+
+/// Find the imported file a diagnostic is located in, if it's not in `doc`,
+/// along with its origin as seen from that file.
+///
+/// Definitions of imported modules are renamed after the module, so a
+/// function `foo` of `Lib.bend` is called `Lib/foo` in `doc`.
+fn diagnostic_file(
+    doc: &Document,
+    origin: &DiagnosticOrigin,
+    diag: &Diagnostic,
+) -> Option<(lsp::Url, DiagnosticOrigin)> {
+    if diag.source.is_builtin() {
+        return None;
+    }
+
+    let file = diag.source.file.as_ref()?;
+    let path = match Path::new(file) {
+        path if path.is_absolute() => path.to_path_buf(),
+        _ => imports::module_file(&imports::base_dir(doc)?, file),
+    };
+    if doc
+        .url
+        .to_file_path()
+        .is_ok_and(|doc_path| doc_path == path)
+    {
+        return None;
+    }
+    let url = lsp::Url::from_file_path(path).ok()?;
+
+    let origin = match origin {
+        DiagnosticOrigin::Function(name) => {
+            let local_name = name.strip_prefix(&format!("{file}/")).unwrap_or(name);
+            DiagnosticOrigin::Function(Name::new(local_name.to_string()))
+        }
+        origin => origin.clone(),
+    };
+
+    Some((url, origin))
+}
+
 fn treat_diagnostic(
     doc: &Document,
     origin: &DiagnosticOrigin,
     diag: &Diagnostic,
+    occurrence: usize,
 ) -> Option<lsp::Diagnostic> {
+    let code = DiagnosticCode::of(origin, diag);
+    let raw_message = treat_colors(&diag.message);
+    let related_information = related_information(doc, origin, code, &raw_message);
+
+    let mut message = treat_colors(&diag.display_with_origin(origin).to_string());
+    if let Some(note) = deprecated::note(code, &raw_message) {
+        let message_end = message.trim_end();
+        let last_line = message_end.lines().last().unwrap_or_default();
+        let indent = &last_line[..last_line.len() - last_line.trim_start().len()];
+        message = format!("{message_end}\n{indent}{note}");
+    }
+
+    let range = if diag.source.is_builtin() {
+        builtin_range(doc, origin)?
+    } else {
+        let span = diag.source.span.as_ref().map(span_to_range);
+        diagnostic_range(doc, origin, code, &raw_message, span, occurrence)
+    };
+
     Some(lsp::Diagnostic {
-        message: treat_colors(&diag.display_with_origin(origin).to_string()),
+        message,
         severity: match diag.severity {
             Severity::Allow => Some(lsp::DiagnosticSeverity::HINT),
             Severity::Warning => Some(lsp::DiagnosticSeverity::WARNING),
             Severity::Error => Some(lsp::DiagnosticSeverity::ERROR),
         },
-        range: match origin {
-            DiagnosticOrigin::Function(name) => find_def(doc, name.as_ref())?,
-            DiagnosticOrigin::Inet(name) => find_def(doc, name.as_ref())?,
-            _ => span_to_range(&diag.source.span),
-        },
-        code: None,
+        range,
+        code: Some(lsp::NumberOrString::String(code.as_str().into())),
         code_description: None,
         source: Some("bend".into()),
-        related_information: None,
-        tags: None,
+        related_information,
+        tags: code.tags(),
         data: None,
     })
 }
 
-/// Diagnostics with `Rule`, `Function` or `Inet` origins may have their
-/// spans including entire definitions, while we would only like to
-/// highlight their names.
-fn find_def(doc: &Document, name: &str) -> Option<lsp::Range> {
-    let query = format!(
-        r#"
-        (fun_function_definition
-            name: (identifier) @name
-            (#eq? @name "{name}"))
-        (imp_function_definition
-            name: (identifier) @name
-            (#eq? @name "{name}"))
-    "#
-    );
+fn span_to_range(span: &TextSpan) -> lsp::Range {
+    lsp::Range {
+        start: Position {
+            line: span.start.line as u32,
+            character: span.start.char as u32,
+        },
+        end: Position {
+            line: span.end.line as u32,
+            character: span.end.char as u32,
+        },
+    }
+}
 
-    doc.find_one(&query)
-        .map(|node| ts_range_to_lsp(node.range()))
-}
-
-fn span_to_range(span: &Option<TextSpan>) -> lsp::Range {
-    span.as_ref()
-        .map(|span| lsp::Range {
-            start: Position {
-                line: span.start.line as u32,
-                character: span.start.char as u32,
-            },
-            end: Position {
-                line: span.end.line as u32,
-                character: span.end.char as u32,
-            },
-        })
+/// Identifier of a set of diagnostics for pull diagnostic requests, which
+/// only changes when the diagnostics change.
+pub fn result_id(diagnostics: &[lsp::Diagnostic]) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(diagnostics)
         .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
 }
 
 pub fn ts_range_to_lsp(range: ts::Range) -> lsp::Range {
@@ -108,3 +365,101 @@ pub fn ts_range_to_lsp(range: ts::Range) -> lsp::Range {
         },
     }
 }
+
+#[test]
+fn imported_diagnostics_test() {
+    let dir = TempDir::new("imported_diagnostics_test");
+
+    let lib = r#"type T = A | B
+
+def foo(t):
+  match t:
+    case T/A:
+      return y
+    case T/B:
+      return 1
+"#;
+    let code = r#"from Lib import foo
+
+def main():
+  return foo(0)
+"#;
+    dir.write("Lib.bend", lib);
+    dir.write("main.bend", code);
+
+    let url = dir.url("main.bend");
+    let lib_url = dir.url("Lib.bend");
+    let doc = Document::new_with_text(url.clone(), code);
+    let mut by_file = lsp_diagnostics(&doc, &check(&doc.url).unwrap(), &LintConfig::default());
+
+    assert_eq!(by_file.remove(&url), Some(vec![]));
+    let lib_diagnostics = by_file.remove(&lib_url).unwrap();
+    assert_eq!(lib_diagnostics.len(), 1);
+    assert_eq!(
+        lib_diagnostics[0].message,
+        "In definition 'foo':\n  Unbound variable 'y'.\n"
+    );
+    assert_eq!(
+        lib_diagnostics[0].range,
+        lsp::Range::new(lsp::Position::new(2, 4), lsp::Position::new(2, 7))
+    );
+}
+
+#[test]
+fn failed_import_test() {
+    let dir = TempDir::new("failed_import_test");
+    let code = "from Missing import foo\n\ndef main():\n  return foo\n";
+    dir.write("main.bend", code);
+
+    let url = dir.url("main.bend");
+    let doc = Document::new_with_text(url.clone(), code);
+    let by_file = lsp_diagnostics(&doc, &check(&doc.url).unwrap(), &LintConfig::default());
+
+    // Only the error on the import path is reported, not the compiler's.
+    let codes: Vec<_> = by_file[&url].iter().map(|diag| diag.code.clone()).collect();
+    assert_eq!(
+        codes,
+        [Some(lsp::NumberOrString::String("missing-module".into()))]
+    );
+    assert_eq!(by_file[&url][0].range.start, lsp::Position::new(0, 5));
+}
+
+#[test]
+fn check_limits_test() {
+    let limits = CheckLimits::from_settings(&serde_json::json!({
+        "checkTimeout": 2.5,
+        "maxConcurrentChecks": 0,
+    }));
+
+    assert_eq!(limits.timeout, Duration::from_millis(2500));
+    assert_eq!(limits.max_concurrent, CheckLimits::default().max_concurrent);
+}
+
+#[test]
+fn diagnostic_locations_test() {
+    let dir = TempDir::new("diagnostic_locations_test");
+    let url = dir.url("main.bend");
+    let diagnostics = |code: &str| {
+        dir.write("main.bend", code);
+        let doc = Document::new_with_text(url.clone(), code);
+        let mut by_file = lsp_diagnostics(&doc, &check(&doc.url).unwrap(), &LintConfig::default());
+        by_file.remove(&url).unwrap()
+    };
+
+    let range =
+        |l1, c1, l2, c2| lsp::Range::new(lsp::Position::new(l1, c1), lsp::Position::new(l2, c2));
+
+    // Errors in builtins are reported on their uses, or dropped if there are none.
+    let code = "type T = A | (B x)\n\ndef main():\n  return T/A\n";
+    assert_eq!(diagnostics(code), []);
+    let code = "type T = A | (B x)\n\ndef main():\n  return IO/call(\"a\", T/A)\n";
+    let ranges: Vec<_> = diagnostics(code).iter().map(|diag| diag.range).collect();
+    assert_eq!(ranges, [range(3, 9, 3, 16)]);
+
+    // Errors of the whole book fall back to the first definition.
+    let ranges: Vec<_> = diagnostics("def foo():\n  return 0\n")
+        .iter()
+        .map(|diag| diag.range)
+        .collect();
+    assert_eq!(ranges, [range(0, 4, 0, 7)]);
+}
@@ -35,6 +35,12 @@ impl Document {
         doc
     }
 
+    /// Load a document from the file system, for files that aren't open in the client.
+    pub fn load(url: lsp::Url) -> Option<Self> {
+        let text = std::fs::read_to_string(url.to_file_path().ok()?).ok()?;
+        Some(Self::new_with_text(url, &text))
+    }
+
     /// Update the document with entirely new text.
     pub fn update_whole_text(&mut self, text: &str) {
         self.text = Rope::from_str(text);
@@ -49,7 +55,7 @@ impl Document {
     }
 
     /// Find up to one node based on a tree-sitter query.
-    pub fn find_one(&self, query: &str) -> Option<ts::Node> {
+    pub fn find_one(&self, query: &str) -> Option<ts::Node<'_>> {
         let mut cursor = ts::QueryCursor::new();
         let query = ts::Query::new(&bend(), query).unwrap();
         let root = self.tree.as_ref()?.root_node();
@@ -61,6 +67,46 @@ impl Document {
             .map(|capture| capture.node)
     }
 
+    /// Find every node captured by a tree-sitter query, in document order.
+    pub fn find_all(&self, query: &str) -> Vec<ts::Node<'_>> {
+        let mut cursor = ts::QueryCursor::new();
+        let query = ts::Query::new(&bend(), query).unwrap();
+        let Some(tree) = self.tree.as_ref() else {
+            return vec![];
+        };
+
+        cursor
+            .captures(&query, tree.root_node(), &TextProviderRope(&self.text))
+            .map(|(m, i)| m.captures[i].node)
+            .collect()
+    }
+
+    /// Get the text of a node of this document's tree.
+    pub fn node_text(&self, node: &ts::Node) -> String {
+        self.text.byte_slice(node.byte_range()).to_string()
+    }
+
+    /// Get the range of a node of this document's tree.
+    ///
+    /// Unlike tree-sitter's columns, which are byte offsets, the columns of
+    /// this range count characters, so edits stay in place in lines with
+    /// characters like `λ`.
+    pub fn node_range(&self, node: &ts::Node) -> lsp::Range {
+        let position = |byte: usize| {
+            let line = self.text.byte_to_line(byte);
+            let column = self.text.byte_to_char(byte) - self.text.line_to_char(line);
+            lsp::Position::new(line as u32, column as u32)
+        };
+        lsp::Range::new(position(node.start_byte()), position(node.end_byte()))
+    }
+
+    /// Position after the last character of the document, in its last line.
+    pub fn end_position(&self) -> lsp::Position {
+        let line = self.text.len_lines() - 1;
+        let column = self.text.len_chars() - self.text.line_to_char(line);
+        lsp::Position::new(line as u32, column as u32)
+    }
+
     fn do_parse(&mut self) -> Option<ts::Tree> {
         self.parser.parse_with(
             &mut |start_byte, _| {
@@ -0,0 +1,241 @@
+//! Refactoring to extract an expression into a new top-level function.
+//!
+//! The local variables the expression uses become the parameters of the new
+//! function, which is written in the syntax of the function the expression
+//! comes from and placed after it.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::code_actions::node_at;
+use super::definitions::find_definitions;
+use super::document::Document;
+use super::lints::scopes;
+use crate::utils::tree::descendants;
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+
+/// Kinds of the nodes that are expressions, in both syntaxes.
+const EXPRESSIONS: &[&str] = &[
+    "binary_op",
+    "call_expression",
+    "character",
+    "comparison_op",
+    "constructor",
+    "eraser",
+    "float",
+    "fun_application",
+    "fun_bend",
+    "fun_fold",
+    "fun_if",
+    "fun_lambda",
+    "fun_match",
+    "fun_open",
+    "fun_switch",
+    "identifier",
+    "imp_lambda",
+    "integer",
+    "let_bind",
+    "list",
+    "list_comprehension",
+    "map",
+    "num_operator",
+    "parenthesized_expression",
+    "string",
+    "superposition",
+    "symbol",
+    "tree_leaf",
+    "tree_node",
+    "tuple",
+    "unary_op",
+    "use",
+];
+
+/// Action to move the expression selected by `range` into a new function,
+/// replacing it with a call.
+pub fn extract_function(doc: &Document, range: lsp::Range) -> Option<lsp::CodeAction> {
+    if range.start == range.end {
+        return None;
+    }
+    let node = node_at(doc, range)?;
+    let node = match node.parent() {
+        Some(parent) if parent.kind() == "identifier" => parent,
+        _ => node,
+    };
+    if !EXPRESSIONS.contains(&node.kind()) || doc.node_range(&node) != range {
+        return None;
+    }
+
+    let definition = std::iter::successors(Some(node), |node| node.parent()).find(|node| {
+        node.parent()
+            .is_some_and(|parent| parent.parent().is_none())
+    })?;
+    let functional = match definition.kind() {
+        "fun_function_definition" => true,
+        "imp_function_definition" => false,
+        _ => return None,
+    };
+    let mut cursor = definition.walk();
+    let body = definition
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "body")?;
+    if !body.byte_range().contains(&node.start_byte()) || !is_movable(doc, node) {
+        return None;
+    }
+
+    // The variables bound outside of the expression become parameters. The
+    // expression can't bind variables used outside of it, like the left side
+    // of an assignment.
+    let selection = node.byte_range();
+    let mut params: Vec<String> = vec![];
+    for binding in scopes::bindings(doc) {
+        let inside = selection.contains(&binding.node.start_byte());
+        let escapes = binding
+            .references
+            .iter()
+            .any(|reference| !selection.contains(&reference.start_byte()));
+        if inside && escapes {
+            return None;
+        }
+        if inside {
+            continue;
+        }
+        for reference in binding.references {
+            let name = doc.node_text(&reference);
+            if selection.contains(&reference.start_byte()) && !params.contains(&name) {
+                params.push(name);
+            }
+        }
+    }
+    let order = |name: &String| {
+        descendants(node)
+            .position(|node| node.kind() == "identifier" && doc.node_text(&node) == *name)
+    };
+    params.sort_by_key(order);
+
+    let name = fresh_function_name(doc, "extracted");
+    let expression = doc.node_text(&node);
+    let (call, function) = match (functional, params.is_empty()) {
+        (true, true) => (name.clone(), format!("{name} = {expression}")),
+        (true, false) => {
+            let call = format!("({name} {})", params.join(" "));
+            (call.clone(), format!("{call} = {expression}"))
+        }
+        (false, _) => {
+            let call = format!("{name}({})", params.join(", "));
+            (call.clone(), format!("def {call}:\n  return {expression}"))
+        }
+    };
+
+    // The new function goes after the last rule of the current one.
+    let name_node = definition.child_by_field_name("name")?;
+    let last = find_definitions(doc, &doc.node_text(&name_node))
+        .into_iter()
+        .map(|def| def.node)
+        .max_by_key(|node| node.end_byte())
+        .unwrap_or(definition);
+    let end = doc.node_range(&last).end;
+
+    let edits = vec![
+        lsp::TextEdit {
+            range,
+            new_text: call,
+        },
+        lsp::TextEdit {
+            range: lsp::Range::new(end, end),
+            new_text: format!("\n\n{function}"),
+        },
+    ];
+    Some(lsp::CodeAction {
+        title: "Extract into a new function".into(),
+        kind: Some(lsp::CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Whether an expression means the same in a function of its own.
+///
+/// Unscoped variables, `wrap` in `with` blocks and `fork` in `bend`s depend
+/// on where they are written.
+fn is_movable(doc: &Document, node: ts::Node) -> bool {
+    let inside = |kinds: &[&str]| {
+        std::iter::successors(Some(node), |node| node.parent())
+            .any(|node| kinds.contains(&node.kind()))
+    };
+    let in_with = inside(&["with_statement", "fun_with"]);
+    let in_bend = inside(&["bend_statement", "fun_bend"]);
+    !descendants(node).any(|node| match node.kind() {
+        "unscoped_var" | "fun_ask" => true,
+        "identifier" => {
+            let text = doc.node_text(&node);
+            (in_with && text == "wrap") || (in_bend && text == "fork")
+        }
+        _ => false,
+    })
+}
+
+/// `base`, or `base` followed by a number, such that the document has no
+/// definition or identifier with that name.
+fn fresh_function_name(doc: &Document, base: &str) -> String {
+    let root = doc.tree.as_ref().map(|tree| tree.root_node());
+    let names: Vec<String> = root
+        .into_iter()
+        .flat_map(descendants)
+        .filter(|node| node.kind() == "identifier")
+        .map(|node| doc.node_text(&node))
+        .collect();
+    std::iter::once(base.to_string())
+        .chain((1..).map(|i| format!("{base}{i}")))
+        .find(|name| !names.contains(name) && find_definitions(doc, name).is_empty())
+        .unwrap()
+}
+
+#[test]
+fn extract_function_test() {
+    let code = r#"def area(shape, scale):
+  match shape:
+    case Shape/Rect:
+      return shape.width * shape.height * scale
+
+(Dist a b) = (+ (* a a) (* b b))
+
+def main():
+  x = 2
+  return [x, 1]
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let extract = |start: (u32, u32), end: (u32, u32)| {
+        let range = lsp::Range::new(
+            lsp::Position::new(start.0, start.1),
+            lsp::Position::new(end.0, end.1),
+        );
+        extract_function(&doc, range).map(|action| apply_fix(&doc, &action))
+    };
+
+    assert_eq!(
+        extract((3, 13), (3, 39)).unwrap(),
+        code.replacen(
+            "return shape.width * shape.height * scale\n",
+            "return extracted(shape.width, shape.height) * scale\n\n\
+             def extracted(shape.width, shape.height):\n  return shape.width * shape.height\n",
+            1
+        )
+    );
+    assert_eq!(
+        extract((5, 16), (5, 23)).unwrap(),
+        code.replacen(
+            "(Dist a b) = (+ (* a a) (* b b))\n",
+            "(Dist a b) = (+ (extracted a) (* b b))\n\n(extracted a) = (* a a)\n",
+            1
+        )
+    );
+    // Only whole expressions can be extracted.
+    assert_eq!(extract((3, 19), (3, 39)), None);
+}
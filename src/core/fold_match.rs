@@ -0,0 +1,310 @@
+//! Refactorings between a `match` with explicit recursive calls and a `fold`.
+//!
+//! A `fold` calls itself on the fields of the matched value that are marked
+//! with `~`, so in a function whose whole body is a `match` on a parameter,
+//! a recursive call on such a field, passing the other parameters unchanged,
+//! is the same as using the field inside a `fold`:
+//!
+//! ```bend
+//! def sum(list):            def sum(list):
+//!   match list:               fold list:
+//!     case List/Cons:           case List/Cons:
+//!       return list.head          return list.head + list.tail
+//!         + sum(list.tail)
+//! ```
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::code_actions::node_at;
+use super::definitions::find_definitions;
+use super::document::Document;
+use super::lints::scopes;
+use super::types::{adt_of_constructor, adts};
+use crate::utils::tree::{descendants, named_children};
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+
+/// A `match` or `fold` that is the whole body of a function, on one of its
+/// parameters.
+struct Recursion<'t> {
+    function: String,
+    params: Vec<String>,
+    /// Index of the matched parameter.
+    matched: usize,
+    /// Arms of the `match` or `fold`.
+    cases: Vec<ts::Node<'t>>,
+    /// Names of the fields marked with `~` of the matched type.
+    recursive_fields: Vec<String>,
+    functional: bool,
+}
+
+/// Action to turn the `match` at `range` into a `fold`, or the `fold` into a
+/// `match`.
+pub fn fold_match(doc: &Document, range: lsp::Range) -> Option<lsp::CodeAction> {
+    let node = node_at(doc, range)?;
+    let node = std::iter::successors(Some(node), |node| node.parent()).find(|node| {
+        matches!(
+            node.kind(),
+            "match_statement" | "fun_match" | "fold_statement" | "fun_fold"
+        )
+    })?;
+    let recursion = recursion(doc, node)?;
+
+    let (title, keyword, mut edits) = match node.kind() {
+        "match_statement" | "fun_match" => (
+            "Convert 'match' to 'fold'",
+            "fold",
+            match_to_fold(doc, &recursion)?,
+        ),
+        _ => (
+            "Convert 'fold' to 'match'",
+            "match",
+            fold_to_match(doc, &recursion)?,
+        ),
+    };
+    let keyword_node = node.child(0)?;
+    edits.push(lsp::TextEdit {
+        range: doc.node_range(&keyword_node),
+        new_text: keyword.into(),
+    });
+
+    Some(lsp::CodeAction {
+        title: title.into(),
+        kind: Some(lsp::CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+fn recursion<'t>(doc: &'t Document, node: ts::Node<'t>) -> Option<Recursion<'t>> {
+    let definition = std::iter::successors(Some(node), |node| node.parent()).find(|node| {
+        node.parent()
+            .is_some_and(|parent| parent.parent().is_none())
+    })?;
+    let function = doc.node_text(&definition.child_by_field_name("name")?);
+    let functional = definition.kind() == "fun_function_definition";
+    if !functional && definition.kind() != "imp_function_definition" {
+        return None;
+    }
+    if functional && find_definitions(doc, &function).len() != 1 {
+        return None;
+    }
+
+    // The parameters, and the body without the lambdas of functional definitions.
+    let mut params = vec![];
+    let mut cursor = definition.walk();
+    for child in definition.named_children(&mut cursor) {
+        match child.kind() {
+            "parameters" => {
+                let mut cursor = child.walk();
+                for param in child.named_children(&mut cursor) {
+                    params.push(param);
+                }
+            }
+            "pattern" => params.push(child.named_child(0)?),
+            _ => {}
+        }
+    }
+    let mut body = named_children(definition)
+        .into_iter()
+        .find(|child| child.kind() == "body")?;
+    loop {
+        let statements: Vec<ts::Node> = named_children(body)
+            .into_iter()
+            .filter(|child| !child.kind().ends_with("comment"))
+            .collect();
+        let [statement] = statements[..] else {
+            return None;
+        };
+        if statement.kind() != "fun_lambda" {
+            if statement != node {
+                return None;
+            }
+            break;
+        }
+        let children = named_children(statement);
+        params.push(children.first()?.named_child(0)?);
+        body = *children.get(1)?;
+    }
+    if params.iter().any(|param| param.kind() != "identifier") {
+        return None;
+    }
+    let params: Vec<String> = params.iter().map(|param| doc.node_text(param)).collect();
+
+    let children = named_children(node);
+    let arg = children.iter().find(|child| child.kind() == "arg")?;
+    let arg = match arg.named_child(0)? {
+        term if term.kind() == "term" => term.named_child(0)?,
+        arg => arg,
+    };
+    if arg.kind() != "identifier" || children.iter().any(|child| child.kind().contains("with")) {
+        return None;
+    }
+    let matched = params
+        .iter()
+        .position(|param| *param == doc.node_text(&arg))?;
+
+    let cases: Vec<ts::Node> =
+        named_children(*children.iter().find(|child| child.kind() == "body")?)
+            .into_iter()
+            .filter(|child| child.kind() == "match_case")
+            .collect();
+    let adts = adts(doc);
+    let adt = cases.iter().find_map(|case| {
+        let pattern = named_children(*case)
+            .into_iter()
+            .find(|child| child.kind() == "match_pattern")?;
+        adt_of_constructor(&adts, &doc.node_text(&pattern))
+    })?;
+    let recursive_fields: Vec<String> = adt
+        .constructors
+        .iter()
+        .flat_map(|ctr| &ctr.fields)
+        .filter(|field| field.recursive)
+        .map(|field| field.name.clone())
+        .collect();
+    if recursive_fields.is_empty() {
+        return None;
+    }
+
+    // Calls with the parameters are only the same as the `fold` if the
+    // arms don't bind other values to them.
+    let rebinds_param = scopes::bindings(doc).iter().any(|binding| {
+        node.byte_range().contains(&binding.node.start_byte()) && params.contains(&binding.name)
+    });
+    if rebinds_param {
+        return None;
+    }
+
+    Some(Recursion {
+        function,
+        params,
+        matched,
+        cases,
+        recursive_fields,
+        functional,
+    })
+}
+
+/// Replace the recursive calls of a `match` with the recursive fields.
+fn match_to_fold(doc: &Document, recursion: &Recursion) -> Option<Vec<lsp::TextEdit>> {
+    let mut edits = vec![];
+    for field in recursive_field_uses(doc, recursion) {
+        // The field must be the matched argument of a recursive call.
+        let (call, args) = match field.parent()? {
+            args if args.kind() == "arguments" => (args.parent()?, named_children(args)),
+            call if call.kind() == "fun_application" => (call, named_children(call)[1..].to_vec()),
+            _ => return None,
+        };
+        let callee = call.named_child(0)?;
+        if callee.kind() != "identifier" || doc.node_text(&callee) != recursion.function {
+            return None;
+        }
+        if args.len() != recursion.params.len() || args[recursion.matched] != field {
+            return None;
+        }
+        let unchanged = args
+            .iter()
+            .zip(&recursion.params)
+            .enumerate()
+            .all(|(i, (arg, param))| {
+                i == recursion.matched
+                    || (arg.kind() == "identifier" && doc.node_text(arg) == *param)
+            });
+        if !unchanged {
+            return None;
+        }
+
+        edits.push(lsp::TextEdit {
+            range: doc.node_range(&call),
+            new_text: doc.node_text(&field),
+        });
+    }
+    (!edits.is_empty()).then_some(edits)
+}
+
+/// Replace the recursive fields of a `fold` with explicit recursive calls.
+fn fold_to_match(doc: &Document, recursion: &Recursion) -> Option<Vec<lsp::TextEdit>> {
+    let edits: Vec<lsp::TextEdit> = recursive_field_uses(doc, recursion)
+        .into_iter()
+        .map(|field| {
+            let mut args = recursion.params.clone();
+            args[recursion.matched] = doc.node_text(&field);
+            let new_text = match recursion.functional {
+                true => format!("({} {})", recursion.function, args.join(" ")),
+                false => format!("{}({})", recursion.function, args.join(", ")),
+            };
+            lsp::TextEdit {
+                range: doc.node_range(&field),
+                new_text,
+            }
+        })
+        .collect();
+    (!edits.is_empty()).then_some(edits)
+}
+
+/// Uses of the recursive fields of the matched value in the arms, like
+/// `list.tail`.
+fn recursive_field_uses<'t>(doc: &Document, recursion: &Recursion<'t>) -> Vec<ts::Node<'t>> {
+    let matched = &recursion.params[recursion.matched];
+    let fields: Vec<String> = recursion
+        .recursive_fields
+        .iter()
+        .map(|field| format!("{matched}.{field}"))
+        .collect();
+    recursion
+        .cases
+        .iter()
+        .flat_map(|case| descendants(*case))
+        .filter(|node| node.kind() == "identifier" && fields.contains(&doc.node_text(node)))
+        .collect()
+}
+
+#[test]
+fn fold_match_test() {
+    let code = r#"def sum(list, k):
+  match list:
+    case List/Cons:
+      return list.head * k + sum(list.tail, k)
+    case List/Nil:
+      return 0
+
+(Size t) = fold t {
+  Tree/Node: (+ t.left t.right)
+  Tree/Leaf: 1
+}
+
+def count(list, n):
+  match list:
+    case List/Cons:
+      return count(list.tail, n + 1)
+    case List/Nil:
+      return n
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let convert = |line: u32, character: u32| {
+        let position = lsp::Position::new(line, character);
+        fold_match(&doc, lsp::Range::new(position, position))
+            .map(|action| (action.title.clone(), apply_fix(&doc, &action)))
+    };
+
+    let (title, text) = convert(1, 2).unwrap();
+    assert_eq!(title, "Convert 'match' to 'fold'");
+    assert!(text.starts_with(
+        "def sum(list, k):\n  fold list:\n    case List/Cons:\n      return list.head * k + list.tail\n"
+    ));
+
+    let (title, text) = convert(8, 14).unwrap();
+    assert_eq!(title, "Convert 'fold' to 'match'");
+    assert!(text.contains("(Size t) = match t {\n  Tree/Node: (+ (Size t.left) (Size t.right))\n"));
+
+    // The recursive call changes the other parameter, so it needs `with`.
+    assert_eq!(convert(13, 2), None);
+}
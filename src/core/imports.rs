@@ -0,0 +1,643 @@
+//! Import statements of a Bend document and their resolution to files.
+//!
+//! Our tree-sitter grammar only understands the simplest forms of imports, so
+//! statements are located through the tree and then parsed from their text.
+//!
+//! The imports and names of the other modules are read from their files,
+//! and kept while the contents of the files don't change.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use bend::fun::parser::{FunParser, ParseBook};
+use bend::fun::Name;
+use lazy_static::lazy_static;
+use tower_lsp::lsp_types as lsp;
+
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use crate::utils::tree::descendants;
+
+/// An import statement, like `from Path import (a, b as c)` or `import (A, B/c)`.
+#[derive(Debug, Clone)]
+pub struct Import {
+    /// Range of the entire statement.
+    pub range: lsp::Range,
+    /// Module path of `from ... import` statements, and its range.
+    pub from: Option<(String, lsp::Range)>,
+    /// Whether this is a `from Path import *` statement.
+    pub glob: bool,
+    pub items: Vec<ImportItem>,
+}
+
+/// One of the names imported by an import statement.
+#[derive(Debug, Clone)]
+pub struct ImportItem {
+    /// Path of the module containing the imported name (possibly empty).
+    pub module: String,
+    /// The imported name, without its module path.
+    pub name: String,
+    pub alias: Option<String>,
+    /// Range of the imported name as written in the statement, including its path.
+    pub range: lsp::Range,
+}
+
+/// What an imported name refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// A top-level name defined in a module file.
+    Name(PathBuf),
+    /// An entire module file, whose names are accessed as `name/...`.
+    Module(PathBuf),
+    /// Nothing could be found.
+    Unresolved,
+}
+
+impl ImportItem {
+    /// Name this import binds in the importing file.
+    pub fn bind(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Full path of the import, as reported by the compiler.
+    pub fn source(&self) -> String {
+        if self.module.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}/{}", self.module, self.name)
+        }
+    }
+
+    /// Find what this import refers to, relative to the directory of the importing file.
+    pub fn resolve(&self, base_dir: &Path) -> Resolution {
+        if !self.module.is_empty() {
+            let file = module_file(base_dir, &self.module);
+            if file.is_file() {
+                return Resolution::Name(file);
+            }
+        }
+
+        let file = module_file(base_dir, &self.source());
+        if file.is_file() {
+            return Resolution::Module(file);
+        }
+
+        Resolution::Unresolved
+    }
+}
+
+/// Path of the file of a module path, relative to `base_dir`.
+pub fn module_file(base_dir: &Path, module: &str) -> PathBuf {
+    base_dir.join(format!("{module}.bend"))
+}
+
+/// Module path of a file relative to `base_dir`, like `lib/util`.
+pub fn module_path(base_dir: &Path, file: &Path) -> Option<String> {
+    let relative = file.strip_prefix(base_dir).ok()?.with_extension("");
+    let segments: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(segments.join("/"))
+}
+
+/// Directory against which the imports of a document are resolved.
+pub fn base_dir(doc: &Document) -> Option<PathBuf> {
+    let path = doc.url.to_file_path().ok()?;
+    path.parent().map(Path::to_path_buf)
+}
+
+/// Find the import statements of a document.
+pub fn imports(doc: &Document) -> Vec<Import> {
+    let Some(tree) = doc.tree.as_ref() else {
+        return vec![];
+    };
+
+    // Lines where import statements start. Imports are always top-level, and
+    // even when tree-sitter fails to parse them, they're inside a top-level node.
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut lines = vec![];
+    for node in root.children(&mut cursor) {
+        if matches!(node.kind(), "import_name" | "import_from" | "ERROR") {
+            let (start, end) = (node.start_position().row, node.end_position().row);
+            lines.extend(start..=end);
+        }
+    }
+    lines.dedup();
+
+    let mut imports: Vec<Import> = vec![];
+    for line in lines {
+        // Skip lines belonging to a multi-line statement we already parsed.
+        if imports
+            .last()
+            .is_some_and(|i| i.range.end.line as usize >= line)
+        {
+            continue;
+        }
+        if let Some(import) = parse_import(doc, line) {
+            imports.push(import);
+        }
+    }
+    imports
+}
+
+/// Find the imported names that are never used in the document, along with
+/// the statements importing them. Glob imports are never considered unused.
+pub fn unused_imports(doc: &Document) -> Vec<(Import, Vec<ImportItem>)> {
+    let Some(tree) = doc.tree.as_ref() else {
+        return vec![];
+    };
+    let imports = imports(doc);
+    let in_import = |position: lsp::Position| {
+        imports
+            .iter()
+            .any(|i| i.range.start <= position && position < i.range.end)
+    };
+
+    // Full names used in the document, like `List/map`.
+    let used: HashSet<String> = descendants(tree.root_node())
+        .filter(|node| node.kind() == "identifier")
+        .filter(|node| node.parent().is_some_and(|p| p.kind() != "identifier"))
+        .filter(|node| !in_import(ts_range_to_lsp(node.range()).start))
+        .map(|node| doc.node_text(&node))
+        .collect();
+    let is_used = |bind: &str| {
+        used.iter().any(|name| {
+            name.strip_prefix(bind)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    };
+
+    imports
+        .into_iter()
+        .filter(|import| !import.glob)
+        .filter_map(|import| {
+            let unused = import
+                .items
+                .iter()
+                .filter(|item| !is_used(item.bind()))
+                .cloned()
+                .collect::<Vec<_>>();
+            (!unused.is_empty()).then_some((import, unused))
+        })
+        .collect()
+}
+
+/// An import of a module that doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingModule {
+    /// Range of the module path in the import statement.
+    pub range: lsp::Range,
+    pub module: String,
+    /// File where the module would be.
+    pub file: PathBuf,
+    /// Names imported from the module.
+    pub names: Vec<String>,
+}
+
+/// Find the imports of modules that don't exist.
+///
+/// A `from Path import ...` statement imports the names of a missing module
+/// when neither `Path` nor some of the imported names are module files.
+pub fn missing_modules(doc: &Document) -> Vec<MissingModule> {
+    let Some(base_dir) = base_dir(doc) else {
+        return vec![];
+    };
+
+    let mut missing = vec![];
+    for import in imports(doc) {
+        match &import.from {
+            Some((module, range)) => {
+                let file = module_file(&base_dir, module);
+                let unresolved: Vec<String> = import
+                    .items
+                    .iter()
+                    .filter(|item| item.resolve(&base_dir) == Resolution::Unresolved)
+                    .map(|item| item.name.clone())
+                    .collect();
+                let missing_glob = import.glob && !base_dir.join(module).is_dir();
+                if !file.is_file() && (missing_glob || !unresolved.is_empty()) {
+                    missing.push(MissingModule {
+                        range: *range,
+                        module: module.clone(),
+                        file,
+                        names: unresolved,
+                    });
+                }
+            }
+            None => missing.extend(
+                import
+                    .items
+                    .iter()
+                    .filter(|item| item.resolve(&base_dir) == Resolution::Unresolved)
+                    .map(|item| MissingModule {
+                        range: item.range,
+                        module: item.source(),
+                        file: module_file(&base_dir, &item.source()),
+                        names: vec![],
+                    }),
+            ),
+        }
+    }
+    missing
+}
+
+lazy_static! {
+    /// Modules read from their files, by path.
+    static ref MODULES: Mutex<HashMap<PathBuf, Arc<Module>>> = Mutex::new(HashMap::new());
+}
+
+/// A module file, and what was read from it while it had these contents.
+struct Module {
+    /// Hash of the contents of the file.
+    hash: u64,
+    code: String,
+    imports: OnceLock<Vec<Import>>,
+    names: OnceLock<Option<HashSet<String>>>,
+}
+
+impl Module {
+    /// Read the module at `file`, reusing the previous reading of the same contents.
+    fn read(file: &Path) -> Option<Arc<Module>> {
+        let code = std::fs::read_to_string(file).ok()?;
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let mut modules = MODULES.lock().unwrap();
+        if let Some(module) = modules.get(file).filter(|module| module.hash == hash) {
+            return Some(module.clone());
+        }
+        let module = Arc::new(Module {
+            hash,
+            code,
+            imports: OnceLock::new(),
+            names: OnceLock::new(),
+        });
+        modules.insert(file.to_path_buf(), module.clone());
+        Some(module)
+    }
+
+    fn imports(&self, file: &Path) -> &[Import] {
+        self.imports.get_or_init(|| {
+            lsp::Url::from_file_path(file)
+                .map(|url| imports(&Document::new_with_text(url, &self.code)))
+                .unwrap_or_default()
+        })
+    }
+
+    /// Top-level names defined by the module, as the compiler reads them, or
+    /// `None` if it can't be parsed.
+    fn names(&self) -> Option<&HashSet<String>> {
+        self.names
+            .get_or_init(|| {
+                let book = FunParser::new(Name::new(""), &self.code, false)
+                    .parse_book(ParseBook::default())
+                    .ok()?;
+                Some(
+                    book.top_level_names()
+                        .map(|name| name.to_string())
+                        .collect(),
+                )
+            })
+            .as_ref()
+    }
+}
+
+/// Find the imported names that the module they're imported from doesn't
+/// define.
+pub fn unknown_imports(doc: &Document) -> Vec<ImportItem> {
+    let Some(base_dir) = base_dir(doc) else {
+        return vec![];
+    };
+
+    let mut modules = HashMap::new();
+    imports(doc)
+        .into_iter()
+        .flat_map(|import| import.items)
+        .filter(|item| {
+            let Resolution::Name(file) = item.resolve(&base_dir) else {
+                return false;
+            };
+            // Names can also be modules inside the directory of the module.
+            if module_file(&base_dir, &item.source()).is_file() {
+                return false;
+            }
+            let module = modules
+                .entry(file.clone())
+                .or_insert_with(|| Module::read(&file));
+            module
+                .as_ref()
+                .and_then(|module| module.names())
+                .is_some_and(|names| !names.contains(&item.name))
+        })
+        .collect()
+}
+
+/// An import of the document that leads back to it through other imports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportCycle {
+    /// Range of the import in the document.
+    pub range: lsp::Range,
+    /// Module paths of the cycle, starting and ending with the document.
+    pub modules: Vec<String>,
+    /// Location of the import continuing the cycle in each of the other modules.
+    pub imports: Vec<lsp::Location>,
+}
+
+/// Find the imports of a document that are part of import cycles, with the
+/// shortest cycle each one is part of.
+///
+/// Files importing themselves are not cycles of this kind.
+pub fn import_cycles(doc: &Document) -> Vec<ImportCycle> {
+    let (Some(root), Ok(path)) = (base_dir(doc), doc.url.to_file_path()) else {
+        return vec![];
+    };
+    let module = |file: &Path| module_path(&root, file).unwrap_or_default();
+
+    let mut edges: HashMap<PathBuf, Vec<(lsp::Range, PathBuf)>> = HashMap::new();
+    edges.insert(path.clone(), imported_files(&path, &imports(doc), &root));
+    let mut cycles = vec![];
+    for (range, first) in edges[&path].clone() {
+        if first == path {
+            continue;
+        }
+
+        // Search the shortest path back to the document.
+        let mut previous: HashMap<PathBuf, (PathBuf, lsp::Range)> = HashMap::new();
+        let mut queue = VecDeque::from([first.clone()]);
+        while let Some(file) = queue.pop_front() {
+            if file == path {
+                break;
+            }
+            let imported = edges.entry(file.clone()).or_insert_with(|| {
+                Module::read(&file)
+                    .map(|module| imported_files(&file, module.imports(&file), &root))
+                    .unwrap_or_default()
+            });
+            for (import, next) in imported.clone() {
+                if next != first && !previous.contains_key(&next) {
+                    previous.insert(next.clone(), (file.clone(), import));
+                    queue.push_back(next);
+                }
+            }
+        }
+        if !previous.contains_key(&path) {
+            continue;
+        }
+
+        let mut modules = vec![module(&path)];
+        let mut imports = vec![];
+        let mut file = path.clone();
+        while let Some((importer, import)) = previous.get(&file) {
+            modules.push(module(importer));
+            imports.push(lsp::Location::new(
+                lsp::Url::from_file_path(importer).unwrap(),
+                *import,
+            ));
+            file = importer.clone();
+        }
+        modules.push(module(&path));
+        modules.reverse();
+        imports.reverse();
+        cycles.push(ImportCycle {
+            range,
+            modules,
+            imports,
+        });
+    }
+    cycles
+}
+
+/// Files imported by the `imports` of `file`, with the range of the import
+/// of each one.
+///
+/// Imports are resolved relative to `root`, the directory of the checked
+/// file, except for relative imports like `./Lib`, which are resolved
+/// relative to the importing file.
+fn imported_files(file: &Path, imports: &[Import], root: &Path) -> Vec<(lsp::Range, PathBuf)> {
+    let Some(dir) = file.parent() else {
+        return vec![];
+    };
+
+    let mut files: Vec<(lsp::Range, PathBuf)> = vec![];
+    for import in imports {
+        for item in &import.items {
+            let base = match item.source().starts_with('.') {
+                true => dir,
+                false => root,
+            };
+            let (range, file) = match (item.resolve(base), &import.from) {
+                (Resolution::Name(file), Some((_, range))) => (*range, file),
+                (Resolution::Name(file) | Resolution::Module(file), _) => (item.range, file),
+                (Resolution::Unresolved, _) => continue,
+            };
+            if !files.iter().any(|(_, other)| *other == file) {
+                files.push((range, file));
+            }
+        }
+    }
+    files
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Symbol(char),
+}
+
+/// Tokenize the text of a document starting from the beginning of `line`,
+/// until the end of a statement.
+fn tokenize_statement(doc: &Document, line: usize) -> Vec<(Token, lsp::Range)> {
+    let mut tokens = vec![];
+    let mut depth = 0;
+
+    for (row, text) in doc.text.lines_at(line).enumerate() {
+        let row = (line + row) as u32;
+        let chars: Vec<char> = text.chars().collect();
+        let mut col = 0;
+
+        while col < chars.len() {
+            let c = chars[col];
+            if c == '#' {
+                break;
+            } else if c.is_whitespace() {
+                col += 1;
+            } else if is_name_char(c) {
+                let start = col;
+                while col < chars.len() && is_name_char(chars[col]) {
+                    col += 1;
+                }
+                let word = chars[start..col].iter().collect();
+                tokens.push((Token::Word(word), char_range(row, start, col)));
+            } else {
+                match c {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                tokens.push((Token::Symbol(c), char_range(row, col, col + 1)));
+                col += 1;
+            }
+        }
+
+        if depth <= 0 {
+            break;
+        }
+    }
+
+    tokens
+}
+
+fn parse_import(doc: &Document, line: usize) -> Option<Import> {
+    let tokens = tokenize_statement(doc, line);
+    let mut tokens = tokens.into_iter().peekable();
+
+    let (first, first_range) = tokens.next()?;
+    if first_range.start.character != 0 {
+        return None;
+    }
+
+    let from = match first {
+        Token::Word(word) if word == "from" => {
+            let (Token::Word(path), range) = tokens.next()? else {
+                return None;
+            };
+            if tokens.next()?.0 != Token::Word("import".into()) {
+                return None;
+            }
+            Some((path, range))
+        }
+        Token::Word(word) if word == "import" => None,
+        _ => return None,
+    };
+
+    let mut end = first_range.end;
+    let mut glob = false;
+    let mut items = vec![];
+
+    let mut parse_item = |name: String, range: lsp::Range, alias: Option<String>| {
+        let (module, name) = match &from {
+            Some((path, _)) => (path.clone(), name),
+            None => match name.rsplit_once('/') {
+                Some((module, name)) => (module.to_string(), name.to_string()),
+                None => (String::new(), name),
+            },
+        };
+        items.push(ImportItem {
+            module,
+            name,
+            alias,
+            range,
+        });
+    };
+
+    let parenthesized = matches!(tokens.peek(), Some((Token::Symbol('('), _)));
+    if parenthesized {
+        tokens.next();
+    }
+
+    while let Some((token, range)) = tokens.next() {
+        end = range.end;
+        match token {
+            Token::Symbol('*') => glob = true,
+            Token::Symbol(')') => break,
+            Token::Symbol(',') => continue,
+            Token::Word(name) => {
+                let mut alias = None;
+                if matches!(tokens.peek(), Some((Token::Word(w), _)) if w == "as") {
+                    tokens.next();
+                    if let Some((Token::Word(a), r)) = tokens.next() {
+                        alias = Some(a);
+                        end = r.end;
+                    }
+                }
+                parse_item(name, range, alias);
+            }
+            Token::Symbol(_) => return None,
+        }
+        if !parenthesized {
+            break;
+        }
+    }
+
+    Some(Import {
+        range: lsp::Range::new(first_range.start, end),
+        from,
+        glob,
+        items,
+    })
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '/')
+}
+
+fn char_range(line: u32, start: usize, end: usize) -> lsp::Range {
+    lsp::Range::new(
+        lsp::Position::new(line, start as u32),
+        lsp::Position::new(line, end as u32),
+    )
+}
+
+#[test]
+fn parse_imports_test() {
+    let code = r#"from Foo/Bar import (baz, qux as q)
+import Lib
+import (A, B/c)
+from ./Rel import *
+
+def main():
+  return 0
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///test.bend").unwrap(), code);
+    let imports = imports(&doc);
+
+    assert_eq!(imports.len(), 4);
+
+    let sources: Vec<_> = imports
+        .iter()
+        .flat_map(|i| {
+            i.items
+                .iter()
+                .map(|item| (item.source(), item.bind().to_string()))
+        })
+        .collect();
+    assert_eq!(
+        sources,
+        [
+            ("Foo/Bar/baz".into(), "baz".into()),
+            ("Foo/Bar/qux".into(), "q".into()),
+            ("Lib".into(), "Lib".into()),
+            ("A".into(), "A".into()),
+            ("B/c".into(), "c".into()),
+        ]
+    );
+
+    assert_eq!(imports[0].range.end, lsp::Position::new(0, 35));
+    assert_eq!(imports[0].from.as_ref().unwrap().0, "Foo/Bar");
+    assert!(imports[3].glob);
+    assert_eq!(imports[3].from.as_ref().unwrap().0, "./Rel");
+}
+
+#[test]
+fn unused_imports_test() {
+    let code = r#"from Lib import (foo, bar)
+import Other
+import Types
+
+def main():
+  return (foo, Types/T/A)
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///test.bend").unwrap(), code);
+    let unused: Vec<_> = unused_imports(&doc)
+        .into_iter()
+        .flat_map(|(_, items)| items.into_iter().map(|item| item.source()))
+        .collect();
+
+    assert_eq!(unused, ["Lib/bar", "Other"]);
+}
@@ -0,0 +1,516 @@
+//! Refactoring to inline a function at its call sites.
+//!
+//! Functions whose body is a single expression can be inlined: a call is
+//! replaced by the body, with the arguments in place of the parameters. The
+//! variables the body binds are renamed when they would capture a variable of
+//! the arguments. Arguments that would be computed more than once are bound to
+//! a variable first.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::code_actions::{indentation, node_at, rewrite};
+use super::definitions::find_definitions;
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use super::lints::scopes::{self, Binding};
+use super::quick_fixes::whole_lines;
+use super::syntax_errors::valid_definitions;
+use crate::utils::tree::{descendants, named_children};
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+
+/// A function that can be inlined.
+struct Function<'t> {
+    name: String,
+    node: ts::Node<'t>,
+    params: Vec<ts::Node<'t>>,
+    /// The expression the function returns.
+    body: ts::Node<'t>,
+    functional: bool,
+}
+
+/// Actions to inline the call at `range`, and to inline every call to the
+/// function and remove it.
+pub fn inline_function(doc: &Document, range: lsp::Range) -> Vec<lsp::CodeAction> {
+    let Some(node) = node_at(doc, range) else {
+        return vec![];
+    };
+    let node = match node.parent() {
+        Some(parent) if parent.kind() == "identifier" => parent,
+        _ => node,
+    };
+    let bindings = scopes::bindings(doc);
+    let Some(function) = function(doc, &bindings, node) else {
+        return vec![];
+    };
+    let Some(edits) = inline_call(doc, &bindings, &function, node, &mut vec![]) else {
+        return vec![];
+    };
+    let mut actions = vec![action(doc, format!("Inline '{}'", function.name), edits)];
+
+    // Every use of the function must be a call that can be inlined, and the
+    // calls can't be nested in each other.
+    let uses: Vec<ts::Node> = descendants(doc.tree.as_ref().unwrap().root_node())
+        .filter(|node| is_function_reference(doc, &bindings, *node, &function.name))
+        .filter(|node| !function.node.byte_range().contains(&node.start_byte()))
+        .collect();
+    // Calls in the same statement can't bind their arguments to the same names.
+    let mut bound = vec![];
+    let edits: Option<Vec<lsp::TextEdit>> = uses
+        .iter()
+        .map(|node| inline_call(doc, &bindings, &function, *node, &mut bound))
+        .collect::<Option<Vec<_>>>()
+        .map(|edits| edits.concat());
+    let nested = uses.iter().enumerate().any(|(i, node)| {
+        uses[..i]
+            .iter()
+            .any(|other| call_of(*other).0.byte_range().contains(&node.start_byte()))
+    });
+    let is_main = matches!(function.name.as_str(), "main" | "Main");
+    if let Some(mut edits) = edits.filter(|_| !nested && !is_main) {
+        edits.push(lsp::TextEdit {
+            range: whole_lines(doc, ts_range_to_lsp(function.node.range()), true),
+            new_text: String::new(),
+        });
+        actions.push(action(
+            doc,
+            format!("Inline every call to '{}' and remove it", function.name),
+            edits,
+        ));
+    }
+    actions
+}
+
+fn action(doc: &Document, title: String, edits: Vec<lsp::TextEdit>) -> lsp::CodeAction {
+    lsp::CodeAction {
+        title,
+        kind: Some(lsp::CodeActionKind::REFACTOR_INLINE),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// The function referenced by `node`, if it can be inlined.
+///
+/// Only functions with a single rule of variable patterns, or a single
+/// `return` statement, can be inlined.
+fn function<'t>(
+    doc: &'t Document,
+    bindings: &[Binding],
+    node: ts::Node<'t>,
+) -> Option<Function<'t>> {
+    let name = doc.node_text(&node);
+    if !is_function_reference(doc, bindings, node, &name) {
+        return None;
+    }
+    let [definition] = &find_definitions(doc, &name)[..] else {
+        return None;
+    };
+    let definition = definition.node;
+    if !valid_definitions(doc).contains(&definition) {
+        return None;
+    }
+
+    let mut params = vec![];
+    let mut body = None;
+    let mut cursor = definition.walk();
+    for child in definition.named_children(&mut cursor) {
+        match child.kind() {
+            "parameters" => {
+                let mut cursor = child.walk();
+                params.extend(child.named_children(&mut cursor));
+            }
+            "pattern" => params.push(child.named_child(0)?),
+            "body" => body = Some(child),
+            _ => {}
+        }
+    }
+    if params.iter().any(|param| param.kind() != "identifier") {
+        return None;
+    }
+
+    let functional = definition.kind() == "fun_function_definition";
+    let mut cursor = body?.walk();
+    let statements: Vec<ts::Node> = body?
+        .named_children(&mut cursor)
+        .filter(|child| !child.kind().ends_with("comment"))
+        .collect();
+    let body = match (functional, &statements[..]) {
+        (true, [term]) => *term,
+        (false, [statement]) if statement.kind() == "return_statement" => {
+            statement.named_child(0)?
+        }
+        _ => return None,
+    };
+
+    Some(Function {
+        name,
+        node: definition,
+        params,
+        body,
+        functional,
+    })
+}
+
+/// Whether `node` is a reference to the top-level definition `name`, and
+/// not to a variable.
+fn is_function_reference(doc: &Document, bindings: &[Binding], node: ts::Node, name: &str) -> bool {
+    node.kind() == "identifier"
+        && node.parent().is_some_and(|parent| {
+            parent.kind() != "identifier" && parent.child_by_field_name("name") != Some(node)
+        })
+        && doc.node_text(&node) == name
+        && !is_local(bindings, node)
+}
+
+fn is_local(bindings: &[Binding], node: ts::Node) -> bool {
+    bindings
+        .iter()
+        .any(|binding| binding.node == node || binding.references.contains(&node))
+}
+
+/// The call of a reference to a function, and its arguments. A reference
+/// outside of a call is a call without arguments.
+fn call_of(node: ts::Node) -> (ts::Node, Vec<ts::Node>) {
+    match node.parent() {
+        Some(call) if call.kind() == "call_expression" && call.named_child(0) == Some(node) => {
+            let args = call.named_child(1).map(named_children).unwrap_or_default();
+            (call, args)
+        }
+        Some(call) if call.kind() == "fun_application" && call.named_child(0) == Some(node) => {
+            (call, named_children(call)[1..].to_vec())
+        }
+        _ => (node, vec![]),
+    }
+}
+
+/// The edits replacing the call of `node` by the body of `function`.
+///
+/// Arguments used more than once that aren't variables or literals are bound
+/// to a variable, named differently from the ones in `bound`, which is
+/// extended with the new names.
+fn inline_call(
+    doc: &Document,
+    bindings: &[Binding],
+    function: &Function,
+    node: ts::Node,
+    bound: &mut Vec<String>,
+) -> Option<Vec<lsp::TextEdit>> {
+    let (call, args) = call_of(node);
+    let functional = call.kind() == "fun_application"
+        || (call.kind() == "identifier"
+            && std::iter::successors(Some(call), |node| node.parent())
+                .any(|node| node.kind() == "fun_function_definition"));
+    if functional != function.functional || args.len() != function.params.len() {
+        return None;
+    }
+    if call.kind() == "identifier" && !function.params.is_empty() {
+        return None;
+    }
+
+    // The definitions the body uses must not be shadowed at the call site, and
+    // the function can't be recursive.
+    for reference in descendants(function.body) {
+        if reference.kind() != "identifier"
+            || reference
+                .parent()
+                .is_some_and(|parent| parent.kind() == "identifier")
+            || is_local(bindings, reference)
+        {
+            continue;
+        }
+        let name = doc.node_text(&reference);
+        let shadowed = bindings
+            .iter()
+            .any(|binding| binding.name == name && binding.scope.contains(&call.start_byte()));
+        if name == function.name || shadowed {
+            return None;
+        }
+    }
+
+    let mut replacements: HashMap<usize, String> = HashMap::new();
+
+    // Rename the variables of the body that would capture the arguments.
+    let arg_names: Vec<String> = args
+        .iter()
+        .flat_map(|arg| descendants(*arg))
+        .filter(|node| node.kind() == "identifier")
+        .map(|node| root_name(&doc.node_text(&node)).to_string())
+        .collect();
+    let mut used: Vec<String> = descendants(function.body)
+        .filter(|node| node.kind() == "identifier")
+        .map(|node| root_name(&doc.node_text(&node)).to_string())
+        .chain(arg_names.iter().cloned())
+        .collect();
+    let body = function.body.byte_range();
+    for binding in bindings {
+        if !body.contains(&binding.node.start_byte()) || !arg_names.contains(&binding.name) {
+            continue;
+        }
+        let renamed = (1..)
+            .map(|i| format!("{}{i}", binding.name))
+            .find(|name| !used.contains(name))
+            .unwrap();
+        used.push(renamed.clone());
+        replacements.insert(binding.node.id(), renamed.clone());
+        for reference in &binding.references {
+            let text = doc.node_text(reference);
+            let field = &text[root_name(&text).len()..];
+            replacements.insert(reference.id(), format!("{renamed}{field}"));
+        }
+    }
+
+    // Names for the variables bound to arguments. The parameters are all
+    // replaced, but the variables of the function containing the call mustn't
+    // be shadowed.
+    let params: Vec<String> = function
+        .params
+        .iter()
+        .map(|param| doc.node_text(param))
+        .collect();
+    let definition = std::iter::successors(Some(call), |node| node.parent()).find(|node| {
+        node.parent()
+            .is_some_and(|parent| parent.kind() == "source_file")
+    })?;
+    let mut taken: Vec<String> = used
+        .into_iter()
+        .filter(|name| !params.contains(name))
+        .chain(
+            descendants(definition)
+                .filter(|node| node.kind() == "identifier")
+                .map(|node| root_name(&doc.node_text(&node)).to_string()),
+        )
+        .chain(bound.iter().cloned())
+        .collect();
+
+    // Put the arguments in place of the parameters.
+    let mut lets: Vec<(String, ts::Node)> = vec![];
+    for (param, arg) in function.params.iter().zip(&args) {
+        let binding = bindings.iter().find(|binding| binding.node == *param)?;
+        let is_simple = matches!(
+            arg.kind(),
+            "identifier" | "integer" | "float" | "character" | "string" | "symbol"
+        );
+        let has_fields = binding
+            .references
+            .iter()
+            .any(|reference| doc.node_text(reference).contains('.'));
+        let var = (!is_simple && (binding.references.len() > 1 || has_fields)).then(|| {
+            let var = (0..)
+                .map(|i| match i {
+                    0 => binding.name.clone(),
+                    i => format!("{}{i}", binding.name),
+                })
+                .find(|name| !taken.contains(name))
+                .unwrap();
+            taken.push(var.clone());
+            bound.push(var.clone());
+            lets.push((var.clone(), *arg));
+            var
+        });
+        for reference in &binding.references {
+            let text = doc.node_text(reference);
+            let field = &text[root_name(&text).len()..];
+            let arg_text = match (&var, field.is_empty()) {
+                (Some(var), _) => format!("{var}{field}"),
+                (None, true) => parenthesize(doc, *arg, *reference, functional),
+                // Fields of matched parameters need a variable to refer to.
+                (None, false) if arg.kind() == "identifier" => {
+                    format!("{}{field}", doc.node_text(arg))
+                }
+                (None, false) => return None,
+            };
+            replacements.insert(reference.id(), arg_text);
+        }
+    }
+
+    let text = rewrite(doc, function.body, &|node| {
+        replacements.get(&node.id()).cloned()
+    });
+    let mut edits = vec![];
+    let text = if functional {
+        let lets: String = lets
+            .iter()
+            .map(|(var, arg)| format!("let {var} = {}; ", doc.node_text(arg)))
+            .collect();
+        let kind = match lets.is_empty() {
+            true => function.body.kind(),
+            false => "let_bind",
+        };
+        match needs_parens(kind, call, functional) {
+            true => format!("({lets}{text})"),
+            false => format!("{lets}{text}"),
+        }
+    } else {
+        if !lets.is_empty() {
+            // The variables are assigned before the statement with the call,
+            // where the arguments' own variables must be bound already.
+            let statement = std::iter::successors(Some(call), |node| node.parent())
+                .find(|node| node.parent().is_some_and(|parent| parent.kind() == "body"))?;
+            let hoistable = lets.iter().all(|(_, arg)| {
+                descendants(*arg).all(|node| {
+                    !bindings.iter().any(|binding| {
+                        binding.references.contains(&node)
+                            && statement.byte_range().contains(&binding.node.start_byte())
+                    })
+                })
+            });
+            if !hoistable {
+                return None;
+            }
+            let indent = indentation(doc, statement);
+            let start = doc.node_range(&statement).start;
+            let line_start = lsp::Position::new(start.line, 0);
+            edits.push(lsp::TextEdit {
+                range: lsp::Range::new(line_start, line_start),
+                new_text: lets
+                    .iter()
+                    .map(|(var, arg)| format!("{indent}{var} = {}\n", doc.node_text(arg)))
+                    .collect(),
+            });
+        }
+        match needs_parens(function.body.kind(), call, functional) {
+            true => format!("({text})"),
+            false => text,
+        }
+    };
+    edits.push(lsp::TextEdit {
+        range: doc.node_range(&call),
+        new_text: text,
+    });
+    Some(edits)
+}
+
+/// The variable of a name, without the field of names like `list.head`.
+fn root_name(name: &str) -> &str {
+    name.split('.').next().unwrap_or(name)
+}
+
+/// The text of `expr`, in parentheses if it needs them to replace `replaced`.
+fn parenthesize(doc: &Document, expr: ts::Node, replaced: ts::Node, functional: bool) -> String {
+    match needs_parens(expr.kind(), replaced, functional) {
+        true => format!("({})", doc.node_text(&expr)),
+        false => doc.node_text(&expr),
+    }
+}
+
+/// Whether an expression of kind `kind` needs parentheses to replace
+/// `replaced`, as an operand of an operator or as a term followed by others.
+fn needs_parens(kind: &str, replaced: ts::Node, functional: bool) -> bool {
+    let Some(parent) = replaced.parent() else {
+        return false;
+    };
+    if functional {
+        matches!(kind, "let_bind" | "use" | "fun_local_def" | "fun_ask")
+            && !matches!(parent.kind(), "body" | "let_next" | "use_next" | "ask_next")
+    } else {
+        matches!(
+            kind,
+            "binary_op" | "comparison_op" | "unary_op" | "imp_lambda"
+        ) && (matches!(parent.kind(), "binary_op" | "comparison_op" | "unary_op")
+            || (parent.kind() == "call_expression" && parent.named_child(0) == Some(replaced)))
+    }
+}
+
+#[test]
+fn inline_function_test() {
+    let code = r#"def square(x):
+  return x * x
+
+def main():
+  y = 3
+  return square(y + 1) + square(2)
+
+(Add n) = @x (+ x n)
+Main = let x = 1; (Add x)
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let inline = |line: u32, character: u32| {
+        let position = lsp::Position::new(line, character);
+        inline_function(&doc, lsp::Range::new(position, position))
+            .iter()
+            .map(|action| (action.title.clone(), apply_fix(&doc, action)))
+            .collect::<Vec<_>>()
+    };
+
+    let actions = inline(5, 10);
+    assert_eq!(actions[0].0, "Inline 'square'");
+    assert_eq!(
+        actions[0].1,
+        code.replacen(
+            "  return square(y + 1) +",
+            "  x = y + 1\n  return (x * x) +",
+            1
+        )
+    );
+    assert_eq!(actions[1].0, "Inline every call to 'square' and remove it");
+    assert!(actions[1]
+        .1
+        .starts_with("def main():\n  y = 3\n  x = y + 1\n  return (x * x) + (2 * 2)\n"));
+
+    // The lambda's variable is renamed so it doesn't capture the argument.
+    let actions = inline(8, 20);
+    assert_eq!(
+        actions[0].1,
+        code.replacen("(Add x)\n", "@x1 (+ x1 x)\n", 1)
+    );
+    assert!(actions[1].1.ends_with("\nMain = let x = 1; @x1 (+ x1 x)\n"));
+}
+
+#[test]
+fn inline_repeated_argument_test() {
+    let code = r#"def double(n):
+  return n + n
+
+def main():
+  return double(f(1)) + double(f(2))
+
+def later(g):
+  return lambda z: double(g(z))
+
+(Double n) = (+ n n)
+Main = (Double (f 1))
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let inline = |line: u32, character: u32| {
+        let position = lsp::Position::new(line, character);
+        inline_function(&doc, lsp::Range::new(position, position))
+            .iter()
+            .map(|action| apply_fix(&doc, action))
+            .collect::<Vec<_>>()
+    };
+
+    // Arguments used twice are computed once.
+    assert_eq!(
+        inline(4, 10),
+        [code.replacen(
+            "  return double(f(1)) +",
+            "  n = f(1)\n  return (n + n) +",
+            1
+        )]
+    );
+    assert_eq!(
+        inline(10, 9)[0],
+        code.replacen("Main = (Double (f 1))", "Main = let n = (f 1); (+ n n)", 1)
+    );
+
+    // Calls in the same statement bind different variables, but the argument
+    // of a lambda's variable can't be bound outside of it.
+    let all = Document::new_with_text(
+        doc.url.clone(),
+        &code.replacen("lambda z: double(g(z))", "g", 1),
+    );
+    let position = lsp::Position::new(4, 10);
+    let actions = inline_function(&all, lsp::Range::new(position, position));
+    assert!(apply_fix(&all, &actions[1])
+        .starts_with("def main():\n  n = f(1)\n  n1 = f(2)\n  return (n + n) + (n1 + n1)\n"));
+    assert_eq!(inline(4, 10).len(), 1);
+    assert_eq!(inline(7, 20), Vec::<String>::new());
+}
@@ -0,0 +1,172 @@
+//! Calls with a different number of arguments than the called definition has
+//! parameters.
+
+use std::collections::{HashMap, HashSet};
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::{valid_definitions, Lint, LintRule};
+use crate::core::definitions::{definitions, DefinitionKind};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::diagnostics::ts_range_to_lsp;
+use crate::core::document::Document;
+use crate::utils::tree::descendants;
+
+pub struct ArgumentCount;
+
+impl LintRule for ArgumentCount {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::ArgumentCount
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::WARNING
+    }
+
+    /// Only imperative calls, like `f(a, b)`, are checked for functions, as
+    /// partial application is common in functional syntax. Constructors are
+    /// checked in both syntaxes.
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        let arities = arities(doc);
+
+        valid_definitions(doc)
+            .into_iter()
+            .flat_map(descendants)
+            .filter_map(|node| {
+                let (callee, given) = match node.kind() {
+                    "call_expression" => {
+                        let arguments = node.named_child(1)?;
+                        (node.named_child(0)?, arguments.named_child_count())
+                    }
+                    "fun_application" => (node.named_child(0)?, node.named_child_count() - 1),
+                    _ => return None,
+                };
+                if callee.kind() != "identifier" {
+                    return None;
+                }
+
+                let name = doc.node_text(&callee);
+                let (kind, expected) = *arities.get(&name)?;
+                let checked = node.kind() == "call_expression" || kind != DefinitionKind::Function;
+                if !checked || given == expected {
+                    return None;
+                }
+
+                let message = format!(
+                    "'{name}' expects {}, but {given} {} given.",
+                    plural(expected, "argument"),
+                    if given == 1 { "was" } else { "were" }
+                );
+                Some(Lint::new(ts_range_to_lsp(callee.range()), message))
+            })
+            .collect()
+    }
+}
+
+/// Number of parameters of the functions, and of fields of the constructors,
+/// defined in the document. Definitions that can't be linted, and functions
+/// whose rules disagree, are left out.
+fn arities(doc: &Document) -> HashMap<String, (DefinitionKind, usize)> {
+    let mut arities = HashMap::new();
+    let mut conflicting = vec![];
+    let valid: HashSet<usize> = valid_definitions(doc).iter().map(|def| def.id()).collect();
+
+    for def in definitions(doc) {
+        let top_level = match def.kind {
+            DefinitionKind::Constructor => def.node.parent(),
+            _ => Some(def.node),
+        };
+        if !top_level.is_some_and(|node| valid.contains(&node.id())) {
+            conflicting.push(def.name);
+            continue;
+        }
+
+        let arity = match (def.kind, def.node.kind()) {
+            (_, "imp_function_definition") => def
+                .node
+                .child_by_field_name("parameters")
+                .map_or(0, |params| params.named_child_count()),
+            (_, "fun_function_definition") => children_of_kind(def.node, "pattern"),
+            (_, "imp_type_constructor") => children_of_kind(def.node, "imp_type_constructor_field"),
+            (_, "fun_type_constructor") => {
+                let mut cursor = def.node.walk();
+                let fields = def
+                    .node
+                    .children(&mut cursor)
+                    .find(|child| child.kind() == "fun_type_constructor_fields");
+                fields.map_or(0, |fields| fields.named_child_count())
+            }
+            (DefinitionKind::Object, _) => {
+                let mut cursor = def.node.walk();
+                let count = def
+                    .node
+                    .children_by_field_name("field", &mut cursor)
+                    .count();
+                count
+            }
+            _ => continue,
+        };
+
+        match arities.insert(def.name.clone(), (def.kind, arity)) {
+            Some((_, previous)) if previous != arity => conflicting.push(def.name),
+            _ => {}
+        }
+    }
+
+    for name in conflicting {
+        arities.remove(&name);
+    }
+    arities
+}
+
+fn children_of_kind(node: ts::Node, kind: &str) -> usize {
+    let mut cursor = node.walk();
+    let count = node
+        .children(&mut cursor)
+        .filter(|child| child.kind() == kind)
+        .count();
+    count
+}
+
+fn plural(count: usize, word: &str) -> String {
+    match count {
+        1 => format!("1 {word}"),
+        _ => format!("{count} {word}s"),
+    }
+}
+
+#[test]
+fn argument_count_test() {
+    let code = r#"
+type Pair = (Pair a b)
+
+object Point { x, y }
+
+def add(a, b):
+  return a + b
+
+(Inc x) = (+ x 1)
+
+def main():
+  p = Point(1)
+  q = Pair/Pair(1, 2)
+  return add(1, 2, 3) + add(1)
+
+(Fun) = (Inc (Inc 1 2) (Pair/Pair 1))
+"#;
+    let lints: Vec<_> = super::test_lints(&ArgumentCount, code)
+        .into_iter()
+        .map(|(_, message)| message)
+        .collect();
+
+    assert_eq!(
+        lints,
+        [
+            "'Point' expects 2 arguments, but 1 was given.",
+            "'add' expects 2 arguments, but 3 were given.",
+            "'add' expects 2 arguments, but 1 was given.",
+            "'Pair/Pair' expects 2 arguments, but 1 was given.",
+        ]
+    );
+}
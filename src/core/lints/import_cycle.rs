@@ -0,0 +1,92 @@
+//! Imports leading back to the importing file through other modules.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::document::Document;
+use crate::core::imports;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+pub struct ImportCycle;
+
+impl LintRule for ImportCycle {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::ImportCycle
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::ERROR
+    }
+
+    /// Each module of the cycle is pointed to by its import of the next one.
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        imports::import_cycles(doc)
+            .into_iter()
+            .map(|cycle| {
+                let message = format!("Import cycle: {}.", cycle.modules.join(" -> "));
+                let related = cycle
+                    .imports
+                    .into_iter()
+                    .zip(cycle.modules.windows(2).skip(1))
+                    .map(|(location, modules)| lsp::DiagnosticRelatedInformation {
+                        location,
+                        message: format!("'{}' imports '{}'", modules[0], modules[1]),
+                    })
+                    .collect();
+                Lint {
+                    related_information: Some(related),
+                    ..Lint::new(cycle.range, message)
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn import_cycle_test() {
+    let dir = TempDir::new("import_cycle_test");
+    dir.write("A.bend", "from lib/B import b\n");
+    dir.write("lib/B.bend", "import main\n\ndef b():\n  return 1\n");
+    dir.write("Leaf.bend", "def leaf():\n  return 1\n");
+    let code = "from A import a\nimport (Leaf, main)\n";
+    let path = dir.write("main.bend", code);
+    let doc = Document::new_with_text(lsp::Url::from_file_path(&path).unwrap(), code);
+    let lints = ImportCycle.check(&doc);
+
+    assert_eq!(lints.len(), 1);
+    assert_eq!(
+        lints[0].message,
+        "Import cycle: main -> A -> lib/B -> main."
+    );
+    assert_eq!(lints[0].range.start, lsp::Position::new(0, 5));
+    let related: Vec<_> = lints[0]
+        .related_information
+        .iter()
+        .flatten()
+        .map(|info| {
+            let file = info.location.uri.to_file_path().unwrap();
+            (file, info.location.range.start, info.message.clone())
+        })
+        .collect();
+    assert_eq!(
+        related,
+        [
+            (
+                dir.join("A.bend"),
+                lsp::Position::new(0, 5),
+                "'A' imports 'lib/B'".into()
+            ),
+            (
+                dir.join("lib/B.bend"),
+                lsp::Position::new(0, 7),
+                "'lib/B' imports 'main'".into()
+            ),
+        ]
+    );
+
+    // Modules are read again when they change.
+    dir.write("lib/B.bend", "def b():\n  return 1\n");
+    assert!(ImportCycle.check(&doc).is_empty());
+}
@@ -0,0 +1,67 @@
+//! Imports of modules that don't exist.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::document::Document;
+use crate::core::imports;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+pub struct MissingModule;
+
+impl LintRule for MissingModule {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::MissingModule
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::ERROR
+    }
+
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        imports::missing_modules(doc)
+            .into_iter()
+            .map(|missing| {
+                let message = format!("Module '{}' was not found.", missing.module);
+                Lint::new(missing.range, message)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn missing_module_test() {
+    let dir = TempDir::new("missing_module_test");
+    dir.write("Lib.bend", "def foo():\n  return 1\n");
+    dir.write("Dir/mod.bend", "");
+    let code = r#"
+from Lib import foo
+from Missing import (a, b)
+from Dir import mod
+import (Lib/foo, Gone)
+from Dir import *
+"#;
+    let path = dir.join("main.bend");
+    let doc = Document::new_with_text(lsp::Url::from_file_path(&path).unwrap(), code);
+    let lints: Vec<_> = MissingModule
+        .check(&doc)
+        .into_iter()
+        .map(|lint| {
+            (
+                lint.range.start.line,
+                lint.range.start.character,
+                lint.message,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        lints,
+        [
+            (2, 5, "Module 'Missing' was not found.".into()),
+            (4, 17, "Module 'Gone' was not found.".into()),
+        ]
+    );
+}
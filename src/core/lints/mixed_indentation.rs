@@ -0,0 +1,68 @@
+//! Imperative definitions indented with both tabs and spaces.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::document::Document;
+
+pub struct MixedIndentation;
+
+impl LintRule for MixedIndentation {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::MixedIndentation
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::WARNING
+    }
+
+    /// The first indented line of each definition decides which character
+    /// the rest of its lines should be indented with.
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        doc.find_all("(imp_function_definition) @def")
+            .into_iter()
+            .filter(|def| def.parent().is_some_and(|p| p.kind() == "source_file"))
+            .flat_map(|def| {
+                let mut expected = None;
+                let lines = def.start_position().row + 1..=def.end_position().row;
+
+                lines.filter_map(move |row| {
+                    let line = doc.text.get_line(row)?.to_string();
+                    let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+                    if indent.is_empty() || line.trim().is_empty() {
+                        return None;
+                    }
+
+                    let expected = *expected.get_or_insert(indent.as_bytes()[0]);
+                    let unexpected = indent.bytes().any(|c| c != expected);
+                    unexpected.then(|| {
+                        let (used, other) = match expected {
+                            b'\t' => ("tabs", "spaces"),
+                            _ => ("spaces", "tabs"),
+                        };
+                        let range = lsp::Range::new(
+                            lsp::Position::new(row as u32, 0),
+                            lsp::Position::new(row as u32, indent.len() as u32),
+                        );
+                        Lint::new(
+                            range,
+                            format!("Indentation uses {other}, but this definition is indented with {used}."),
+                        )
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn mixed_indentation_test() {
+    let code = "def main():\n  x = 1\n\ty = 2\n  \tz = 3\n  return x\n\ndef tabs():\n\treturn 1\n";
+    let lints: Vec<_> = super::test_lints(&MixedIndentation, code)
+        .into_iter()
+        .map(|(range, _)| (range.start.line, range.end.character))
+        .collect();
+
+    assert_eq!(lints, [(2, 1), (3, 3)]);
+}
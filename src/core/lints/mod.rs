@@ -0,0 +1,172 @@
+//! Lints that run over the tree-sitter tree of a document, without the
+//! compiler, so they can be reported while typing.
+//!
+//! Each lint rule has its own diagnostic code, and its severity can be
+//! changed, or the rule turned off, in the `bend.lints` setting.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types as lsp;
+
+use super::diagnostic_code::DiagnosticCode;
+use super::document::Document;
+use super::syntax_errors::valid_definitions;
+
+mod argument_count;
+mod import_cycle;
+mod missing_module;
+mod mixed_indentation;
+mod number_overflow;
+pub(crate) mod scopes;
+mod self_import;
+mod shadowed_variable;
+mod unknown_import;
+mod unused_import;
+mod unused_variable;
+
+/// A problem found by a lint rule.
+#[derive(Debug, Clone)]
+pub struct Lint {
+    pub range: lsp::Range,
+    pub message: String,
+    pub related_information: Option<Vec<lsp::DiagnosticRelatedInformation>>,
+}
+
+impl Lint {
+    pub fn new(range: lsp::Range, message: String) -> Self {
+        Self {
+            range,
+            message,
+            related_information: None,
+        }
+    }
+}
+
+pub trait LintRule: Sync {
+    fn code(&self) -> DiagnosticCode;
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity;
+
+    fn check(&self, doc: &Document) -> Vec<Lint>;
+}
+
+/// Every lint rule, in the order their diagnostics are reported.
+pub const RULES: &[&dyn LintRule] = &[
+    &unused_variable::UnusedVariable,
+    &shadowed_variable::ShadowedVariable,
+    &number_overflow::NumberOverflow,
+    &mixed_indentation::MixedIndentation,
+    &argument_count::ArgumentCount,
+    &self_import::SelfImport,
+    &missing_module::MissingModule,
+    &unknown_import::UnknownImport,
+    &import_cycle::ImportCycle,
+    &unused_import::UnusedImport,
+];
+
+/// Severities of the lint rules chosen by the user.
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    /// Severity of each configured rule, or `None` if it was turned off.
+    severities: HashMap<DiagnosticCode, Option<lsp::DiagnosticSeverity>>,
+}
+
+impl LintConfig {
+    /// Read the configuration from an object like
+    /// `{ "unused-variable": "off", "shadowed-variable": "error" }`.
+    ///
+    /// Unknown codes and severities are ignored.
+    pub fn from_settings(settings: &serde_json::Value) -> Self {
+        let severities = settings
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(code, severity)| {
+                let code = DiagnosticCode::from_name(code)?;
+                let severity = match severity.as_str()? {
+                    "off" => None,
+                    "hint" => Some(lsp::DiagnosticSeverity::HINT),
+                    "information" => Some(lsp::DiagnosticSeverity::INFORMATION),
+                    "warning" => Some(lsp::DiagnosticSeverity::WARNING),
+                    "error" => Some(lsp::DiagnosticSeverity::ERROR),
+                    _ => return None,
+                };
+                Some((code, severity))
+            })
+            .collect();
+
+        Self { severities }
+    }
+
+    /// Severity of the diagnostics of a rule, or `None` if it's turned off.
+    pub fn severity(&self, rule: &dyn LintRule) -> Option<lsp::DiagnosticSeverity> {
+        match self.severities.get(&rule.code()) {
+            Some(severity) => *severity,
+            None => Some(rule.default_severity()),
+        }
+    }
+}
+
+/// Run every enabled lint rule over a document.
+pub fn lint_diagnostics(doc: &Document, config: &LintConfig) -> Vec<lsp::Diagnostic> {
+    if doc.tree.is_none() {
+        return vec![];
+    }
+
+    RULES
+        .iter()
+        .filter_map(|rule| Some((rule, config.severity(*rule)?)))
+        .flat_map(|(rule, severity)| {
+            let code = rule.code();
+            rule.check(doc)
+                .into_iter()
+                .map(move |lint| lsp::Diagnostic {
+                    range: lint.range,
+                    severity: Some(severity),
+                    code: Some(lsp::NumberOrString::String(code.as_str().into())),
+                    source: Some("bend".into()),
+                    message: lint.message,
+                    related_information: lint.related_information,
+                    tags: code.tags(),
+                    ..Default::default()
+                })
+        })
+        .collect()
+}
+
+/// Whether a diagnostic was reported by a lint rule.
+pub fn is_lint(diag: &lsp::Diagnostic) -> bool {
+    RULES
+        .iter()
+        .any(|rule| diag.code == Some(lsp::NumberOrString::String(rule.code().as_str().into())))
+}
+
+/// Find the lints of `code`, for the tests of each rule.
+#[cfg(test)]
+fn test_lints(rule: &dyn LintRule, code: &str) -> Vec<(lsp::Range, String)> {
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    rule.check(&doc)
+        .into_iter()
+        .map(|lint| (lint.range, lint.message))
+        .collect()
+}
+
+#[test]
+fn lint_config_test() {
+    let config = LintConfig::from_settings(&serde_json::json!({
+        "unused-variable": "off",
+        "shadowed-variable": "error",
+        "number-overflow": "loud",
+        "unknown-code": "hint",
+    }));
+
+    assert_eq!(config.severity(&unused_variable::UnusedVariable), None);
+    assert_eq!(
+        config.severity(&shadowed_variable::ShadowedVariable),
+        Some(lsp::DiagnosticSeverity::ERROR)
+    );
+    assert_eq!(
+        config.severity(&number_overflow::NumberOverflow),
+        Some(lsp::DiagnosticSeverity::ERROR)
+    );
+}
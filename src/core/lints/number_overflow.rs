@@ -0,0 +1,117 @@
+//! Integer literals that don't fit in Bend's 24-bit numbers.
+
+use std::num::IntErrorKind;
+
+use tower_lsp::lsp_types as lsp;
+
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::diagnostics::ts_range_to_lsp;
+use crate::core::document::Document;
+use crate::utils::tree::descendants;
+
+const U24_MAX: i64 = (1 << 24) - 1;
+const I24_MIN: i64 = -(1 << 23);
+const I24_MAX: i64 = (1 << 23) - 1;
+
+pub struct NumberOverflow;
+
+impl LintRule for NumberOverflow {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::NumberOverflow
+    }
+
+    /// The compiler rejects these literals too, but stops at the first one.
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::ERROR
+    }
+
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        let Some(tree) = doc.tree.as_ref() else {
+            return vec![];
+        };
+
+        descendants(tree.root_node())
+            .filter(|node| node.kind() == "integer")
+            .filter_map(|node| {
+                let text = doc.node_text(&node);
+                let message = match literal_value(&text)? {
+                    (true, Some(value)) if (I24_MIN..=I24_MAX).contains(&value) => return None,
+                    (false, Some(value)) if value <= U24_MAX => return None,
+                    (true, _) => format!(
+                        "Number literal '{text}' is outside of the range of i24 ({I24_MIN} to +{I24_MAX})."
+                    ),
+                    (false, _) => format!(
+                        "Number literal '{text}' is outside of the range of u24 (0 to {U24_MAX})."
+                    ),
+                };
+                Some(Lint::new(ts_range_to_lsp(node.range()), message))
+            })
+            .collect()
+    }
+}
+
+/// Whether an integer literal has a sign, and its value if it fits in an `i64`.
+///
+/// Literals Bend doesn't accept, like ones with the uppercase prefixes `0X`
+/// and `0B`, are left to the compiler's error.
+fn literal_value(text: &str) -> Option<(bool, Option<i64>)> {
+    let (signed, negative, digits) = match text.as_bytes().first() {
+        Some(b'+') => (true, false, &text[1..]),
+        Some(b'-') => (true, true, &text[1..]),
+        _ => (false, false, text),
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, digits),
+    };
+    let digits = digits.replace('_', "");
+
+    let value = match i64::from_str_radix(&digits, radix) {
+        Ok(value) => Some(if negative { -value } else { value }),
+        Err(err) if *err.kind() == IntErrorKind::PosOverflow => None,
+        Err(_) => return None,
+    };
+    Some((signed, value))
+}
+
+#[test]
+fn number_overflow_test() {
+    let code = r#"
+def main():
+  a = 16777215 + 16777216
+  b = -8388608 + +8388608
+  c = 0xFF_FFFF + 0x1_000_000 + 0b1 + 0XFFFFFFF
+  return 99999999999999999999999
+"#;
+    let lints: Vec<_> = super::test_lints(&NumberOverflow, code)
+        .into_iter()
+        .map(|(range, message)| (range.start.line, message))
+        .collect();
+
+    assert_eq!(
+        lints,
+        [
+            (
+                2,
+                "Number literal '16777216' is outside of the range of u24 (0 to 16777215).".into()
+            ),
+            (
+                3,
+                "Number literal '+8388608' is outside of the range of i24 (-8388608 to +8388607)."
+                    .into()
+            ),
+            (
+                4,
+                "Number literal '0x1_000_000' is outside of the range of u24 (0 to 16777215)."
+                    .into()
+            ),
+            (
+                5,
+                "Number literal '99999999999999999999999' is outside of the range of u24 (0 to 16777215)."
+                    .into()
+            ),
+        ]
+    );
+}
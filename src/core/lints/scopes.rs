@@ -0,0 +1,300 @@
+//! Local variables of the definitions of a document, and the references that
+//! use them.
+//!
+//! This is an approximation of Bend's scoping rules that is good enough for
+//! lints: when unsure whether a reference uses a variable, we assume it does.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use tree_sitter as ts;
+
+use super::valid_definitions;
+use crate::core::document::Document;
+use crate::utils::tree::descendants;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingKind {
+    /// A parameter of a top-level definition.
+    Parameter,
+    /// A parameter of a local definition.
+    LocalParameter,
+    /// A variable assigned in an imperative block, with `=`, `<-` or `use`.
+    Assignment,
+    /// A parameter of a lambda, or a variable of a list comprehension.
+    Lambda,
+    /// A variable bound by `let`, `use` or `ask` in functional syntax.
+    Let,
+    /// A variable bound by `match x = ...`, `fold`, `bend` and similar.
+    MatchBind,
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding<'t> {
+    pub name: String,
+    pub kind: BindingKind,
+    /// The identifier being bound.
+    pub node: ts::Node<'t>,
+    /// Bytes where the variable can be used.
+    pub scope: Range<usize>,
+    /// Bytes of the block containing an assignment. References inside the
+    /// block after the assignment can only use this binding.
+    pub block: Range<usize>,
+    /// References that may use this binding.
+    pub references: Vec<ts::Node<'t>>,
+}
+
+/// Find the local variables of the valid definitions of a document, and
+/// their references.
+pub fn bindings(doc: &Document) -> Vec<Binding<'_>> {
+    let mut bindings = vec![];
+    for def in valid_definitions(doc) {
+        let first = bindings.len();
+        for node in descendants(def) {
+            collect_bindings(doc, node, &mut bindings);
+        }
+
+        let binders: HashSet<usize> = bindings[first..].iter().map(|b| b.node.id()).collect();
+        for node in descendants(def) {
+            if binders.contains(&node.id()) || !is_reference(node) {
+                continue;
+            }
+            let text = variable_name(doc, node);
+            // Fields of a matched variable, like `list.head`, use the variable.
+            let name = text.split('.').next().unwrap_or_default();
+            resolve(&mut bindings[first..], name, node);
+        }
+    }
+
+    bindings
+}
+
+/// Mark the bindings that a reference may use.
+///
+/// The innermost binding is used, unless it's an assignment in a different
+/// block than the reference, like one of the branches of an `if`, in which
+/// case the outer bindings may be used too.
+fn resolve<'t>(bindings: &mut [Binding<'t>], name: &str, reference: ts::Node<'t>) {
+    let position = reference.start_byte();
+    let mut candidates: Vec<&mut Binding> = bindings
+        .iter_mut()
+        .filter(|b| b.name == name && b.scope.contains(&position))
+        .collect();
+    candidates.sort_by_key(|b| std::cmp::Reverse(b.scope.start));
+
+    for binding in candidates {
+        binding.references.push(reference);
+        if binding.kind != BindingKind::Assignment || binding.block.contains(&position) {
+            break;
+        }
+    }
+}
+
+fn collect_bindings<'t>(doc: &Document, node: ts::Node<'t>, bindings: &mut Vec<Binding<'t>>) {
+    let mut bind = |binder: ts::Node<'t>, kind, scope: Range<usize>, block: Range<usize>| {
+        for var in variables(binder) {
+            let name = variable_name(doc, var);
+            // Assignments to fields, like `(t.a, t.b) = t`, don't bind new variables.
+            if name.contains('.') {
+                continue;
+            }
+            bindings.push(Binding {
+                name,
+                kind,
+                node: var,
+                scope: scope.clone(),
+                block: block.clone(),
+                references: vec![],
+            });
+        }
+    };
+
+    match node.kind() {
+        "imp_function_definition" | "local_def_statement" => {
+            let kind = match node.kind() {
+                "local_def_statement" => BindingKind::LocalParameter,
+                _ => BindingKind::Parameter,
+            };
+            if let (Some(params), Some(body)) = (
+                node.child_by_field_name("parameters"),
+                child_of_kind(node, "body"),
+            ) {
+                bind(params, kind, body.byte_range(), body.byte_range());
+            }
+        }
+        "fun_function_definition" => {
+            let kind = match node.parent().map(|p| p.kind()) {
+                Some("source_file") => BindingKind::Parameter,
+                _ => BindingKind::LocalParameter,
+            };
+            let mut cursor = node.walk();
+            let patterns: Vec<_> = node
+                .children(&mut cursor)
+                .filter(|child| child.kind() == "pattern")
+                .collect();
+            if let Some(last) = patterns.last() {
+                let scope = last.end_byte()..node.end_byte();
+                for pattern in &patterns {
+                    bind(*pattern, kind, scope.clone(), scope.clone());
+                }
+            }
+        }
+        "assignment_statement" | "ask_statement" | "use_statement" => {
+            let binder = node
+                .child_by_field_name("pat")
+                .or_else(|| node.named_child(0));
+            let function = ancestors(node).find(|a| {
+                matches!(
+                    a.kind(),
+                    "imp_function_definition" | "local_def_statement" | "source_file"
+                )
+            });
+            if let (Some(binder), Some(function), Some(block)) = (binder, function, node.parent()) {
+                let scope = node.end_byte()..function.end_byte();
+                bind(binder, BindingKind::Assignment, scope, block.byte_range());
+            }
+        }
+        "imp_lambda" => {
+            if let Some(body) = node.child_by_field_name("body") {
+                let mut cursor = node.walk();
+                let params: Vec<_> = node
+                    .children(&mut cursor)
+                    .take_while(|child| child.start_byte() < body.start_byte())
+                    .collect();
+                for param in params {
+                    bind(
+                        param,
+                        BindingKind::Lambda,
+                        body.byte_range(),
+                        body.byte_range(),
+                    );
+                }
+            }
+        }
+        "fun_lambda" => {
+            if let Some(pattern) = child_of_kind(node, "pattern") {
+                let scope = pattern.end_byte()..node.end_byte();
+                bind(pattern, BindingKind::Lambda, scope.clone(), scope);
+            }
+        }
+        "let_bind" | "fun_ask" => {
+            let next = child_of_kind(node, "let_next").or_else(|| child_of_kind(node, "ask_next"));
+            if let (Some(pattern), Some(next)) = (child_of_kind(node, "pattern"), next) {
+                bind(
+                    pattern,
+                    BindingKind::Let,
+                    next.byte_range(),
+                    next.byte_range(),
+                );
+            }
+        }
+        "use" => {
+            if let (Some(var), Some(next)) = (node.named_child(0), child_of_kind(node, "use_next"))
+            {
+                bind(var, BindingKind::Let, next.byte_range(), next.byte_range());
+            }
+        }
+        "arg_bind" => {
+            // Keyword arguments of calls are field names, not variables.
+            let statement = ancestors(node).find(|a| {
+                matches!(
+                    a.kind(),
+                    "match_statement"
+                        | "fold_statement"
+                        | "switch_statement"
+                        | "bend_statement"
+                        | "fun_match"
+                        | "fun_fold"
+                        | "fun_switch"
+                        | "fun_bend"
+                        | "arguments"
+                )
+            });
+            if let (Some(statement), Some(var)) = (statement, node.child_by_field_name("field")) {
+                if statement.kind() != "arguments" {
+                    let scope = node.end_byte()..statement.end_byte();
+                    bind(var, BindingKind::MatchBind, scope.clone(), scope);
+                }
+            }
+        }
+        "list_comprehension" => {
+            if let Some(left) = child_of_kind(node, "for_clause")
+                .and_then(|clause| clause.child_by_field_name("left"))
+            {
+                bind(
+                    left,
+                    BindingKind::Lambda,
+                    node.byte_range(),
+                    node.byte_range(),
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Variables bound by a pattern, skipping constructor names.
+fn variables(pattern: ts::Node) -> impl Iterator<Item = ts::Node> {
+    descendants(pattern).filter(|node| {
+        node.kind() == "identifier"
+            && node.named_child_count() == 0
+            && !node.parent().is_some_and(|p| {
+                matches!(p.kind(), "identifier" | "match_pattern")
+                    || (p.kind() == "other_pattern" && p.child_by_field_name("name") == Some(*node))
+                    || (p.kind() == "arg_bind" && p.named_child(0) != Some(*node))
+            })
+    })
+}
+
+/// Name of a variable, including the leading underscore that our grammar
+/// parses as a separate error in names like `_unused`.
+fn variable_name(doc: &Document, node: ts::Node) -> String {
+    let text = doc.node_text(&node);
+    let underscore = node
+        .start_byte()
+        .checked_sub(1)
+        .and_then(|byte| doc.text.get_byte_slice(byte..node.start_byte()))
+        .is_some_and(|prefix| prefix == "_");
+    match underscore {
+        true => format!("_{text}"),
+        false => text,
+    }
+}
+
+/// Whether an identifier may be a reference to a local variable.
+fn is_reference(node: ts::Node) -> bool {
+    if node.kind() != "identifier" || node.named_child_count() != 0 {
+        return false;
+    }
+    let Some(parent) = node.parent() else {
+        return false;
+    };
+    let is_name = parent.child_by_field_name("name") == Some(node);
+    let is_field =
+        matches!(parent.kind(), "arg_bind" | "constructor") && parent.named_child(0) == Some(node);
+
+    !is_name
+        && !is_field
+        && !matches!(
+            parent.kind(),
+            "identifier"
+                | "match_pattern"
+                | "imp_type_constructor"
+                | "imp_type_constructor_field"
+                | "fun_type_constructor"
+                | "fun_type_constructor_fields"
+                | "object_field"
+        )
+}
+
+fn child_of_kind<'t>(node: ts::Node<'t>, kind: &str) -> Option<ts::Node<'t>> {
+    let mut cursor = node.walk();
+    let child = node
+        .children(&mut cursor)
+        .find(|child| child.kind() == kind);
+    child
+}
+
+fn ancestors(node: ts::Node) -> impl Iterator<Item = ts::Node> {
+    std::iter::successors(node.parent(), |node| node.parent())
+}
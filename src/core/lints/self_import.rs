@@ -0,0 +1,71 @@
+//! Files that import themselves.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::document::Document;
+use crate::core::imports::{self, Resolution};
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+pub struct SelfImport;
+
+impl LintRule for SelfImport {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::SelfImport
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::ERROR
+    }
+
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        let (Some(base_dir), Ok(path)) = (imports::base_dir(doc), doc.url.to_file_path()) else {
+            return vec![];
+        };
+        let lint = |range| Lint::new(range, "This file imports itself.".into());
+
+        imports::imports(doc)
+            .into_iter()
+            .flat_map(|import| match &import.from {
+                Some((module, range)) if imports::module_file(&base_dir, module) == path => {
+                    vec![lint(*range)]
+                }
+                _ => import
+                    .items
+                    .iter()
+                    .filter(|item| match item.resolve(&base_dir) {
+                        Resolution::Name(file) | Resolution::Module(file) => file == path,
+                        Resolution::Unresolved => false,
+                    })
+                    .map(|item| lint(item.range))
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn self_import_test() {
+    let dir = TempDir::new("self_import_test");
+    let code = r#"
+from main import helper
+import main
+import (Other, main/helper)
+
+def helper():
+  return 1
+"#;
+    let path = dir.write("main.bend", code);
+    dir.write("Other.bend", "def other():\n  return 2\n");
+
+    let doc = Document::new_with_text(lsp::Url::from_file_path(&path).unwrap(), code);
+    let lines: Vec<_> = SelfImport
+        .check(&doc)
+        .into_iter()
+        .map(|lint| (lint.range.start.line, lint.range.start.character))
+        .collect();
+
+    assert_eq!(lines, [(1, 5), (2, 7), (3, 15)]);
+}
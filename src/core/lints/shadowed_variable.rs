@@ -0,0 +1,101 @@
+//! Variables that hide another variable with the same name.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::scopes::{bindings, BindingKind};
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::diagnostics::ts_range_to_lsp;
+use crate::core::document::Document;
+
+pub struct ShadowedVariable;
+
+impl LintRule for ShadowedVariable {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::ShadowedVariable
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::WARNING
+    }
+
+    /// Reassignments in imperative blocks, and their functional counterpart of
+    /// rebinding a variable with `let`, are not reported, since they're the
+    /// usual way of updating a variable.
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        let bindings = bindings(doc);
+
+        bindings
+            .iter()
+            .filter(|inner| {
+                !matches!(
+                    inner.kind,
+                    BindingKind::Parameter | BindingKind::Assignment | BindingKind::Let
+                )
+            })
+            .filter_map(|inner| {
+                let position = inner.node.start_byte();
+                let outer = bindings.iter().find(|outer| {
+                    outer.name == inner.name
+                        && outer.node.start_byte() < position
+                        && outer.scope.contains(&position)
+                })?;
+
+                let mut lint = Lint::new(
+                    ts_range_to_lsp(inner.node.range()),
+                    format!(
+                        "Variable '{}' shadows a variable with the same name.",
+                        inner.name
+                    ),
+                );
+                lint.related_information = Some(vec![lsp::DiagnosticRelatedInformation {
+                    location: lsp::Location::new(
+                        doc.url.clone(),
+                        ts_range_to_lsp(outer.node.range()),
+                    ),
+                    message: format!("'{}' is first bound here", outer.name),
+                }]);
+                Some(lint)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn shadowed_variable_test() {
+    let code = r#"
+def main(x, list):
+  x = x + 1
+  f = lambda x: x * 2
+  def inner(list):
+    return list
+  return inner(f(x))
+
+(Foo a) = let a = (+ a 1); @b @a (+ a b)
+"#;
+    let lints: Vec<_> = super::test_lints(&ShadowedVariable, code)
+        .into_iter()
+        .map(|(range, message)| (range.start.line, range.start.character, message))
+        .collect();
+
+    assert_eq!(
+        lints,
+        [
+            (
+                3,
+                13,
+                "Variable 'x' shadows a variable with the same name.".into()
+            ),
+            (
+                4,
+                12,
+                "Variable 'list' shadows a variable with the same name.".into()
+            ),
+            (
+                8,
+                31,
+                "Variable 'a' shadows a variable with the same name.".into()
+            ),
+        ]
+    );
+}
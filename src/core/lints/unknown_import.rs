@@ -0,0 +1,79 @@
+//! Imported names that their module doesn't define.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::document::Document;
+use crate::core::imports;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+pub struct UnknownImport;
+
+impl LintRule for UnknownImport {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::UnknownImport
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::ERROR
+    }
+
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        imports::unknown_imports(doc)
+            .into_iter()
+            .map(|item| {
+                let message = format!(
+                    "Module '{}' has no top-level name '{}'.",
+                    item.module, item.name
+                );
+                Lint::new(item.range, message)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn unknown_import_test() {
+    let dir = TempDir::new("unknown_import_test");
+    dir.write("Lib.bend", "def foo():\n  return 1\n\ntype T:\n  A\n");
+    dir.write("Lib/sub.bend", "");
+    let code = r#"
+from Lib import (foo, T, T/A, sub, bar)
+import (Lib/baz, Lib/foo)
+"#;
+    let path = dir.join("main.bend");
+    let doc = Document::new_with_text(lsp::Url::from_file_path(&path).unwrap(), code);
+    let lints = || -> Vec<_> {
+        UnknownImport
+            .check(&doc)
+            .into_iter()
+            .map(|lint| {
+                (
+                    lint.range.start.line,
+                    lint.range.start.character,
+                    lint.message,
+                )
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        lints(),
+        [
+            (1, 35, "Module 'Lib' has no top-level name 'bar'.".into()),
+            (2, 8, "Module 'Lib' has no top-level name 'baz'.".into()),
+        ]
+    );
+
+    // Modules are read again when they change.
+    dir.write(
+        "Lib.bend",
+        "def foo():\n  return 1\n\ndef bar():\n  return 2\n\ntype T:\n  A\n",
+    );
+    assert_eq!(
+        lints(),
+        [(2, 8, "Module 'Lib' has no top-level name 'baz'.".into())]
+    );
+}
@@ -0,0 +1,63 @@
+//! Imported names that are never used.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::document::Document;
+use crate::core::imports;
+
+pub struct UnusedImport;
+
+impl LintRule for UnusedImport {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::UnusedImport
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::WARNING
+    }
+
+    /// When none of the names of a statement are used, the whole statement
+    /// is reported at once.
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        imports::unused_imports(doc)
+            .into_iter()
+            .flat_map(|(import, unused)| {
+                if unused.len() == import.items.len() {
+                    vec![Lint::new(import.range, "Unused import.".into())]
+                } else {
+                    unused
+                        .iter()
+                        .map(|item| {
+                            Lint::new(item.range, format!("Unused import '{}'.", item.bind()))
+                        })
+                        .collect()
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn unused_import_test() {
+    let code = r#"
+from Lists import (map, filter)
+import Other
+
+def main():
+  return map(lambda x: x + 1, [1, 2, 3])
+"#;
+    let lints: Vec<_> = super::test_lints(&UnusedImport, code)
+        .into_iter()
+        .map(|(range, message)| (range.start.line, message))
+        .collect();
+
+    assert_eq!(
+        lints,
+        [
+            (1, "Unused import 'filter'.".into()),
+            (2, "Unused import.".into())
+        ]
+    );
+}
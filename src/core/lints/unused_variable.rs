@@ -0,0 +1,74 @@
+//! Local variables that are never used.
+
+use tower_lsp::lsp_types as lsp;
+
+use super::scopes::bindings;
+use super::{Lint, LintRule};
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::diagnostics::ts_range_to_lsp;
+use crate::core::document::Document;
+
+pub struct UnusedVariable;
+
+impl LintRule for UnusedVariable {
+    fn code(&self) -> DiagnosticCode {
+        DiagnosticCode::UnusedVariable
+    }
+
+    fn default_severity(&self) -> lsp::DiagnosticSeverity {
+        lsp::DiagnosticSeverity::WARNING
+    }
+
+    /// Variables starting with `_` are meant to be unused.
+    fn check(&self, doc: &Document) -> Vec<Lint> {
+        bindings(doc)
+            .into_iter()
+            .filter(|binding| binding.references.is_empty() && !binding.name.starts_with('_'))
+            .map(|binding| {
+                Lint::new(
+                    ts_range_to_lsp(binding.node.range()),
+                    format!("Unused variable '{}'.", binding.name),
+                )
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn unused_variable_test() {
+    let code = r#"
+def main(unused_param, n):
+  x = 1
+  x = 2
+  if n == 0:
+    y = x
+  else:
+    y = 3
+  f = lambda a, b: a
+  match l = List/Cons(1, List/Nil):
+    case List/Cons:
+      return l.head + y + f(1, 2)
+    case List/Nil:
+      _ignored = 0
+      return 0
+
+(Head (List/Cons h t)) = h
+(Fst a) = let (b, c) = a; λd b
+"#;
+    let lints: Vec<_> = super::test_lints(&UnusedVariable, code)
+        .into_iter()
+        .map(|(range, message)| (range.start.line, message))
+        .collect();
+
+    assert_eq!(
+        lints,
+        [
+            (1, "Unused variable 'unused_param'.".into()),
+            (2, "Unused variable 'x'.".into()),
+            (8, "Unused variable 'b'.".into()),
+            (16, "Unused variable 't'.".into()),
+            (17, "Unused variable 'c'.".into()),
+            (17, "Unused variable 'd'.".into()),
+        ]
+    );
+}
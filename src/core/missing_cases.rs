@@ -0,0 +1,332 @@
+//! Code action to add the cases a pattern matching doesn't cover.
+//!
+//! Works on `match` and `fold` expressions, in both syntaxes, and on
+//! functions defined by pattern matching rules. The missing constructors are
+//! found from the `type` and `object` declarations of the document and from
+//! Bend's builtin types.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::code_actions::{indentation, node_at};
+use super::definitions::find_definitions;
+use super::diagnostic_code::DiagnosticCode;
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use super::types::{adt_of_constructor, adts, Adt, Constructor};
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+
+/// Action adding the missing cases of the pattern matching at `range`.
+///
+/// `diagnostics` are the diagnostics of the request, so the action fixes the
+/// non-exhaustive match reported there.
+pub fn add_missing_cases(
+    doc: &Document,
+    range: lsp::Range,
+    diagnostics: &[lsp::Diagnostic],
+) -> Option<lsp::CodeAction> {
+    let node = node_at(doc, range)?;
+    let adts = adts(doc);
+
+    let matching = std::iter::successors(Some(node), |node| node.parent()).find(|node| {
+        matches!(
+            node.kind(),
+            "match_statement" | "fold_statement" | "fun_match" | "fun_fold"
+        )
+    });
+    let (title, fixed, edit) = match matching {
+        Some(matching) => {
+            let edit = missing_arms(doc, &adts, matching)?;
+            ("Add missing cases", vec![matching], edit)
+        }
+        None => {
+            let definition = std::iter::successors(Some(node), |node| node.parent())
+                .find(|node| node.kind() == "fun_function_definition")?;
+            let (rules, edit) = missing_rules(doc, &adts, definition)?;
+            ("Add missing rules", rules, edit)
+        }
+    };
+
+    let non_exhaustive = Some(lsp::NumberOrString::String(
+        DiagnosticCode::NonExhaustiveMatch.as_str().into(),
+    ));
+    let fixed_diagnostics: Vec<lsp::Diagnostic> = diagnostics
+        .iter()
+        .filter(|diag| diag.code == non_exhaustive)
+        .filter(|diag| {
+            fixed.iter().any(|node| {
+                let range = ts_range_to_lsp(node.range());
+                diag.range.start <= range.end && range.start <= diag.range.end
+            })
+        })
+        .cloned()
+        .collect();
+
+    Some(lsp::CodeAction {
+        title: title.into(),
+        kind: Some(lsp::CodeActionKind::QUICKFIX),
+        diagnostics: (!fixed_diagnostics.is_empty()).then_some(fixed_diagnostics),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Insert a `case` for each missing constructor of a `match` or `fold`, after
+/// its last case.
+fn missing_arms(doc: &Document, adts: &[Adt], matching: ts::Node) -> Option<lsp::TextEdit> {
+    let mut cursor = matching.walk();
+    let body = matching
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "body")?;
+    let mut cursor = body.walk();
+    let cases: Vec<ts::Node> = body
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "match_case")
+        .collect();
+
+    let mut covered = vec![];
+    for case in &cases {
+        let mut cursor = case.walk();
+        let pattern = case
+            .named_children(&mut cursor)
+            .find(|child| child.kind() == "match_pattern")?;
+        covered.push(doc.node_text(&pattern));
+    }
+    let missing = missing_constructors(adts, &covered)?;
+
+    let last = cases.last()?;
+    let case_indent = indentation(doc, *last);
+    let new_text: String = if matching.kind().starts_with("fun_") {
+        let multiline = matching.start_position().row != last.start_position().row;
+        missing
+            .iter()
+            .map(|ctr| match multiline {
+                true => format!("\n{case_indent}{}: *", ctr.name),
+                false => format!("; {}: *", ctr.name),
+            })
+            .collect()
+    } else {
+        let mut cursor = last.walk();
+        let statement = last
+            .named_children(&mut cursor)
+            .find(|child| child.kind() == "body")
+            .and_then(|body| body.named_child(0))
+            .filter(|statement| statement.start_position().row != last.start_position().row);
+        let body_indent = match statement {
+            Some(statement) => indentation(doc, statement),
+            None => format!("{case_indent}  "),
+        };
+        missing
+            .iter()
+            .map(|ctr| format!("\n{case_indent}case {}:\n{body_indent}return *", ctr.name))
+            .collect()
+    };
+
+    let end = ts_range_to_lsp(last.range()).end;
+    Some(lsp::TextEdit {
+        range: lsp::Range::new(end, end),
+        new_text,
+    })
+}
+
+/// Add a rule for each missing constructor of a function defined by pattern
+/// matching, after its last rule.
+///
+/// Only functions that match on the constructors of a single argument are
+/// supported, as with more arguments the missing combinations are ambiguous.
+fn missing_rules<'t>(
+    doc: &'t Document,
+    adts: &[Adt],
+    definition: ts::Node<'t>,
+) -> Option<(Vec<ts::Node<'t>>, lsp::TextEdit)> {
+    let name = doc.node_text(&definition.child_by_field_name("name")?);
+    let rules: Vec<ts::Node> = find_definitions(doc, &name)
+        .into_iter()
+        .map(|def| def.node)
+        .filter(|node| node.kind() == "fun_function_definition")
+        .collect();
+
+    let patterns: Vec<Vec<Pattern>> = rules
+        .iter()
+        .map(|rule| {
+            let mut cursor = rule.walk();
+            let patterns: Vec<Pattern> = rule
+                .named_children(&mut cursor)
+                .filter(|child| child.kind() == "pattern")
+                .map(|pattern| classify_pattern(doc, adts, pattern))
+                .collect();
+            patterns
+        })
+        .collect();
+    let arity = patterns.first()?.len();
+    if arity == 0 || patterns.iter().any(|rule| rule.len() != arity) {
+        return None;
+    }
+
+    // The single argument matched on constructors.
+    let mut matched = (0..arity).filter(|&arg| {
+        patterns
+            .iter()
+            .any(|rule| matches!(rule[arg], Pattern::Constructor(_)))
+    });
+    let arg = matched.next()?;
+    if matched.next().is_some() {
+        return None;
+    }
+
+    let mut covered = vec![];
+    for rule in &patterns {
+        match &rule[arg] {
+            Pattern::Constructor(name) => covered.push(name.clone()),
+            Pattern::Variable | Pattern::Other => return None,
+        }
+    }
+    let missing = missing_constructors(adts, &covered)?;
+
+    let new_text: String = missing
+        .iter()
+        .map(|ctr| {
+            let args: Vec<String> = (0..arity)
+                .map(|i| match i == arg {
+                    true => constructor_pattern(ctr),
+                    false => "*".into(),
+                })
+                .collect();
+            format!("\n({name} {}) = *", args.join(" "))
+        })
+        .collect();
+
+    let end = ts_range_to_lsp(rules.last()?.range()).end;
+    let edit = lsp::TextEdit {
+        range: lsp::Range::new(end, end),
+        new_text,
+    };
+    Some((rules, edit))
+}
+
+enum Pattern {
+    /// A constructor, by its full name, with or without fields.
+    Constructor(String),
+    /// A variable, which matches anything.
+    Variable,
+    /// Anything else, like numbers and tuples.
+    Other,
+}
+
+fn classify_pattern(doc: &Document, adts: &[Adt], pattern: ts::Node) -> Pattern {
+    let Some(inner) = pattern.named_child(0) else {
+        return Pattern::Other;
+    };
+    let name = match inner.kind() {
+        "identifier" => inner,
+        "other_pattern" => match inner.named_child(0) {
+            Some(name) if name.kind() == "identifier" => name,
+            _ => return Pattern::Other,
+        },
+        _ => return Pattern::Other,
+    };
+
+    let name = doc.node_text(&name);
+    if adt_of_constructor(adts, &name).is_some() {
+        Pattern::Constructor(name)
+    } else if inner.kind() == "identifier" {
+        Pattern::Variable
+    } else {
+        Pattern::Other
+    }
+}
+
+/// The constructors of the matched type that are not in `covered`.
+///
+/// `None` if the cases are not all constructors of the same type, like when
+/// there's a default case, or if nothing is missing.
+fn missing_constructors<'a>(adts: &'a [Adt], covered: &[String]) -> Option<Vec<&'a Constructor>> {
+    let adt = adt_of_constructor(adts, covered.first()?)?;
+    if covered.iter().any(|name| adt.constructor(name).is_none()) {
+        return None;
+    }
+    let missing: Vec<&Constructor> = adt
+        .constructors
+        .iter()
+        .filter(|ctr| !covered.contains(&ctr.name))
+        .collect();
+    (!missing.is_empty()).then_some(missing)
+}
+
+/// A pattern binding every field of a constructor, like `(List/Cons head tail)`.
+fn constructor_pattern(ctr: &Constructor) -> String {
+    if ctr.fields.is_empty() {
+        return ctr.name.clone();
+    }
+    let fields: Vec<&str> = ctr.fields.iter().map(|field| field.name.as_str()).collect();
+    format!("({} {})", ctr.name, fields.join(" "))
+}
+
+#[test]
+fn add_missing_cases_test() {
+    let code = r#"type Shape:
+  Circle { radius }
+  Rect { width, height }
+  Point
+
+def area(shape):
+  match shape:
+    case Shape/Circle:
+      return 3 * shape.radius * shape.radius
+
+(Len List/Nil) = 0
+
+(Zip List/Nil ys) = List/Nil
+
+sum = @t fold t { Tree/Leaf: t.value }
+
+def main():
+  match x = 1:
+    case 0:
+      return 0
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let fill = |line: u32, character: u32| {
+        let position = lsp::Position::new(line, character);
+        add_missing_cases(&doc, lsp::Range::new(position, position), &[])
+            .map(|action| apply_fix(&doc, &action))
+    };
+
+    assert_eq!(
+        fill(8, 6).unwrap(),
+        code.replacen(
+            "shape.radius\n",
+            "shape.radius\n    case Shape/Rect:\n      return *\n    case Shape/Point:\n      return *\n",
+            1
+        )
+    );
+    assert_eq!(
+        fill(10, 1).unwrap(),
+        code.replacen(
+            "(Len List/Nil) = 0\n",
+            "(Len List/Nil) = 0\n(Len (List/Cons head tail)) = *\n",
+            1
+        )
+    );
+    assert_eq!(
+        fill(12, 6).unwrap(),
+        code.replacen(
+            "(Zip List/Nil ys) = List/Nil\n",
+            "(Zip List/Nil ys) = List/Nil\n(Zip (List/Cons head tail) *) = *\n",
+            1
+        )
+    );
+    assert_eq!(
+        fill(14, 20).unwrap(),
+        code.replacen("t.value }", "t.value; Tree/Node: * }", 1)
+    );
+    // Matches on numbers are not supported.
+    assert_eq!(fill(17, 2), None);
+}
@@ -1,3 +1,27 @@
+pub mod auto_import;
+pub mod check_cache;
+pub mod code_actions;
+pub mod completion;
+pub mod convert_syntax;
+pub mod definitions;
+pub mod deprecated;
+pub mod diagnostic_code;
 pub mod diagnostics;
 pub mod document;
+pub mod extract_function;
+pub mod fold_match;
+pub mod imports;
+pub mod inline_function;
+pub mod lints;
+pub mod missing_cases;
+pub mod organize_imports;
+pub mod quick_fixes;
+pub mod ranges;
+pub mod related_information;
+pub mod rename_files;
 pub mod semantic_token;
+pub mod suppression;
+pub mod syntax_errors;
+pub mod types;
+pub mod with_blocks;
+pub mod workspace;
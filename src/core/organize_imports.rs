@@ -0,0 +1,164 @@
+//! Source action to organize the import statements of a document.
+//!
+//! Imports are sorted and grouped, with the modules relative to the document,
+//! like `./Lib`, after the others. Imports of the same module are merged into
+//! a single statement, and the names that aren't used are removed.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use tower_lsp::lsp_types as lsp;
+
+use super::document::Document;
+use super::imports::{self, ImportItem};
+use super::quick_fixes::whole_lines;
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+
+/// The imports of one group.
+#[derive(Default)]
+struct Group {
+    /// Items of `import` statements, as written.
+    modules: BTreeSet<String>,
+    /// Items of `from ... import` statements, by module.
+    from: BTreeMap<String, BTreeSet<String>>,
+    /// Modules of `from ... import *` statements.
+    globs: BTreeSet<String>,
+}
+
+/// Action to organize the imports of a document, if they aren't already.
+pub fn organize_imports(doc: &Document) -> Option<lsp::CodeAction> {
+    let imports = imports::imports(doc);
+    let (first, last) = (imports.first()?, imports.last()?);
+    let unused: Vec<lsp::Range> = imports::unused_imports(doc)
+        .into_iter()
+        .flat_map(|(_, items)| items.into_iter().map(|item| item.range))
+        .collect();
+
+    // Absolute modules, and modules relative to the document.
+    let mut groups = [Group::default(), Group::default()];
+    let group = |module: &str| usize::from(module.starts_with('.'));
+    for import in &imports {
+        let items = import
+            .items
+            .iter()
+            .filter(|item| !unused.contains(&item.range));
+        match &import.from {
+            Some((module, _)) => {
+                let group = &mut groups[group(module)];
+                if import.glob {
+                    group.globs.insert(module.clone());
+                }
+                let names: BTreeSet<String> =
+                    items.map(|item| item_text(item, &item.name)).collect();
+                if !names.is_empty() {
+                    group.from.entry(module.clone()).or_default().extend(names);
+                }
+            }
+            None => {
+                for item in items {
+                    let source = item.source();
+                    groups[group(&source)]
+                        .modules
+                        .insert(item_text(item, &source));
+                }
+            }
+        }
+    }
+
+    let blocks: Vec<String> = groups
+        .iter()
+        .map(|group| {
+            let modules = group
+                .modules
+                .iter()
+                .map(|module| format!("import {module}\n"));
+            let globs = group
+                .globs
+                .iter()
+                .map(|module| format!("from {module} import *\n"));
+            let from = group.from.iter().map(|(module, names)| match names.len() {
+                1 => format!("from {module} import {}\n", names.first().unwrap()),
+                _ => format!(
+                    "from {module} import ({})\n",
+                    names.iter().cloned().collect::<Vec<_>>().join(", ")
+                ),
+            });
+            modules.chain(globs).chain(from).collect()
+        })
+        .filter(|block: &String| !block.is_empty())
+        .collect();
+    let organized = blocks.join("\n");
+
+    let span = doc.text.line_to_char(first.range.start.line as usize)
+        ..doc.text.line_to_char(last.range.end.line as usize + 1);
+    if doc.text.slice(span) == organized.as_str() {
+        return None;
+    }
+
+    // The organized imports take the place of the first statement.
+    let mut edits = vec![lsp::TextEdit {
+        range: whole_lines(doc, first.range, organized.is_empty()),
+        new_text: organized,
+    }];
+    edits.extend(imports[1..].iter().map(|import| lsp::TextEdit {
+        range: whole_lines(doc, import.range, true),
+        new_text: String::new(),
+    }));
+
+    Some(lsp::CodeAction {
+        title: "Organize imports".into(),
+        kind: Some(lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Text of an imported name, with its alias.
+fn item_text(item: &ImportItem, name: &str) -> String {
+    match &item.alias {
+        Some(alias) => format!("{name} as {alias}"),
+        None => name.to_string(),
+    }
+}
+
+#[test]
+fn organize_imports_test() {
+    let code = r#"from Lib import foo
+import Zed
+from ./Rel import *
+import (Other, Unused)
+from Lib import (bar, foo, baz as b)
+import Zed
+
+def main():
+  return (foo, bar, b, Zed/x, Other/y)
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let action = organize_imports(&doc).unwrap();
+    let organized = apply_fix(&doc, &action);
+    assert_eq!(
+        organized,
+        r#"import Other
+import Zed
+from Lib import (bar, baz as b, foo)
+
+from ./Rel import *
+
+def main():
+  return (foo, bar, b, Zed/x, Other/y)
+"#
+    );
+
+    // Organized imports are left as they are.
+    let doc = Document::new_with_text(doc.url.clone(), &organized);
+    assert!(organize_imports(&doc).is_none());
+
+    // Imports that are all unused are removed with the blank line after them.
+    let doc = Document::new_with_text(doc.url.clone(), "import A\n\ndef main():\n  return 0\n");
+    let action = organize_imports(&doc).unwrap();
+    assert_eq!(apply_fix(&doc, &action), "def main():\n  return 0\n");
+}
@@ -0,0 +1,605 @@
+//! Quick fixes for the diagnostics of the compiler and of the lints.
+
+use std::collections::HashMap;
+
+use bend::fun::parser::ParseBook;
+use lazy_static::lazy_static;
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::auto_import::{self, ImportStatement, Importable};
+use super::code_actions::node_at;
+use super::definitions::{definitions, find_definitions, DefinitionKind};
+use super::diagnostic_code::{quoted_names, DiagnosticCode};
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use super::imports;
+use super::lints::scopes;
+use crate::utils::tree::descendants;
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+lazy_static! {
+    /// Names of the definitions, types and constructors of Bend's builtins.
+    static ref BUILTIN_NAMES: Vec<String> = {
+        let book = ParseBook::builtins();
+        book.fun_defs
+            .keys()
+            .chain(book.imp_defs.keys())
+            .chain(book.hvm_defs.keys())
+            .chain(book.adts.keys())
+            .chain(book.ctrs.keys())
+            .map(|name| name.to_string())
+            .collect()
+    };
+}
+
+/// Quick fixes for a diagnostic reported in `doc`, which may import the
+/// `importables` of other modules.
+pub fn quick_fixes(
+    doc: &Document,
+    diag: &lsp::Diagnostic,
+    importables: &[Importable],
+) -> Vec<lsp::CodeAction> {
+    let Some(lsp::NumberOrString::String(code)) = &diag.code else {
+        return vec![];
+    };
+    let Some(code) = DiagnosticCode::from_name(code) else {
+        return vec![];
+    };
+
+    match code {
+        DiagnosticCode::UnusedDefinition => remove_definition(doc, diag).into_iter().collect(),
+        DiagnosticCode::MissingMain => vec![add_main(doc, diag)],
+        DiagnosticCode::UnusedVariable => unused_variable(doc, diag),
+        DiagnosticCode::RedundantMatch => remove_arms(doc, diag, "Remove redundant arm")
+            .into_iter()
+            .collect(),
+        DiagnosticCode::UnreachableMatch => remove_arms(doc, diag, "Remove unreachable arms")
+            .into_iter()
+            .collect(),
+        DiagnosticCode::UnboundVariable
+        | DiagnosticCode::UndefinedReference
+        | DiagnosticCode::UnboundConstructor => fix_misspelling(doc, diag, code)
+            .into_iter()
+            .chain(add_import(doc, diag, importables))
+            .collect(),
+        DiagnosticCode::MissingModule => create_module(doc, diag).into_iter().collect(),
+        _ => vec![],
+    }
+}
+
+/// Remove every rule of an unused definition.
+fn remove_definition(doc: &Document, diag: &lsp::Diagnostic) -> Option<lsp::CodeAction> {
+    let name = definition_name(&diag.message)?;
+    let rules = find_definitions(doc, name);
+    if rules.is_empty()
+        || rules
+            .iter()
+            .any(|def| def.kind == DefinitionKind::Constructor)
+    {
+        return None;
+    }
+
+    let edits = rules
+        .iter()
+        .map(|def| lsp::TextEdit {
+            range: whole_lines(doc, ts_range_to_lsp(def.node.range()), true),
+            new_text: String::new(),
+        })
+        .collect();
+    Some(action(
+        doc,
+        diag,
+        format!("Remove unused definition '{name}'"),
+        edits,
+    ))
+}
+
+/// Add an empty `main` definition at the end of the file, in the syntax the
+/// file uses the most.
+fn add_main(doc: &Document, diag: &lsp::Diagnostic) -> lsp::CodeAction {
+    let imperative = definitions(doc)
+        .iter()
+        .filter(|def| def.node.kind() == "imp_function_definition")
+        .count();
+    let functional = definitions(doc)
+        .iter()
+        .filter(|def| def.node.kind() == "fun_function_definition")
+        .count();
+    let main = if functional > imperative {
+        "main = *\n"
+    } else {
+        "def main():\n  return *\n"
+    };
+
+    let text = doc.text.to_string();
+    let separator = match text.trim_end_matches(' ') {
+        "" => "",
+        text if text.ends_with("\n\n") => "",
+        text if text.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    let end = doc.end_position();
+    let edit = lsp::TextEdit {
+        range: lsp::Range::new(end, end),
+        new_text: format!("{separator}{main}"),
+    };
+
+    action(doc, diag, "Add a 'main' definition".into(), vec![edit])
+}
+
+/// Rename an unused variable to start with `_`, or erase it with `*` where
+/// patterns allow it.
+fn unused_variable(doc: &Document, diag: &lsp::Diagnostic) -> Vec<lsp::CodeAction> {
+    let Some(name) = quoted_names(&diag.message).next() else {
+        return vec![];
+    };
+
+    let prefix = lsp::TextEdit {
+        range: lsp::Range::new(diag.range.start, diag.range.start),
+        new_text: "_".into(),
+    };
+    let mut actions = vec![action(
+        doc,
+        diag,
+        format!("Rename '{name}' to '_{name}'"),
+        vec![prefix],
+    )];
+
+    let in_pattern = node_at(doc, diag.range).is_some_and(|node| {
+        std::iter::successors(node.parent(), |node| node.parent())
+            .any(|ancestor| ancestor.kind() == "pattern")
+    });
+    if in_pattern {
+        let erase = lsp::TextEdit {
+            range: diag.range,
+            new_text: "*".into(),
+        };
+        actions.push(action(
+            doc,
+            diag,
+            format!("Erase '{name}' with '*'"),
+            vec![erase],
+        ));
+    }
+
+    actions
+}
+
+/// Remove the match arms of a diagnostic.
+///
+/// Diagnostics whose arms weren't found are reported on the name of their
+/// definition instead, so the fix is only offered if the range covers arms.
+fn remove_arms(doc: &Document, diag: &lsp::Diagnostic, title: &str) -> Option<lsp::CodeAction> {
+    let node = node_at(doc, diag.range)?;
+    let arms: Vec<ts::Node> = if node.kind() == "match_case" {
+        vec![node]
+    } else {
+        let mut cursor = node.walk();
+        node.named_children(&mut cursor)
+            .filter(|child| child.kind() != "comment")
+            .filter(|child| {
+                let range = ts_range_to_lsp(child.range());
+                diag.range.start <= range.start && range.end <= diag.range.end
+            })
+            .collect()
+    };
+    let (first, last) = (arms.first()?, arms.last()?);
+    let is_arms = arms.iter().all(|arm| arm.kind() == "match_case")
+        && ts_range_to_lsp(first.range()).start == diag.range.start
+        && ts_range_to_lsp(last.range()).end == diag.range.end;
+    if !is_arms {
+        return None;
+    }
+
+    let range = if is_alone_in_lines(doc, diag.range) {
+        whole_lines(doc, diag.range, false)
+    } else {
+        diag.range
+    };
+    let edit = lsp::TextEdit {
+        range,
+        new_text: String::new(),
+    };
+    Some(action(doc, diag, title.into(), vec![edit]))
+}
+
+/// Replace a name that doesn't exist with the closest name that does.
+fn fix_misspelling(
+    doc: &Document,
+    diag: &lsp::Diagnostic,
+    code: DiagnosticCode,
+) -> Option<lsp::CodeAction> {
+    let name = quoted_names(&diag.message).last()?;
+    let tree = doc.tree.as_ref()?;
+
+    // Uses of the name, in the definition the diagnostic is about if we know it.
+    let scope: Vec<ts::Node> = match definition_name(&diag.message) {
+        Some(def) => find_definitions(doc, def)
+            .into_iter()
+            .map(|def| def.node)
+            .collect(),
+        None => vec![tree.root_node()],
+    };
+    let uses: Vec<ts::Node> = scope
+        .into_iter()
+        .flat_map(descendants)
+        .filter(|node| node.kind() == "identifier")
+        .filter(|node| node.parent().is_some_and(|p| p.kind() != "identifier"))
+        .filter(|node| doc.node_text(node) == name)
+        .collect();
+    let first_use = uses.first()?.start_byte();
+
+    let mut candidates: Vec<String> = vec![];
+    if code == DiagnosticCode::UnboundVariable {
+        candidates.extend(
+            scopes::bindings(doc)
+                .into_iter()
+                .filter(|binding| binding.scope.contains(&first_use))
+                .map(|binding| binding.name),
+        );
+    }
+    candidates.extend(definitions(doc).into_iter().map(|def| def.name));
+    candidates.extend(
+        imports::imports(doc)
+            .iter()
+            .flat_map(|import| import.items.iter().map(|item| item.bind().to_string())),
+    );
+    candidates.extend(BUILTIN_NAMES.iter().cloned());
+
+    let max_distance = (name.chars().count() / 3).max(1);
+    let (suggestion, _) = candidates
+        .iter()
+        .filter(|candidate| candidate.as_str() != name)
+        .map(|candidate| (candidate, edit_distance(name, candidate)))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)?;
+
+    let edits = uses
+        .iter()
+        .map(|node| lsp::TextEdit {
+            range: ts_range_to_lsp(node.range()),
+            new_text: suggestion.clone(),
+        })
+        .collect();
+    let mut action = action(
+        doc,
+        diag,
+        format!("Change '{name}' to '{suggestion}'"),
+        edits,
+    );
+    action.is_preferred = Some(true);
+    Some(action)
+}
+
+/// Import a name that doesn't exist from the modules that define it.
+fn add_import(
+    doc: &Document,
+    diag: &lsp::Diagnostic,
+    importables: &[Importable],
+) -> Vec<lsp::CodeAction> {
+    let Some(name) = quoted_names(&diag.message).last() else {
+        return vec![];
+    };
+    auto_import::imports_for(importables, name)
+        .into_iter()
+        .map(|statement| {
+            let title = match &statement {
+                ImportStatement::From { module, name } => {
+                    format!("Import '{name}' from '{module}'")
+                }
+                ImportStatement::Module(module) => format!("Import module '{module}'"),
+            };
+            let edit = auto_import::import_edit(doc, &statement);
+            action(doc, diag, title, vec![edit])
+        })
+        .collect()
+}
+
+/// Create the file of a missing module, with a stub for each imported name.
+fn create_module(doc: &Document, diag: &lsp::Diagnostic) -> Option<lsp::CodeAction> {
+    let missing = imports::missing_modules(doc)
+        .into_iter()
+        .find(|missing| missing.range == diag.range)?;
+    let uri = lsp::Url::from_file_path(&missing.file).ok()?;
+    let text = missing
+        .names
+        .iter()
+        .map(|name| format!("def {name}():\n  return *\n"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let create = lsp::ResourceOp::Create(lsp::CreateFile {
+        uri: uri.clone(),
+        options: Some(lsp::CreateFileOptions {
+            overwrite: Some(false),
+            ignore_if_exists: Some(true),
+        }),
+        annotation_id: None,
+    });
+    let mut operations = vec![lsp::DocumentChangeOperation::Op(create)];
+    if !text.is_empty() {
+        let start = lsp::Position::new(0, 0);
+        operations.push(lsp::DocumentChangeOperation::Edit(lsp::TextDocumentEdit {
+            text_document: lsp::OptionalVersionedTextDocumentIdentifier { uri, version: None },
+            edits: vec![lsp::OneOf::Left(lsp::TextEdit {
+                range: lsp::Range::new(start, start),
+                new_text: text,
+            })],
+        }));
+    }
+
+    Some(lsp::CodeAction {
+        title: format!("Create module '{}'", missing.module),
+        kind: Some(lsp::CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diag.clone()]),
+        edit: Some(lsp::WorkspaceEdit {
+            document_changes: Some(lsp::DocumentChanges::Operations(operations)),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+fn action(
+    doc: &Document,
+    diag: &lsp::Diagnostic,
+    title: String,
+    edits: Vec<lsp::TextEdit>,
+) -> lsp::CodeAction {
+    lsp::CodeAction {
+        title,
+        kind: Some(lsp::CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diag.clone()]),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), edits)])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Name of the definition a compiler diagnostic is about, from messages like
+/// `In definition 'foo': ...`.
+fn definition_name(message: &str) -> Option<&str> {
+    message
+        .trim_start()
+        .starts_with("In definition '")
+        .then(|| quoted_names(message).next())
+        .flatten()
+}
+
+/// Whether `range` is the only code in its lines.
+fn is_alone_in_lines(doc: &Document, range: lsp::Range) -> bool {
+    let line = |line: u32| doc.text.get_line(line as usize).map(|l| l.to_string());
+    let (Some(first), Some(last)) = (line(range.start.line), line(range.end.line)) else {
+        return false;
+    };
+    let before = first.chars().take(range.start.character as usize);
+    let after = last.chars().skip(range.end.character as usize);
+    before.chain(after).all(char::is_whitespace)
+}
+
+/// Range of the lines of `range`, including the line break of the last one.
+///
+/// With `blank_after`, the following blank lines are included too when the
+/// code is preceded by a blank line, so removing it doesn't leave two
+/// consecutive blank lines.
+pub(crate) fn whole_lines(doc: &Document, range: lsp::Range, blank_after: bool) -> lsp::Range {
+    let is_blank = |line: u32| {
+        doc.text
+            .get_line(line as usize)
+            .is_some_and(|text| text.chars().all(char::is_whitespace))
+    };
+
+    let mut end = range.end.line + 1;
+    let preceded_by_blank = range.start.line == 0 || is_blank(range.start.line - 1);
+    if blank_after && preceded_by_blank {
+        while (end as usize) < doc.text.len_lines() && is_blank(end) {
+            end += 1;
+        }
+    }
+    lsp::Range::new(
+        lsp::Position::new(range.start.line, 0),
+        lsp::Position::new(end, 0),
+    )
+}
+
+/// Number of insertions, deletions, substitutions and transpositions of
+/// adjacent characters needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[test]
+fn quick_fixes_test() {
+    let code = r#"def unused_fn(x):
+  return x
+
+def length(list):
+  match list:
+    case List/Cons:
+      return 1 + lenght(list.tail)
+    case List/Nil:
+      return 0
+    case List/Nil:
+      return 1
+
+(Fst (a, b)) = a
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let diagnostic =
+        |code: DiagnosticCode, message: &str, range: (u32, u32, u32, u32)| lsp::Diagnostic {
+            range: lsp::Range::new(
+                lsp::Position::new(range.0, range.1),
+                lsp::Position::new(range.2, range.3),
+            ),
+            code: Some(lsp::NumberOrString::String(code.as_str().into())),
+            message: message.into(),
+            ..Default::default()
+        };
+    let fixes = |diag: lsp::Diagnostic| -> Vec<(String, String)> {
+        quick_fixes(&doc, &diag, &[])
+            .iter()
+            .map(|action| (action.title.clone(), apply_fix(&doc, action)))
+            .collect()
+    };
+
+    let unused = diagnostic(
+        DiagnosticCode::UnusedDefinition,
+        "In definition 'unused_fn':\n  Definition is unused.",
+        (0, 4, 0, 13),
+    );
+    assert_eq!(
+        fixes(unused)[0].1,
+        code.replacen("def unused_fn(x):\n  return x\n\n", "", 1)
+    );
+
+    let typo = diagnostic(
+        DiagnosticCode::UnboundVariable,
+        "In definition 'length':\n  Unbound variable 'lenght'.",
+        (3, 4, 3, 10),
+    );
+    let typo_fixes = fixes(typo.clone());
+    assert_eq!(typo_fixes[0].0, "Change 'lenght' to 'length'");
+    assert!(typo_fixes[0].1.contains("return 1 + length(list.tail)"));
+    let importable = Importable {
+        module: "lib/util".into(),
+        name: "lenght".into(),
+        kind: DefinitionKind::Function,
+    };
+    let import_fixes = quick_fixes(&doc, &typo, &[importable]);
+    assert_eq!(import_fixes[1].title, "Import 'lenght' from 'lib/util'");
+
+    let redundant = diagnostic(
+        DiagnosticCode::RedundantMatch,
+        "In definition 'length':\n  Redundant arm in 'match' expression.",
+        (9, 4, 10, 14),
+    );
+    assert_eq!(
+        fixes(redundant)[0].1,
+        code.replacen("    case List/Nil:\n      return 1\n", "", 1)
+    );
+
+    let unused_var = diagnostic(
+        DiagnosticCode::UnusedVariable,
+        "Unused variable 'b'.",
+        (12, 9, 12, 10),
+    );
+    let var_fixes = fixes(unused_var);
+    assert_eq!(var_fixes.len(), 2);
+    assert!(var_fixes[0].1.contains("(Fst (a, _b)) = a"));
+    assert!(var_fixes[1].1.contains("(Fst (a, *)) = a"));
+
+    let missing_main = diagnostic(
+        DiagnosticCode::MissingMain,
+        "File has no 'main' definition.",
+        (0, 0, 0, 0),
+    );
+    assert!(fixes(missing_main.clone())[0]
+        .1
+        .ends_with("(Fst (a, b)) = a\n\ndef main():\n  return *\n"));
+    // The definition is inserted at the end of the last line, inside the document.
+    let action = &quick_fixes(&doc, &missing_main, &[])[0];
+    let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+    let end = lsp::Position::new(code.lines().count() as u32, 0);
+    assert_eq!(changes[&doc.url][0].range, lsp::Range::new(end, end));
+}
+
+#[test]
+fn remove_arms_test() {
+    let code = r#"type T = A | B
+
+def foo(t):
+  match t:
+    case T/A:
+      return 0
+    case _:
+      return 1
+    case T/B:
+      return 2
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let unreachable = |range: lsp::Range| lsp::Diagnostic {
+        range,
+        code: Some(lsp::NumberOrString::String("unreachable-match".into())),
+        message: "Unreachable arms in 'match' expression. All cases after '*' will be ignored."
+            .into(),
+        ..Default::default()
+    };
+    let range =
+        |l1, c1, l2, c2| lsp::Range::new(lsp::Position::new(l1, c1), lsp::Position::new(l2, c2));
+
+    // The arms after the wildcard.
+    let fixes = quick_fixes(&doc, &unreachable(range(8, 4, 9, 14)), &[]);
+    assert_eq!(
+        apply_fix(&doc, &fixes[0]),
+        code.replacen("    case T/B:\n      return 2\n", "", 1)
+    );
+
+    // Diagnostics whose arms weren't found are on the name of the definition,
+    // which mustn't be removed, and neither must parts of arms.
+    assert!(quick_fixes(&doc, &unreachable(range(2, 4, 2, 7)), &[]).is_empty());
+    assert!(quick_fixes(&doc, &unreachable(range(5, 6, 7, 14)), &[]).is_empty());
+}
+
+#[test]
+fn create_module_test() {
+    let dir = TempDir::new("create_module_test");
+    let code = "from Lib/Utils import (foo, bar)\n";
+    let doc = Document::new_with_text(dir.url("main.bend"), code);
+    let diag = lsp::Diagnostic {
+        range: imports::missing_modules(&doc)[0].range,
+        code: Some(lsp::NumberOrString::String("missing-module".into())),
+        ..Default::default()
+    };
+
+    let fixes = quick_fixes(&doc, &diag, &[]);
+    assert_eq!(fixes[0].title, "Create module 'Lib/Utils'");
+    let Some(lsp::DocumentChanges::Operations(operations)) =
+        &fixes[0].edit.as_ref().unwrap().document_changes
+    else {
+        panic!("expected resource operations");
+    };
+    let file = dir.url("Lib/Utils.bend");
+    assert!(matches!(
+        &operations[0],
+        lsp::DocumentChangeOperation::Op(lsp::ResourceOp::Create(create)) if create.uri == file
+    ));
+    let lsp::DocumentChangeOperation::Edit(edit) = &operations[1] else {
+        panic!("expected an edit of the new file");
+    };
+    assert_eq!(edit.text_document.uri, file);
+    assert_eq!(
+        edit.edits,
+        [lsp::OneOf::Left(lsp::TextEdit {
+            range: lsp::Range::default(),
+            new_text: "def foo():\n  return *\n\ndef bar():\n  return *\n".into(),
+        })]
+    );
+}
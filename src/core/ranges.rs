@@ -0,0 +1,421 @@
+//! Precise ranges for compiler diagnostics.
+//!
+//! The compiler reports most diagnostics on entire definitions, or without
+//! any location at all, so we look for the code a diagnostic is about.
+
+use bend::diagnostics::DiagnosticOrigin;
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::definitions::{definitions, find_definitions};
+use super::diagnostic_code::{quoted_names, DiagnosticCode};
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use super::imports;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+use crate::utils::tree::descendants;
+
+/// Find the range of a diagnostic in `doc`.
+///
+/// `span` is the range reported by the compiler, if any. Diagnostics without
+/// one are located by the definitions and imports they mention, and fall back
+/// to the closest enclosing definition.
+///
+/// As the same diagnostic may be reported many times for a single definition,
+/// `occurrence` tells which of them this is, in order.
+///
+/// `message` must be the diagnostic message without color codes.
+pub fn diagnostic_range(
+    doc: &Document,
+    origin: &DiagnosticOrigin,
+    code: DiagnosticCode,
+    message: &str,
+    span: Option<lsp::Range>,
+    occurrence: usize,
+) -> lsp::Range {
+    let range = match origin {
+        DiagnosticOrigin::Parsing => span,
+        DiagnosticOrigin::Function(name) => definition_range(doc, name),
+        // Nets of generated definitions are named like `foo__C0`.
+        DiagnosticOrigin::Inet(name) => definition_range(doc, name.split("__").next().unwrap()),
+        DiagnosticOrigin::Book => book_range(doc, code, message),
+        // Readback errors come from the result of running the entry point.
+        DiagnosticOrigin::Readback => definition_range(doc, "main"),
+    };
+    let range = range
+        .or_else(|| span.map(|span| enclosing_definition(doc, span.start).unwrap_or(span)))
+        .or_else(|| definitions(doc).first().map(|def| def.name_range()))
+        .unwrap_or_else(|| document_range(doc));
+
+    precise_range(doc, origin, code, message, range, occurrence).unwrap_or(range)
+}
+
+/// Find the range of a diagnostic located in a builtin definition.
+///
+/// The builtins file isn't visible to the user, so these are reported on the
+/// first use of the definition in `doc`, if there is any.
+pub fn builtin_range(doc: &Document, origin: &DiagnosticOrigin) -> Option<lsp::Range> {
+    let name: &str = match origin {
+        DiagnosticOrigin::Function(name) => name,
+        DiagnosticOrigin::Inet(name) => name,
+        _ => return None,
+    };
+    let nodes = definitions(doc)
+        .iter()
+        .flat_map(|def| descendants(def.node))
+        .collect::<Vec<_>>();
+    references(doc, &nodes, name.split("__").next().unwrap())
+        .first()
+        .copied()
+}
+
+/// Find a more precise range for a diagnostic inside the code at `range`.
+fn precise_range(
+    doc: &Document,
+    origin: &DiagnosticOrigin,
+    code: DiagnosticCode,
+    message: &str,
+    range: lsp::Range,
+    occurrence: usize,
+) -> Option<lsp::Range> {
+    if code == DiagnosticCode::DeprecatedSyntax {
+        return data_keyword(doc, range.start.line);
+    }
+    // Redefinitions are reported on the entire repeated definition.
+    if code == DiagnosticCode::DuplicateDefinition && *origin == DiagnosticOrigin::Parsing {
+        return enclosing_definition(doc, range.start);
+    }
+
+    let DiagnosticOrigin::Function(def_name) = origin else {
+        return None;
+    };
+    let definitions = find_definitions(doc, def_name);
+    let nodes = definitions
+        .iter()
+        .flat_map(|def| descendants(def.node))
+        .collect::<Vec<_>>();
+
+    let candidates = match code {
+        // Messages about arms also quote the word 'match'.
+        DiagnosticCode::RedundantMatch => {
+            let ctr = quoted_names(message).last()?;
+            redundant_arms(doc, &nodes, ctr)
+        }
+        DiagnosticCode::UnreachableMatch => {
+            let var = quoted_names(message).last()?;
+            unreachable_arms(doc, &nodes, var)
+        }
+        DiagnosticCode::DeprecatedBuiltin => {
+            let name = quoted_names(message).next()?;
+            references(doc, &nodes, name)
+        }
+        DiagnosticCode::RepeatedBind => {
+            let var = quoted_names(message).next()?;
+            definitions
+                .iter()
+                .flat_map(|def| repeated_binds(doc, def.node, var))
+                .collect()
+        }
+        _ => vec![],
+    };
+
+    candidates.get(occurrence).or(candidates.last()).copied()
+}
+
+/// Range of the name of a definition or, if it isn't defined in the document,
+/// of the closest definition or import it comes from, like the type `T` of a
+/// constructor `T/A` or the module `Lib` of an imported `Lib/foo`.
+fn definition_range(doc: &Document, name: &str) -> Option<lsp::Range> {
+    let mut name = name;
+    loop {
+        if let Some(def) = find_definitions(doc, name).first() {
+            return Some(def.name_range());
+        }
+        if let Some(range) = import_range(doc, name) {
+            return Some(range);
+        }
+        name = name.rsplit_once('/')?.0;
+    }
+}
+
+/// Range of the import of a name or module.
+fn import_range(doc: &Document, name: &str) -> Option<lsp::Range> {
+    imports::imports(doc).into_iter().find_map(|import| {
+        if let Some(item) = import
+            .items
+            .iter()
+            .find(|item| item.source() == name || item.bind() == name)
+        {
+            return Some(item.range);
+        }
+        match import.from {
+            Some((module, range)) if module == name => Some(range),
+            _ => None,
+        }
+    })
+}
+
+/// Range of a diagnostic about the relationships between definitions, which
+/// the compiler reports without any location.
+fn book_range(doc: &Document, code: DiagnosticCode, message: &str) -> Option<lsp::Range> {
+    let mut names = quoted_names(message);
+
+    match code {
+        DiagnosticCode::MissingMain => None,
+        // Both definitions are mentioned by name, so highlight the last one.
+        DiagnosticCode::DuplicateDefinition => {
+            let name = names.next()?;
+            find_definitions(doc, name)
+                .last()
+                .map(|def| def.name_range())
+        }
+        DiagnosticCode::RecursionCycle => {
+            let cycle = message
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("* "))?;
+            definition_range(doc, cycle.split(" -> ").next()?.trim())
+        }
+        DiagnosticCode::ImportError if message.starts_with("Package '") => {
+            let (module, name) = (names.next()?, names.next()?);
+            import_range(doc, &format!("{module}/{name}")).or_else(|| import_range(doc, module))
+        }
+        _ => names.find_map(|name| definition_range(doc, name)),
+    }
+}
+
+/// Range of the entire document.
+fn document_range(doc: &Document) -> lsp::Range {
+    lsp::Range::new(lsp::Position::new(0, 0), doc.end_position())
+}
+
+/// Range of the name of the top-level definition containing `position`.
+fn enclosing_definition(doc: &Document, position: lsp::Position) -> Option<lsp::Range> {
+    definitions(doc)
+        .into_iter()
+        .rev()
+        .find(|def| {
+            let range = ts_range_to_lsp(def.node.range());
+            range.start <= position && position < range.end
+        })
+        .map(|def| def.name_range())
+}
+
+/// Arms matching a constructor that was already matched by a previous arm.
+fn redundant_arms(doc: &Document, nodes: &[ts::Node], ctr: &str) -> Vec<lsp::Range> {
+    let mut ranges = vec![];
+    for arms in nodes.iter().filter_map(match_arms) {
+        let patterns = arms
+            .iter()
+            .map(|arm| arm_pattern(doc, arm))
+            .collect::<Vec<_>>();
+        for (i, arm) in arms.iter().enumerate() {
+            let pattern = patterns[i].as_deref();
+            if pattern == Some(ctr) && patterns[..i].iter().any(|p| p.as_deref() == Some(ctr)) {
+                ranges.push(ts_range_to_lsp(arm.range()));
+            }
+        }
+    }
+    ranges
+}
+
+/// Arms after an arm matching any value with variable `var`.
+fn unreachable_arms(doc: &Document, nodes: &[ts::Node], var: &str) -> Vec<lsp::Range> {
+    let mut ranges = vec![];
+    for arms in nodes.iter().filter_map(match_arms) {
+        let Some(i) = arms
+            .iter()
+            .position(|arm| arm_pattern(doc, arm).as_deref() == Some(var))
+        else {
+            continue;
+        };
+        if let (Some(first), Some(last)) = (arms.get(i + 1), arms.last()) {
+            let start = ts_range_to_lsp(first.range()).start;
+            let end = ts_range_to_lsp(last.range()).end;
+            ranges.push(lsp::Range::new(start, end));
+        }
+    }
+    ranges
+}
+
+/// Variables bound more than once in the patterns of a rule, except for
+/// their first binding.
+fn repeated_binds(doc: &Document, rule: ts::Node, var: &str) -> Vec<lsp::Range> {
+    descendants(rule)
+        .filter(|node| node.kind() == "identifier" && doc.node_text(node) == var)
+        .filter(|node| {
+            node.parent()
+                .is_some_and(|parent| matches!(parent.kind(), "pattern" | "parameters"))
+        })
+        .skip(1)
+        .map(|node| ts_range_to_lsp(node.range()))
+        .collect()
+}
+
+/// Uses of a name, including in paths like `name/field`.
+fn references(doc: &Document, nodes: &[ts::Node], name: &str) -> Vec<lsp::Range> {
+    nodes
+        .iter()
+        .filter(|node| node.kind() == "identifier")
+        .filter(|node| node.parent().is_some_and(|p| p.kind() != "identifier"))
+        .filter(|node| doc.node_text(node) == name)
+        .map(|node| ts_range_to_lsp(node.range()))
+        .collect()
+}
+
+/// The `match_case` nodes of a `match` or `fold`, if `node` is one.
+fn match_arms<'t>(node: &ts::Node<'t>) -> Option<Vec<ts::Node<'t>>> {
+    if !matches!(
+        node.kind(),
+        "match_statement" | "fold_statement" | "fun_match" | "fun_fold"
+    ) {
+        return None;
+    }
+
+    let mut cursor = node.walk();
+    let body = node
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "body")?;
+    let mut cursor = body.walk();
+    let arms = body
+        .named_children(&mut cursor)
+        .filter(|child| child.kind() == "match_case")
+        .collect();
+    Some(arms)
+}
+
+/// Pattern of a match arm, as the compiler quotes it in its messages.
+fn arm_pattern(doc: &Document, arm: &ts::Node) -> Option<String> {
+    let mut cursor = arm.walk();
+    let pattern = arm
+        .named_children(&mut cursor)
+        .find(|child| child.kind() == "match_pattern")?;
+    match doc.node_text(&pattern).trim() {
+        // Wildcards are quoted as '*'.
+        "_" => Some("*".to_string()),
+        pattern => Some(pattern.to_string()),
+    }
+}
+
+/// The `data` keyword at the start of a line.
+fn data_keyword(doc: &Document, line: u32) -> Option<lsp::Range> {
+    let text = doc.text.get_line(line as usize)?;
+    text.to_string()
+        .starts_with("data ")
+        .then(|| lsp::Range::new(lsp::Position::new(line, 0), lsp::Position::new(line, 4)))
+}
+
+#[test]
+fn match_arm_ranges_test() {
+    use super::diagnostics::{check, lsp_diagnostics};
+    use super::lints::LintConfig;
+
+    let code = r#"type T = A | (B x)
+
+def foo(t):
+  match t:
+    case T/A:
+      return 0
+    case x:
+      return 1
+    case T/B:
+      return 2
+
+(Bar t) = match t {
+  T/A: 0
+  T/B: t.x
+  T/A: 1
+}
+
+def baz(t):
+  match t:
+    case T/A:
+      return 0
+    case _:
+      return 1
+    case T/B:
+      return 2
+
+def main():
+  return (foo(T/A), Bar(T/A), baz(T/A))
+"#;
+    let dir = TempDir::new("match_arm_ranges_test");
+    let path = dir.write("main.bend", code);
+
+    let doc = Document::new_with_text(lsp::Url::from_file_path(&path).unwrap(), code);
+    let ranges: Vec<_> = lsp_diagnostics(&doc, &check(&doc.url).unwrap(), &LintConfig::default())
+        .remove(&doc.url)
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.code.unwrap(), d.range, d.tags))
+        .collect();
+
+    let code = |code: &str| lsp::NumberOrString::String(code.into());
+    let range =
+        |l1, c1, l2, c2| lsp::Range::new(lsp::Position::new(l1, c1), lsp::Position::new(l2, c2));
+    assert!(ranges.contains(&(
+        code("unreachable-match"),
+        range(8, 4, 9, 14),
+        Some(vec![lsp::DiagnosticTag::UNNECESSARY])
+    )));
+    assert!(ranges.contains(&(
+        code("redundant-match"),
+        range(14, 2, 14, 8),
+        Some(vec![lsp::DiagnosticTag::UNNECESSARY])
+    )));
+    // Arms after a wildcard, which the compiler quotes as '*'.
+    assert!(ranges.contains(&(
+        code("unreachable-match"),
+        range(23, 4, 24, 14),
+        Some(vec![lsp::DiagnosticTag::UNNECESSARY])
+    )));
+}
+
+#[test]
+fn definition_ranges_test() {
+    use super::diagnostics::{check, lsp_diagnostics};
+    use super::lints::LintConfig;
+
+    let code = r#"type P = (P a b)
+
+(Fst (P/P x x)) = x
+
+(Foo) = (Bar)
+(Bar) = (Foo)
+
+def main():
+  return (Foo, Fst(P/P(1, 2)))
+"#;
+    let dir = TempDir::new("definition_ranges_test");
+    let path = dir.write("main.bend", code);
+
+    let doc = Document::new_with_text(lsp::Url::from_file_path(&path).unwrap(), code);
+    let ranges: Vec<_> = lsp_diagnostics(&doc, &check(&doc.url).unwrap(), &LintConfig::default())
+        .remove(&doc.url)
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.code.unwrap(), d.range))
+        .collect();
+
+    let code = |code: &str| lsp::NumberOrString::String(code.into());
+    let range = |line, start, end| {
+        lsp::Range::new(
+            lsp::Position::new(line, start),
+            lsp::Position::new(line, end),
+        )
+    };
+    // The second bind of `x`.
+    assert!(ranges.contains(&(code("repeated-bind"), range(2, 12, 13))));
+    // The first function of the cycle, although the compiler gives no location.
+    assert!(ranges.contains(&(code("recursion-cycle"), range(5, 1, 4))));
+}
+
+#[test]
+fn document_range_test() {
+    let url = lsp::Url::parse("file:///main.bend").unwrap();
+    let end = |code| document_range(&Document::new_with_text(url.clone(), code)).end;
+
+    assert_eq!(end(""), lsp::Position::new(0, 0));
+    assert_eq!(end("def main():\n  return 1\n"), lsp::Position::new(2, 0));
+    assert_eq!(end("def main():\n  return λx x"), lsp::Position::new(1, 13));
+}
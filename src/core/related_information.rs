@@ -0,0 +1,201 @@
+//! Related information for diagnostics that involve more than one location,
+//! like recursion cycles and duplicated definitions.
+
+use bend::diagnostics::DiagnosticOrigin;
+use itertools::Itertools;
+use tower_lsp::lsp_types as lsp;
+
+use super::definitions::{find_definitions, locate_definitions};
+use super::diagnostic_code::{quoted_names, DiagnosticCode};
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use super::imports;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+use crate::utils::tree::descendants;
+
+/// Find the other locations taking part in a diagnostic.
+///
+/// `message` must be the diagnostic message without color codes.
+pub fn related_information(
+    doc: &Document,
+    origin: &DiagnosticOrigin,
+    code: DiagnosticCode,
+    message: &str,
+) -> Option<Vec<lsp::DiagnosticRelatedInformation>> {
+    let related = match code {
+        DiagnosticCode::RecursionCycle => recursion_cycle(doc, message),
+        DiagnosticCode::DuplicateDefinition => duplicate_definition(doc, message),
+        DiagnosticCode::ImportShadow => import_shadow(doc, message),
+        DiagnosticCode::ImportConflict => import_conflict(doc, message),
+        DiagnosticCode::RepeatedBind => match origin {
+            DiagnosticOrigin::Function(def_name) => repeated_bind(doc, def_name, message),
+            _ => vec![],
+        },
+        _ => vec![],
+    };
+
+    (!related.is_empty()).then_some(related)
+}
+
+/// Every function taking part in the cycles, listed in the message like
+/// `* A -> B -> A`.
+fn recursion_cycle(doc: &Document, message: &str) -> Vec<lsp::DiagnosticRelatedInformation> {
+    message
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("* "))
+        .flat_map(|cycle| cycle.split(" -> "))
+        .map(str::trim)
+        .unique()
+        .flat_map(|name| {
+            locate_definitions(doc, name)
+                .into_iter()
+                .map(move |location| related(location, format!("'{name}' is part of the cycle")))
+        })
+        .collect()
+}
+
+/// The first definition of a name that was redefined, or every definition
+/// sharing the same name.
+fn duplicate_definition(doc: &Document, message: &str) -> Vec<lsp::DiagnosticRelatedInformation> {
+    let Some(name) = quoted_names(message).next() else {
+        return vec![];
+    };
+
+    if message.starts_with("Redefinition of builtin") {
+        vec![]
+    } else if message.starts_with("Redefinition of") {
+        find_definitions(doc, name)
+            .first()
+            .map(|def| {
+                let location = lsp::Location::new(doc.url.clone(), def.name_range());
+                related(location, format!("'{name}' is first defined here"))
+            })
+            .into_iter()
+            .collect()
+    } else {
+        locate_definitions(doc, name)
+            .into_iter()
+            .map(|location| related(location, format!("'{name}' is defined here")))
+            .collect()
+    }
+}
+
+/// Both the shadowing and shadowed imports, from a message like
+/// `The import 'A/x' shadows the imported name 'B/x'`.
+fn import_shadow(doc: &Document, message: &str) -> Vec<lsp::DiagnosticRelatedInformation> {
+    let Some((new, old)) = quoted_names(message).collect_tuple() else {
+        return vec![];
+    };
+
+    import_items(doc)
+        .filter_map(|item| {
+            let source = item.source();
+            let message = if source == old {
+                format!("'{old}' is imported here")
+            } else if source == new {
+                format!("'{new}' is imported here")
+            } else {
+                return None;
+            };
+            Some(related(
+                lsp::Location::new(doc.url.clone(), item.range),
+                message,
+            ))
+        })
+        .collect()
+}
+
+/// The import and the local definition with the same name.
+fn import_conflict(doc: &Document, message: &str) -> Vec<lsp::DiagnosticRelatedInformation> {
+    let Some(name) = quoted_names(message).next() else {
+        return vec![];
+    };
+
+    let imported = import_items(doc)
+        .filter(|item| item.bind() == name || item.source() == name)
+        .map(|item| {
+            let location = lsp::Location::new(doc.url.clone(), item.range);
+            related(location, format!("'{name}' is imported here"))
+        });
+
+    let defined = find_definitions(doc, name).into_iter().map(|def| {
+        let location = lsp::Location::new(doc.url.clone(), def.name_range());
+        related(location, format!("'{name}' is defined here"))
+    });
+
+    imported.chain(defined).collect()
+}
+
+/// Every place where the repeated variable is bound in the patterns of the
+/// rules of a definition.
+fn repeated_bind(
+    doc: &Document,
+    def_name: &str,
+    message: &str,
+) -> Vec<lsp::DiagnosticRelatedInformation> {
+    let Some(var) = quoted_names(message).next() else {
+        return vec![];
+    };
+
+    find_definitions(doc, def_name)
+        .iter()
+        .flat_map(|def| descendants(def.node))
+        .filter(|node| node.kind() == "identifier" && doc.node_text(node) == var)
+        .filter(|node| {
+            node.parent()
+                .is_some_and(|parent| matches!(parent.kind(), "pattern" | "parameters"))
+        })
+        .map(|node| {
+            let location = lsp::Location::new(doc.url.clone(), ts_range_to_lsp(node.range()));
+            related(location, format!("'{var}' is bound here"))
+        })
+        .collect()
+}
+
+fn import_items(doc: &Document) -> impl Iterator<Item = imports::ImportItem> {
+    imports::imports(doc)
+        .into_iter()
+        .flat_map(|import| import.items)
+}
+
+fn related(location: lsp::Location, message: String) -> lsp::DiagnosticRelatedInformation {
+    lsp::DiagnosticRelatedInformation { location, message }
+}
+
+#[test]
+fn related_information_test() {
+    use super::diagnostics::{check, lsp_diagnostics};
+    use super::lints::LintConfig;
+
+    let code = r#"
+type Pair = (Pair a b)
+
+(Foo (Pair/Pair x x)) = x
+
+def main():
+  return Foo(Pair/Pair(1, 2))
+"#;
+    let dir = TempDir::new("related_information_test");
+    let path = dir.write("main.bend", code);
+
+    let doc = Document::new_with_text(lsp::Url::from_file_path(&path).unwrap(), code);
+    let diagnostics = lsp_diagnostics(&doc, &check(&doc.url).unwrap(), &LintConfig::default())
+        .remove(&doc.url)
+        .unwrap();
+    let repeated = diagnostics
+        .iter()
+        .find(|d| d.code == Some(lsp::NumberOrString::String("repeated-bind".into())))
+        .unwrap();
+    let binds: Vec<_> = repeated
+        .related_information
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|info| info.location.range.start)
+        .collect();
+    assert_eq!(
+        binds,
+        [lsp::Position::new(3, 16), lsp::Position::new(3, 18)]
+    );
+}
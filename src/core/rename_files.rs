@@ -0,0 +1,227 @@
+//! Edits to the imports of the workspace when Bend files or directories are
+//! renamed or moved.
+//!
+//! Imports keep binding the same names after the edits: when the name of a
+//! module changes, the import is aliased to its previous name.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use bend::imports::normalize_path;
+use tower_lsp::lsp_types as lsp;
+
+use super::document::Document;
+use super::imports::{self, Resolution};
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+/// Edits to the imports of `docs` that refer to the files and directories
+/// moved by `renames`, given as pairs of old and new paths.
+///
+/// The imports of moved documents are updated for their new location too.
+pub fn rename_imports(
+    docs: &[Document],
+    renames: &[(PathBuf, PathBuf)],
+) -> HashMap<lsp::Url, Vec<lsp::TextEdit>> {
+    let moved = |path: &Path| {
+        let path = normalize_path(path);
+        renames
+            .iter()
+            .find_map(|(old, new)| match path.strip_prefix(old).ok()? {
+                rest if rest.as_os_str().is_empty() => Some(new.clone()),
+                rest => Some(new.join(rest)),
+            })
+            .unwrap_or(path)
+    };
+
+    docs.iter()
+        .map(|doc| (doc.url.clone(), import_edits(doc, &moved)))
+        .filter(|(_, edits)| !edits.is_empty())
+        .collect()
+}
+
+/// Edits to the imports of a document.
+fn import_edits(doc: &Document, moved: &dyn Fn(&Path) -> PathBuf) -> Vec<lsp::TextEdit> {
+    let (Some(base), Ok(path)) = (imports::base_dir(doc), doc.url.to_file_path()) else {
+        return vec![];
+    };
+    let Some(new_base) = moved(&path).parent().map(Path::to_path_buf) else {
+        return vec![];
+    };
+
+    let mut edits = vec![];
+    let mut edit = |range: lsp::Range, old: &str, new: String| {
+        if old != new {
+            edits.push(lsp::TextEdit {
+                range,
+                new_text: new,
+            });
+        }
+    };
+
+    for import in imports::imports(doc) {
+        match &import.from {
+            // Names of a module file.
+            Some((module, range)) if imports::module_file(&base, module).is_file() => {
+                let file = moved(&imports::module_file(&base, module));
+                if let Some(new) = module_text(&new_base, &file, is_relative(module)) {
+                    edit(*range, module, new);
+                }
+            }
+            // Module files inside a directory.
+            Some((module, range)) => {
+                let dir = moved(&base.join(module));
+                if let Some(new) = module_text(&new_base, &dir, is_relative(module)) {
+                    edit(*range, module, new);
+                }
+                for item in &import.items {
+                    let file = moved(&imports::module_file(&base, &item.source()));
+                    if file.parent() != Some(&dir) {
+                        continue;
+                    }
+                    let name = stem(&file);
+                    let new = match &item.alias {
+                        None if name != item.name => format!("{name} as {}", item.name),
+                        _ => name,
+                    };
+                    let range = lsp::Range::new(
+                        item.range.start,
+                        lsp::Position::new(
+                            item.range.start.line,
+                            item.range.start.character + item.name.chars().count() as u32,
+                        ),
+                    );
+                    edit(range, &item.name, new);
+                }
+            }
+            None => {
+                for item in &import.items {
+                    let source = item.source();
+                    let relative = is_relative(&source);
+                    let new = match item.resolve(&base) {
+                        Resolution::Name(file) => module_text(&new_base, &moved(&file), relative)
+                            .map(|module| format!("{module}/{}", item.name)),
+                        Resolution::Module(file) => {
+                            let file = moved(&file);
+                            module_text(&new_base, &file, relative).map(|module| {
+                                match stem(&file) {
+                                    name if name != item.name && item.alias.is_none() => {
+                                        format!("{module} as {}", item.name)
+                                    }
+                                    _ => module,
+                                }
+                            })
+                        }
+                        Resolution::Unresolved => None,
+                    };
+                    if let Some(new) = new {
+                        edit(item.range, &source, new);
+                    }
+                }
+            }
+        }
+    }
+    edits
+}
+
+/// Module path of a file or directory, as written in the imports of files in
+/// `base`.
+///
+/// Relative paths, like `./Lib` or `../Lib`, can point outside of `base`.
+fn module_text(base: &Path, path: &Path, relative: bool) -> Option<String> {
+    if !relative {
+        return imports::module_path(base, path);
+    }
+
+    let path = path.with_extension("");
+    let base: Vec<Component> = base.components().collect();
+    let path: Vec<Component> = path.components().collect();
+    let common = base.iter().zip(&path).take_while(|(a, b)| a == b).count();
+    let segments: Vec<String> = std::iter::repeat_n("..".to_string(), base.len() - common)
+        .chain(
+            path[common..]
+                .iter()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect();
+    match segments.first() {
+        Some(first) if first == ".." => Some(segments.join("/")),
+        _ => Some(format!("./{}", segments.join("/"))),
+    }
+}
+
+fn is_relative(module: &str) -> bool {
+    module.starts_with('.')
+}
+
+/// Name of the module of a file.
+fn stem(file: &Path) -> String {
+    file.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[test]
+fn rename_imports_test() {
+    let root = TempDir::new("rename_imports_test");
+    root.write("Top.bend", "def t():\n  return 1\n");
+    root.write("lib/Util.bend", "def helper():\n  return 1\n");
+
+    let main = r#"from lib/Util import helper
+import lib/Util
+import (lib/Util/helper, ./lib/Util as U)
+from lib import Util
+"#;
+    let util = "from ../Top import t\n";
+    let docs = [
+        Document::new_with_text(root.url("main.bend"), main),
+        Document::new_with_text(root.url("lib/Util.bend"), util),
+    ];
+    let renamed = |old: &str, new: &str| {
+        let edits = rename_imports(&docs, &[(root.join(old), root.join(new))]);
+        docs.iter()
+            .map(|doc| match edits.get(&doc.url) {
+                Some(edits) => {
+                    let action = lsp::CodeAction {
+                        edit: Some(lsp::WorkspaceEdit {
+                            changes: Some(HashMap::from([(doc.url.clone(), edits.clone())])),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    };
+                    apply_fix(doc, &action)
+                }
+                None => doc.text.to_string(),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Renaming a module aliases the imports binding its name.
+    assert_eq!(
+        renamed("lib/Util.bend", "lib/Tools.bend"),
+        [
+            r#"from lib/Tools import helper
+import lib/Tools as Util
+import (lib/Tools/helper, ./lib/Tools as U)
+from lib import Tools as Util
+"#,
+            util,
+        ]
+    );
+
+    // Moving a directory updates the relative imports of the files inside it.
+    assert_eq!(
+        renamed("lib", "pkg/lib"),
+        [
+            r#"from pkg/lib/Util import helper
+import pkg/lib/Util
+import (pkg/lib/Util/helper, ./pkg/lib/Util as U)
+from pkg/lib import Util
+"#,
+            "from ../../Top import t\n",
+        ]
+    );
+}
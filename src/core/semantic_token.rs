@@ -179,7 +179,7 @@ def main():
     // TODO: use TextProviderRope when the highlighting crate allows it
     let text = code.to_string();
     let highlights = highlighter
-        .highlight(&config, text.as_bytes(), None, |_| None)
+        .highlight(config, text.as_bytes(), None, |_| None)
         .unwrap();
 
     let mut stack = vec![];
@@ -201,7 +201,7 @@ def main():
     println!();
 
     let highlights = highlighter
-        .highlight(&config, text.as_bytes(), None, |_| None)
+        .highlight(config, text.as_bytes(), None, |_| None)
         .unwrap();
 
     let mut tokens = vec![];
@@ -214,7 +214,7 @@ def main():
             Result::Ok(HighlightEvent::HighlightStart(h)) => stack.push(h.0),
             Result::Ok(HighlightEvent::HighlightEnd) => drop(stack.pop()),
             Result::Ok(HighlightEvent::Source { mut start, end }) => {
-                stack
+                if let Some(token) = stack
                     .last()
                     .and_then(|curr| HIGHLIGHT_INDEX_TO_LSP_INDEX.get(curr))
                     .and_then(|type_index| {
@@ -227,7 +227,7 @@ def main():
                             start,
                             end,
                             &text[start..end],
-                            LEGEND_TOKEN_TYPE[*type_index as usize].as_str()
+                            LEGEND_TOKEN_TYPE[*type_index].as_str()
                         );
                         make_semantic_token(
                             &code,
@@ -237,7 +237,9 @@ def main():
                             &mut pre_start,
                         )
                     })
-                    .map(|token| tokens.push(token));
+                {
+                    tokens.push(token);
+                }
             }
             Err(_) => { /* log error? */ }
         }
@@ -0,0 +1,210 @@
+//! Line-level diagnostic suppression.
+//!
+//! A `# bend-ignore: <code>, ...` comment hides the diagnostics with the given
+//! codes. When the comment is alone on its line, it applies to the next line;
+//! otherwise it applies to the line it is written on.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use tower_lsp::lsp_types as lsp;
+
+use super::diagnostic_code::DiagnosticCode;
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+
+lazy_static! {
+    static ref SUPPRESSION_REGEX: Regex = Regex::new(r"^#\s*bend-ignore\s*:\s*(.*?)\s*$").unwrap();
+}
+
+/// A `# bend-ignore` comment.
+#[derive(Debug)]
+pub struct Suppression {
+    /// Range of the comment.
+    pub range: lsp::Range,
+    /// Line with the diagnostics this comment suppresses.
+    pub target_line: u32,
+    pub codes: Vec<String>,
+}
+
+impl Suppression {
+    fn matches(&self, diag: &lsp::Diagnostic) -> bool {
+        diag.range.start.line == self.target_line
+            && diagnostic_code(diag).is_some_and(|code| self.codes.iter().any(|c| c == code))
+    }
+}
+
+/// Find every suppression comment in a document.
+pub fn suppressions(doc: &Document) -> Vec<Suppression> {
+    doc.find_all("(comment) @comment")
+        .into_iter()
+        .filter_map(|node| {
+            let text = doc.node_text(&node);
+            let codes = SUPPRESSION_REGEX.captures(&text)?.get(1)?.as_str();
+            let codes = codes
+                .split(',')
+                .map(|code| code.trim().to_string())
+                .filter(|code| !code.is_empty())
+                .collect();
+
+            let range = ts_range_to_lsp(node.range());
+            let target_line = if is_alone_in_line(doc, range.start) {
+                range.start.line + 1
+            } else {
+                range.start.line
+            };
+
+            Some(Suppression {
+                range,
+                target_line,
+                codes,
+            })
+        })
+        .collect()
+}
+
+/// Remove suppressed diagnostics from `diagnostics`, returning the suppressions
+/// that didn't match anything.
+pub fn apply_suppressions(
+    suppressions: Vec<Suppression>,
+    diagnostics: &mut Vec<lsp::Diagnostic>,
+) -> Vec<Suppression> {
+    let mut used = vec![false; suppressions.len()];
+
+    diagnostics.retain(|diag| {
+        let mut suppressed = false;
+        // Every suppression of the diagnostic is used, not only the first one.
+        for (index, suppression) in suppressions.iter().enumerate() {
+            if suppression.matches(diag) {
+                used[index] = true;
+                suppressed = true;
+            }
+        }
+        !suppressed
+    });
+
+    suppressions
+        .into_iter()
+        .zip(used)
+        .filter_map(|(suppression, used)| (!used).then_some(suppression))
+        .collect()
+}
+
+/// Warning reported for a suppression comment that doesn't match any diagnostic.
+pub fn unused_suppression_diagnostic(suppression: &Suppression) -> lsp::Diagnostic {
+    lsp::Diagnostic {
+        range: suppression.range,
+        severity: Some(lsp::DiagnosticSeverity::WARNING),
+        code: Some(lsp::NumberOrString::String(
+            DiagnosticCode::UnusedSuppression.as_str().into(),
+        )),
+        source: Some("bend".into()),
+        message: format!(
+            "This suppression comment does not match any diagnostic ({}).",
+            suppression.codes.join(", ")
+        ),
+        ..Default::default()
+    }
+}
+
+/// Quick fix that suppresses `diag` with a `# bend-ignore` comment in the line above it.
+pub fn suppress_action(doc: &Document, diag: &lsp::Diagnostic) -> Option<lsp::CodeAction> {
+    let code = diagnostic_code(diag)?;
+    if code == DiagnosticCode::UnusedSuppression.as_str() {
+        return None;
+    }
+
+    let line = diag.range.start.line;
+    let line_text = doc.text.get_line(line as usize)?.to_string();
+    let indent: String = line_text
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect();
+
+    // If there's already a suppression comment in the previous line, add the code to it.
+    let existing = suppressions(doc)
+        .into_iter()
+        .find(|s| s.target_line == line && s.range.start.line + 1 == line);
+
+    let edit = match existing {
+        Some(suppression) => lsp::TextEdit {
+            range: lsp::Range::new(suppression.range.end, suppression.range.end),
+            new_text: format!(", {code}"),
+        },
+        None => {
+            let start = lsp::Position::new(line, 0);
+            lsp::TextEdit {
+                range: lsp::Range::new(start, start),
+                new_text: format!("{indent}# bend-ignore: {code}\n"),
+            }
+        }
+    };
+
+    Some(lsp::CodeAction {
+        title: format!("Suppress `{code}` on this line"),
+        kind: Some(lsp::CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diag.clone()]),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some([(doc.url.clone(), vec![edit])].into_iter().collect()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+fn diagnostic_code(diag: &lsp::Diagnostic) -> Option<&str> {
+    match &diag.code {
+        Some(lsp::NumberOrString::String(code)) => Some(code),
+        _ => None,
+    }
+}
+
+/// Checks if there is only whitespace before `position` in its line.
+fn is_alone_in_line(doc: &Document, position: lsp::Position) -> bool {
+    doc.text
+        .get_line(position.line as usize)
+        .map(|line| {
+            line.chars()
+                .take(position.character as usize)
+                .all(char::is_whitespace)
+        })
+        .unwrap_or(false)
+}
+
+#[test]
+fn suppression_targets_test() {
+    let code = r#"
+# bend-ignore: unused-definition
+def foo():
+  return 1 # bend-ignore: unreachable-match, redundant-match
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///test.bend").unwrap(), code);
+    let found = suppressions(&doc);
+
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].target_line, 2);
+    assert_eq!(found[0].codes, ["unused-definition"]);
+    assert_eq!(found[1].target_line, 3);
+    assert_eq!(found[1].codes, ["unreachable-match", "redundant-match"]);
+
+    let diagnostic = |line, code: &str| lsp::Diagnostic {
+        range: lsp::Range::new(lsp::Position::new(line, 4), lsp::Position::new(line, 7)),
+        code: Some(lsp::NumberOrString::String(code.into())),
+        ..Default::default()
+    };
+    let mut diagnostics = vec![
+        diagnostic(2, "unused-definition"),
+        diagnostic(2, "recursion-cycle"),
+    ];
+    let unused = apply_suppressions(found, &mut diagnostics);
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(unused.len(), 1);
+    assert_eq!(unused[0].target_line, 3);
+    // Two suppressions of the same diagnostic are both used.
+    let code = "# bend-ignore: unused-definition\ndef foo(): # bend-ignore: unused-definition\n  return 1\n";
+    let doc = Document::new_with_text(doc.url.clone(), code);
+    let mut diagnostics = vec![diagnostic(1, "unused-definition")];
+    let unused = apply_suppressions(suppressions(&doc), &mut diagnostics);
+    assert!(diagnostics.is_empty());
+    assert!(unused.is_empty());
+}
@@ -0,0 +1,288 @@
+//! Syntax errors found by tree-sitter, which are available as soon as the
+//! document changes and, unlike Bend's parser, don't stop at the first error.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::diagnostic_code::DiagnosticCode;
+use super::diagnostics::ts_range_to_lsp;
+use super::document::Document;
+use crate::utils::tree::descendants;
+
+lazy_static! {
+    /// A name followed by its type, like `x: u24` or `~tail: List(T)`.
+    static ref TYPE_ANNOTATION_REGEX: Regex = Regex::new(r"^~?[\w.]+\s*:").unwrap();
+}
+
+/// Longest piece of unexpected code quoted in a message.
+const MAX_QUOTED: usize = 24;
+
+/// Syntax errors of the `ERROR` and `MISSING` nodes of the document's tree.
+pub fn syntax_diagnostics(doc: &Document) -> Vec<lsp::Diagnostic> {
+    let Some(tree) = doc.tree.as_ref() else {
+        return vec![];
+    };
+    if !tree.root_node().has_error() {
+        return vec![];
+    }
+
+    descendants(tree.root_node())
+        .filter(|node| !is_underscore_prefix(doc, *node))
+        .filter_map(|node| {
+            if node.is_missing() {
+                missing_diagnostic(doc, node)
+            } else if node.is_error() {
+                error_diagnostic(doc, node)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Whether a node contains a syntax error.
+///
+/// Unlike [`ts::Node::has_error`], this ignores the errors our grammar reports
+/// for names starting with an underscore, which Bend accepts.
+pub fn has_syntax_error(doc: &Document, node: ts::Node) -> bool {
+    node.has_error()
+        && descendants(node).any(|inner| {
+            inner.is_missing() || (inner.is_error() && !is_underscore_prefix(doc, inner))
+        })
+}
+
+/// Top-level definitions of a document whose trees can be trusted.
+///
+/// Definitions with syntax errors are left out, and so are the ones right
+/// after them, which tree-sitter may have merged with the rest of the broken
+/// definition.
+pub fn valid_definitions(doc: &Document) -> Vec<ts::Node<'_>> {
+    let Some(tree) = doc.tree.as_ref() else {
+        return vec![];
+    };
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut after_error = false;
+
+    root.named_children(&mut cursor)
+        .filter(|node| node.kind() != "comment")
+        .filter(|node| {
+            let valid = !after_error && !has_syntax_error(doc, *node);
+            after_error = has_syntax_error(doc, *node);
+            valid
+        })
+        .collect()
+}
+
+/// Whether a node is one of the errors our grammar reports for names starting
+/// with an underscore, like `_unused`. Depending on where the name is, either
+/// the underscore or the rest of the name is parsed as an error.
+pub fn is_underscore_prefix(doc: &Document, node: ts::Node) -> bool {
+    if !node.is_error() {
+        return false;
+    }
+    let text = doc.node_text(&node);
+    let char_at = |byte: usize| {
+        doc.text
+            .get_byte_slice(byte..)
+            .and_then(|rest| rest.chars().next())
+    };
+
+    let before_name = text == "_" && char_at(node.end_byte()).is_some_and(is_name_char);
+    let after_underscore = !text.is_empty()
+        && text.chars().all(is_name_char)
+        && node
+            .start_byte()
+            .checked_sub(1)
+            .and_then(char_at)
+            .is_some_and(|c| c == '_');
+    before_name || after_underscore
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '/')
+}
+
+/// Merge live syntax errors with the diagnostics of the compiler.
+///
+/// The compiler's parse errors have better messages, so a syntax error is
+/// dropped when the compiler reports a parse error in the same line.
+pub fn merge_syntax_diagnostics(
+    compiler: Vec<lsp::Diagnostic>,
+    syntax: &[lsp::Diagnostic],
+) -> Vec<lsp::Diagnostic> {
+    let parse_error = Some(lsp::NumberOrString::String(
+        DiagnosticCode::ParseError.as_str().into(),
+    ));
+    let parse_error_lines: Vec<u32> = compiler
+        .iter()
+        .filter(|diag| diag.code == parse_error)
+        .map(|diag| diag.range.start.line)
+        .collect();
+
+    let mut merged = compiler;
+    for diag in syntax {
+        if !parse_error_lines.contains(&diag.range.start.line) && !merged.contains(diag) {
+            merged.push(diag.clone());
+        }
+    }
+    merged
+}
+
+fn missing_diagnostic(doc: &Document, node: ts::Node) -> Option<lsp::Diagnostic> {
+    // Missing tokens after unsupported syntax are part of the same error.
+    let after_unsupported = node.prev_sibling().is_some_and(|prev| {
+        prev.is_error() && is_unsupported_syntax(doc, prev, doc.node_text(&prev).trim())
+    });
+    if after_unsupported {
+        return None;
+    }
+
+    let start = ts_range_to_lsp(node.range()).start;
+    Some(diagnostic(
+        lsp::Range::new(start, start),
+        format!("Syntax error, missing '{}'.", node.kind()),
+    ))
+}
+
+/// Diagnostic for an `ERROR` node, limited to the first line of its
+/// unexpected code.
+///
+/// Tree-sitter may wrap big parts of the file in an error, including
+/// definitions that parsed fine, so those are skipped. Errors containing a
+/// more precise error in the same line are not reported.
+fn error_diagnostic(doc: &Document, node: ts::Node) -> Option<lsp::Diagnostic> {
+    let mut cursor = node.walk();
+    let start = node
+        .children(&mut cursor)
+        .find(|child| !child.kind().ends_with("_definition") || has_syntax_error(doc, *child))?
+        .start_position();
+
+    let nested_in_line = descendants(node).skip(1).any(|inner| {
+        (inner.is_error() || inner.is_missing())
+            && !is_underscore_prefix(doc, inner)
+            && inner.start_position().row == start.row
+    });
+    if nested_in_line {
+        return None;
+    }
+
+    // Tree-sitter columns are byte offsets in the line.
+    let line = doc.text.line(start.row).to_string();
+    let line = line.trim_end();
+    let end_column = if node.end_position().row == start.row {
+        node.end_position().column
+    } else {
+        line.len()
+    };
+    let text = line
+        .get(start.column..end_column)
+        .unwrap_or_default()
+        .trim();
+    if is_unsupported_syntax(doc, node, text) {
+        return None;
+    }
+    let message = match text.char_indices().nth(MAX_QUOTED) {
+        _ if text.is_empty() => "Syntax error.".into(),
+        Some((cut, _)) => format!("Syntax error, unexpected '{}...'.", &text[..cut]),
+        None => format!("Syntax error, unexpected '{text}'."),
+    };
+
+    let range = lsp::Range::new(
+        lsp::Position::new(start.row as u32, start.column as u32),
+        lsp::Position::new(start.row as u32, end_column as u32),
+    );
+    Some(diagnostic(range, message))
+}
+
+/// Whether an `ERROR` node, with unexpected code `text`, covers syntax that
+/// Bend accepts but our grammar doesn't understand yet, like type annotations
+/// (`x: u24`, `-> List(T)`, `type Tree(T):`) and shift operators.
+fn is_unsupported_syntax(doc: &Document, node: ts::Node, text: &str) -> bool {
+    let parent = node
+        .parent()
+        .map(|parent| parent.kind())
+        .unwrap_or_default();
+    let char_at = |byte: usize| {
+        doc.text
+            .get_byte_slice(byte..)
+            .and_then(|rest| rest.chars().next())
+    };
+
+    // The second `<` of `<<`, or `>` of `>>`.
+    let shift = matches!(text, "<" | ">")
+        && [node.start_byte().checked_sub(1), Some(node.end_byte())]
+            .into_iter()
+            .flatten()
+            .any(|byte| char_at(byte).is_some_and(|c| text.starts_with(c)));
+    let annotation = text.starts_with(':') || TYPE_ANNOTATION_REGEX.is_match(text);
+    let return_type = text.contains("->") && parent.ends_with("_function_definition");
+    let type_parameters = text.starts_with('(')
+        && matches!(
+            parent,
+            "imp_type_definition"
+                | "fun_type_definition"
+                | "fun_type_constructor_fields"
+                | "object_definition"
+        );
+    shift || annotation || return_type || type_parameters
+}
+
+fn diagnostic(range: lsp::Range, message: String) -> lsp::Diagnostic {
+    lsp::Diagnostic {
+        range,
+        severity: Some(lsp::DiagnosticSeverity::ERROR),
+        code: Some(lsp::NumberOrString::String(
+            DiagnosticCode::ParseError.as_str().into(),
+        )),
+        source: Some("bend".into()),
+        message,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn syntax_diagnostics_test() {
+    let code = r#"
+def main():
+  return (1 + 2
+
+def ok():
+  return 1
+
+def foo():
+  return 1 +
+
+def bar(_unused):
+  _x = 1
+  return 0
+
+def typed(x: u24) -> u24:
+  return x << 1
+
+def shift(x: u24) -> u24:
+  y = x <<
+  return y
+
+type Tree(T):
+  Node { ~left: Tree(T), ~right: Tree(T) }
+  Leaf { value: T }
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let errors: Vec<_> = syntax_diagnostics(&doc)
+        .into_iter()
+        .map(|diag| (diag.range.start.line, diag.message))
+        .collect();
+
+    assert_eq!(
+        errors,
+        [
+            (2, "Syntax error, unexpected 'return (1 + 2'.".into()),
+            (8, "Syntax error, unexpected 'return 1 +'.".into()),
+            // Only the errors of the annotations are ignored, not the whole line.
+            (18, "Syntax error, unexpected 'y = x <<'.".into()),
+        ]
+    );
+}
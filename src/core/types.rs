@@ -0,0 +1,204 @@
+//! Algebraic data types visible in a document, with their constructors and
+//! fields.
+
+use bend::fun::parser::ParseBook;
+use lazy_static::lazy_static;
+use tree_sitter as ts;
+
+use super::definitions::{definitions, DefinitionKind};
+use super::document::Document;
+use super::syntax_errors::valid_definitions;
+
+lazy_static! {
+    /// Types of Bend's builtins, like `List` and `Tree`.
+    static ref BUILTIN_ADTS: Vec<Adt> = ParseBook::builtins()
+        .adts
+        .values()
+        .map(|adt| Adt {
+            name: adt.name.to_string(),
+            constructors: adt
+                .ctrs
+                .values()
+                .map(|ctr| Constructor {
+                    name: ctr.name.to_string(),
+                    fields: ctr
+                        .fields
+                        .iter()
+                        .map(|field| Field {
+                            name: field.nam.to_string(),
+                            recursive: field.rec,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect();
+}
+
+/// A type declared with `type` or `object`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Adt {
+    pub name: String,
+    pub constructors: Vec<Constructor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constructor {
+    /// Full name of the constructor, like `List/Cons`, or the name of the
+    /// type for objects.
+    pub name: String,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    /// Whether the field is marked with `~`, as it holds a value of the same type.
+    pub recursive: bool,
+}
+
+/// The types declared in a document, followed by the builtin types it doesn't
+/// redefine.
+///
+/// Types with syntax errors are left out, as their fields can't be trusted.
+pub fn adts(doc: &Document) -> Vec<Adt> {
+    let valid = valid_definitions(doc);
+    let mut adts: Vec<Adt> = definitions(doc)
+        .into_iter()
+        .filter(|def| matches!(def.kind, DefinitionKind::Type | DefinitionKind::Object))
+        .filter(|def| valid.contains(&def.node))
+        .map(|def| {
+            let constructors = if def.kind == DefinitionKind::Object {
+                vec![Constructor {
+                    name: def.name.clone(),
+                    fields: fields(doc, def.node),
+                }]
+            } else {
+                let mut cursor = def.node.walk();
+                def.node
+                    .named_children(&mut cursor)
+                    .filter(|node| {
+                        matches!(node.kind(), "imp_type_constructor" | "fun_type_constructor")
+                    })
+                    .filter_map(|node| {
+                        Some(Constructor {
+                            name: format!("{}/{}", def.name, doc.node_text(&node.named_child(0)?)),
+                            fields: fields(doc, node),
+                        })
+                    })
+                    .collect()
+            };
+            Adt {
+                name: def.name,
+                constructors,
+            }
+        })
+        .collect();
+
+    for builtin in BUILTIN_ADTS.iter() {
+        if !adts.iter().any(|adt| adt.name == builtin.name) {
+            adts.push(builtin.clone());
+        }
+    }
+    adts
+}
+
+/// The type of a constructor, by the constructor's full name.
+pub fn adt_of_constructor<'a>(adts: &'a [Adt], name: &str) -> Option<&'a Adt> {
+    adts.iter()
+        .find(|adt| adt.constructors.iter().any(|ctr| ctr.name == name))
+}
+
+impl Adt {
+    pub fn constructor(&self, name: &str) -> Option<&Constructor> {
+        self.constructors.iter().find(|ctr| ctr.name == name)
+    }
+}
+
+/// Fields of a type constructor or of an object.
+fn fields(doc: &Document, node: ts::Node) -> Vec<Field> {
+    let container = match node.kind() {
+        "object_definition" => Some(node),
+        _ => {
+            let mut cursor = node.walk();
+            let fields = node.named_children(&mut cursor).find(|child| {
+                matches!(
+                    child.kind(),
+                    "imp_type_constructor_field" | "fun_type_constructor_fields"
+                )
+            });
+            fields
+        }
+    };
+    let Some(container) = container else {
+        return vec![];
+    };
+
+    let mut cursor = container.walk();
+    let names: Vec<ts::Node> = container
+        .named_children(&mut cursor)
+        .filter_map(|child| match child.kind() {
+            "identifier" if container.kind() != "object_definition" => Some(child),
+            "object_field" => child.named_child(0),
+            _ => None,
+        })
+        .collect();
+    names
+        .into_iter()
+        .map(|name| Field {
+            name: doc.node_text(&name),
+            recursive: name.prev_sibling().is_some_and(|prev| prev.kind() == "~"),
+        })
+        .collect()
+}
+
+#[test]
+fn adts_test() {
+    let code = r#"type Tree:
+  Node { val, ~left, ~right }
+  Leaf
+
+type L = (Cons h ~t) | Nil
+
+object Point { x, y }
+"#;
+    let doc = Document::new_with_text(
+        tower_lsp::lsp_types::Url::parse("file:///main.bend").unwrap(),
+        code,
+    );
+    let adts = adts(&doc);
+    let field = |name: &str, recursive| Field {
+        name: name.into(),
+        recursive,
+    };
+
+    // The document's `Tree` replaces the builtin one.
+    assert_eq!(adts.iter().filter(|adt| adt.name == "Tree").count(), 1);
+    assert_eq!(
+        adts[0].constructors,
+        [
+            Constructor {
+                name: "Tree/Node".into(),
+                fields: vec![
+                    field("val", false),
+                    field("left", true),
+                    field("right", true)
+                ],
+            },
+            Constructor {
+                name: "Tree/Leaf".into(),
+                fields: vec![],
+            },
+        ]
+    );
+    assert_eq!(
+        adts[1].constructors[0].fields,
+        [field("h", false), field("t", true)]
+    );
+    assert_eq!(adts[2].constructors[0].name, "Point");
+    assert_eq!(adts[2].constructors[0].fields.len(), 2);
+
+    let list = adt_of_constructor(&adts, "List/Cons").unwrap();
+    assert_eq!(list.name, "List");
+    assert!(list.constructor("List/Cons").unwrap().fields[1].recursive);
+}
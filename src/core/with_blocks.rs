@@ -0,0 +1,477 @@
+//! Refactorings between `with` blocks and explicit calls to the monad's
+//! `bind` function.
+//!
+//! Bend turns each `x <- value` (or `ask x = value`) of a `with Type` block
+//! into a call to `Type/bind` with the rest of the block as a deferred
+//! continuation, passing it the variables it uses, as `bind` functions call
+//! `undefer` on it. We write the same:
+//!
+//! ```bend
+//! with IO:                      return IO/bind(IO/read(fd), lambda k: k(
+//!   x <- IO/read(fd)              lambda fd, x: IO/close(fd, x), fd))
+//!   return IO/close(fd, x)
+//! ```
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tower_lsp::lsp_types as lsp;
+use tree_sitter as ts;
+
+use super::code_actions::{indentation, node_at, rewrite};
+use super::document::Document;
+use super::lints::scopes::{self, Binding};
+use crate::utils::tree::{descendants, named_children};
+
+#[cfg(test)]
+use super::code_actions::apply_fix;
+
+/// Action to desugar the `with` block at `range` into binds, or to turn the
+/// chain of binds at `range` into a `with` block.
+pub fn with_blocks(doc: &Document, range: lsp::Range) -> Option<lsp::CodeAction> {
+    let node = node_at(doc, range)?;
+    let bindings = scopes::bindings(doc);
+
+    let mut outermost_bind = None;
+    for node in std::iter::successors(Some(node), |node| node.parent()) {
+        match node.kind() {
+            "with_statement" | "fun_with" => {
+                let new_text = desugar(doc, node)?;
+                return Some(action(
+                    doc,
+                    "Desugar 'with' block into binds",
+                    node,
+                    new_text,
+                ));
+            }
+            "call_expression" | "fun_application" if bind(doc, &bindings, node).is_some() => {
+                outermost_bind = Some(node);
+            }
+            _ => {}
+        }
+    }
+
+    let node = outermost_bind?;
+    let (replaced, new_text) = sugar(doc, &bindings, node)?;
+    Some(action(
+        doc,
+        "Convert binds into a 'with' block",
+        replaced,
+        new_text,
+    ))
+}
+
+fn action(doc: &Document, title: &str, node: ts::Node, new_text: String) -> lsp::CodeAction {
+    let edit = lsp::TextEdit {
+        range: doc.node_range(&node),
+        new_text,
+    };
+    lsp::CodeAction {
+        title: title.into(),
+        kind: Some(lsp::CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(lsp::WorkspaceEdit {
+            changes: Some(HashMap::from([(doc.url.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// A `with` block being desugared.
+struct Block<'a, 't> {
+    doc: &'t Document,
+    node: ts::Node<'t>,
+    monad: String,
+    /// Name of the deferred continuation's parameter.
+    thunk: String,
+    bindings: &'a [Binding<'t>],
+}
+
+/// The text replacing a `with` block with binds.
+fn desugar(doc: &Document, node: ts::Node) -> Option<String> {
+    let monad = doc.node_text(&node.named_child(0)?);
+    let bindings = scopes::bindings(doc);
+    let block = Block {
+        doc,
+        node,
+        monad,
+        thunk: fresh_name(doc, node, "k"),
+        bindings: &bindings,
+    };
+    let body = node.named_child(1)?;
+
+    if node.kind() == "fun_with" {
+        let term = body.named_child(0)?;
+        return Some(rewrite(doc, term, &|node| desugar_term(&block, node)));
+    }
+
+    // Imperative lambdas only hold expressions, so the block must be a
+    // sequence of binds followed by a `return`.
+    let statements = named_children(body);
+    let (last, asks) = statements.split_last()?;
+    if asks.is_empty()
+        || last.kind() != "return_statement"
+        || asks.iter().any(|ask| ask.kind() != "ask_statement")
+    {
+        return None;
+    }
+    let mut expr = rewrite(doc, last.named_child(0)?, &|node| {
+        desugar_term(&block, node)
+    });
+    for (i, ask) in asks.iter().enumerate().rev() {
+        let pattern = ask.child_by_field_name("pat")?;
+        let value = ask.child_by_field_name("val")?;
+        if pattern.kind() != "identifier" {
+            return None;
+        }
+        let continuation = statements[i + 1].start_byte()..body.end_byte();
+        let free = free_vars(&block, continuation, pattern.byte_range());
+
+        let params: Vec<String> = free
+            .iter()
+            .cloned()
+            .chain([doc.node_text(&pattern)])
+            .collect();
+        let args: String = free.iter().map(|var| format!(", {var}")).collect();
+        let value = rewrite(doc, value, &|node| desugar_term(&block, node));
+        expr = format!(
+            "{}/bind({value}, lambda {k}: {k}(lambda {}: {expr}{args}))",
+            block.monad,
+            params.join(", "),
+            k = block.thunk,
+        );
+    }
+    Some(format!("return {expr}"))
+}
+
+/// Replacement for a node inside a functional `with` block: binds for its
+/// `ask`s and the monad's function for `wrap`.
+fn desugar_term(block: &Block, node: ts::Node) -> Option<String> {
+    let doc = block.doc;
+    if is_wrap(block, node) {
+        return Some(format!("{}/wrap", block.monad));
+    }
+    if node.kind() != "fun_ask" || innermost_with(node) != Some(block.node) {
+        return None;
+    }
+
+    let pattern = named_children(node)
+        .into_iter()
+        .find(|child| child.kind() == "pattern")?;
+    let value = node.child_by_field_name("value")?.named_child(0)?;
+    let next = named_children(node)
+        .into_iter()
+        .find(|child| child.kind() == "ask_next")?
+        .named_child(0)?;
+    let free = free_vars(block, next.byte_range(), pattern.byte_range());
+
+    let lambdas: String = free.iter().map(|var| format!("@{var} ")).collect();
+    let args: String = free.iter().map(|var| format!(" {var}")).collect();
+    Some(format!(
+        "({}/bind {} @{k} ({k} {lambdas}@{}\n{}{}{args}))",
+        block.monad,
+        rewrite(doc, value, &|node| desugar_term(block, node)),
+        doc.node_text(&pattern),
+        indentation(doc, node),
+        rewrite(doc, next, &|node| desugar_term(block, node)),
+        k = block.thunk,
+    ))
+}
+
+/// A reference to the `wrap` function of the block's monad, and not the last
+/// part of a longer name like `IO/wrap`.
+fn is_wrap(block: &Block, node: ts::Node) -> bool {
+    node.kind() == "identifier"
+        && node
+            .parent()
+            .is_some_and(|parent| parent.kind() != "identifier")
+        && block.doc.node_text(&node) == "wrap"
+        && !block
+            .bindings
+            .iter()
+            .any(|binding| binding.node == node || binding.references.contains(&node))
+}
+
+/// Local variables bound outside of `continuation` and used inside of it, which
+/// Bend passes to the deferred continuation. Variables bound in `pattern` are
+/// the continuation's own parameters.
+fn free_vars(block: &Block, continuation: Range<usize>, pattern: Range<usize>) -> Vec<String> {
+    let mut vars: Vec<String> = block
+        .bindings
+        .iter()
+        .filter(|binding| {
+            let start = binding.node.start_byte();
+            !continuation.contains(&start) && !pattern.contains(&start)
+        })
+        .filter(|binding| {
+            binding
+                .references
+                .iter()
+                .any(|reference| continuation.contains(&reference.start_byte()))
+        })
+        .map(|binding| binding.name.clone())
+        .collect();
+    vars.sort();
+    vars.dedup();
+    vars
+}
+
+/// A call to a monad's `bind` function with a deferred continuation, as
+/// written by [`desugar`].
+struct Bind<'t> {
+    monad: String,
+    value: ts::Node<'t>,
+    pattern: ts::Node<'t>,
+    /// The body of the continuation.
+    next: ts::Node<'t>,
+}
+
+fn bind<'t>(doc: &Document, bindings: &[Binding], node: ts::Node<'t>) -> Option<Bind<'t>> {
+    let callee = node.named_child(0)?;
+    let monad = doc.node_text(&callee).strip_suffix("/bind")?.to_string();
+    if callee.kind() != "identifier" || monad.is_empty() {
+        return None;
+    }
+
+    if node.kind() == "call_expression" {
+        let [value, thunk] = named_children(node.named_child(1)?)[..] else {
+            return None;
+        };
+        let [k] = lambda_params(thunk)?[..] else {
+            return None;
+        };
+        let call = thunk.child_by_field_name("body")?;
+        let (continuation, args) = deferred_call(bindings, k, call)?;
+        let params = lambda_params(continuation)?;
+        let (pattern, free) = params.split_last()?;
+        if !same_names(doc, free, &args) {
+            return None;
+        }
+        let next = continuation.child_by_field_name("body")?;
+        return Some(Bind {
+            monad,
+            value,
+            pattern: *pattern,
+            next,
+        });
+    }
+
+    let [_, value, thunk] = named_children(node)[..] else {
+        return None;
+    };
+    let (k, call) = fun_lambda(thunk)?;
+    let (mut continuation, args) = deferred_call(bindings, k, call)?;
+    let mut free = vec![];
+    while free.len() < args.len() {
+        let (param, body) = fun_lambda(continuation)?;
+        free.push(param);
+        continuation = body;
+    }
+    let (pattern, next) = fun_lambda(continuation)?;
+    if !same_names(doc, &free, &args) {
+        return None;
+    }
+    Some(Bind {
+        monad,
+        value,
+        pattern,
+        next,
+    })
+}
+
+/// The parameters of an imperative lambda.
+fn lambda_params(node: ts::Node) -> Option<Vec<ts::Node>> {
+    if node.kind() != "imp_lambda" {
+        return None;
+    }
+    let params = named_children(node)
+        .into_iter()
+        .find(|child| child.kind() == "parameters")?;
+    Some(named_children(params))
+}
+
+/// The pattern and the body of a functional lambda.
+fn fun_lambda(node: ts::Node) -> Option<(ts::Node, ts::Node)> {
+    if node.kind() != "fun_lambda" {
+        return None;
+    }
+    let [pattern, body] = named_children(node)[..] else {
+        return None;
+    };
+    let pattern = match pattern.named_child(0) {
+        Some(inner) if pattern.named_child_count() == 1 && inner.kind() == "identifier" => inner,
+        _ => pattern,
+    };
+    Some((pattern, body.named_child(0)?))
+}
+
+/// A call `k(continuation, args...)` to the parameter `k` of a thunk, which
+/// must not use `k` anywhere else.
+fn deferred_call<'t>(
+    bindings: &[Binding],
+    k: ts::Node,
+    call: ts::Node<'t>,
+) -> Option<(ts::Node<'t>, Vec<ts::Node<'t>>)> {
+    let (callee, args) = match call.kind() {
+        "call_expression" => (call.named_child(0)?, named_children(call.named_child(1)?)),
+        "fun_application" => {
+            let children = named_children(call);
+            (children[0], children[1..].to_vec())
+        }
+        _ => return None,
+    };
+    let k = bindings.iter().find(|binding| binding.node == k)?;
+    if k.references != [callee] {
+        return None;
+    }
+    let (continuation, args) = args.split_first()?;
+    Some((*continuation, args.to_vec()))
+}
+
+fn same_names(doc: &Document, params: &[ts::Node], args: &[ts::Node]) -> bool {
+    params.len() == args.len()
+        && params.iter().zip(args).all(|(param, arg)| {
+            param.kind() == "identifier"
+                && arg.kind() == "identifier"
+                && doc.node_text(param) == doc.node_text(arg)
+        })
+}
+
+/// The node replaced by a `with` block and its text, for the chain of binds
+/// starting at `node`.
+fn sugar<'t>(
+    doc: &'t Document,
+    bindings: &[Binding],
+    node: ts::Node<'t>,
+) -> Option<(ts::Node<'t>, String)> {
+    let first = bind(doc, bindings, node)?;
+    let monad = first.monad.clone();
+    let functional = node.kind() == "fun_application";
+    let replaced = match functional {
+        true => node,
+        false => node
+            .parent()
+            .filter(|parent| parent.kind() == "return_statement")?,
+    };
+
+    let mut asks = vec![];
+    let mut next = node;
+    while let Some(bind) = bind(doc, bindings, next).filter(|bind| bind.monad == monad) {
+        asks.push((bind.pattern, bind.value));
+        next = bind.next;
+    }
+
+    // Inside the block, `wrap` is the monad's function, unless it's a
+    // variable of the chain.
+    let wrap = format!("{monad}/wrap");
+    let shadows_wrap = bindings.iter().any(|binding| {
+        binding.name == "wrap" && node.byte_range().contains(&binding.node.start_byte())
+    });
+    let text = |node: ts::Node| {
+        rewrite(doc, node, &|node| {
+            let is_wrap = node.kind() == "identifier" && doc.node_text(&node) == wrap;
+            (is_wrap && !shadows_wrap).then(|| "wrap".to_string())
+        })
+    };
+
+    let indent = indentation(doc, replaced);
+    let mut block = match functional {
+        true => format!("with {monad} {{"),
+        false => format!("with {monad}:"),
+    };
+    for (pattern, value) in asks {
+        block += &match functional {
+            true => format!(
+                "\n{indent}  ask {} = {}",
+                doc.node_text(&pattern),
+                text(value)
+            ),
+            false => format!("\n{indent}  {} <- {}", doc.node_text(&pattern), text(value)),
+        };
+    }
+    block += &match functional {
+        true => format!("\n{indent}  {}\n{indent}}}", text(next)),
+        false => format!("\n{indent}  return {}", text(next)),
+    };
+    Some((replaced, block))
+}
+
+fn innermost_with(node: ts::Node) -> Option<ts::Node> {
+    std::iter::successors(node.parent(), |node| node.parent())
+        .find(|node| matches!(node.kind(), "with_statement" | "fun_with"))
+}
+
+/// `base`, or `base` followed by a number, such that no identifier of the
+/// definition of `node` has that name.
+fn fresh_name(doc: &Document, node: ts::Node, base: &str) -> String {
+    let definition = std::iter::successors(Some(node), |node| node.parent())
+        .find(|node| {
+            node.parent()
+                .is_some_and(|parent| parent.parent().is_none())
+        })
+        .unwrap_or(node);
+    let names: Vec<String> = descendants(definition)
+        .filter(|node| node.kind() == "identifier")
+        .map(|node| doc.node_text(&node))
+        .collect();
+    std::iter::once(base.to_string())
+        .chain((1..).map(|i| format!("{base}{i}")))
+        .find(|name| !names.contains(name))
+        .unwrap()
+}
+
+#[test]
+fn with_blocks_test() {
+    let code = r#"def main(fd):
+  with IO:
+    x <- IO/read(fd)
+    y <- IO/read(fd)
+    return wrap(x + y)
+
+Main fd = with IO {
+  ask x = (IO/read fd)
+  (wrap (+ x fd))
+}
+
+def bound(fd):
+  return IO/bind(IO/read(fd), lambda k: k(lambda fd, x: IO/close(fd, x), fd))
+"#;
+    let doc = Document::new_with_text(lsp::Url::parse("file:///main.bend").unwrap(), code);
+    let convert = |line: u32, character: u32| {
+        let position = lsp::Position::new(line, character);
+        with_blocks(&doc, lsp::Range::new(position, position))
+            .map(|action| (action.title.clone(), apply_fix(&doc, &action)))
+    };
+
+    let (title, text) = convert(2, 4).unwrap();
+    assert_eq!(title, "Desugar 'with' block into binds");
+    assert!(text.starts_with(
+        "def main(fd):\n  return IO/bind(IO/read(fd), lambda k: k(lambda fd, x: \
+         IO/bind(IO/read(fd), lambda k: k(lambda x, y: IO/wrap(x + y), x)), fd))\n"
+    ));
+
+    let (_, text) = convert(7, 4).unwrap();
+    assert!(
+        text.contains("Main fd = (IO/bind (IO/read fd) @k (k @fd @x\n  (IO/wrap (+ x fd)) fd))\n")
+    );
+    let desugared = Document::new_with_text(doc.url.clone(), &text);
+    let position = lsp::Position::new(6, 12);
+    let action = with_blocks(&desugared, lsp::Range::new(position, position)).unwrap();
+    assert_eq!(apply_fix(&desugared, &action), code);
+
+    // Qualified names of `wrap` are left as they are.
+    let qualified = "def main(fd):\n  with IO:\n    x <- IO/read(fd)\n    return IO/wrap(x)\n";
+    let qualified = Document::new_with_text(doc.url.clone(), qualified);
+    let position = lsp::Position::new(1, 4);
+    let action = with_blocks(&qualified, lsp::Range::new(position, position)).unwrap();
+    assert_eq!(
+        apply_fix(&qualified, &action),
+        "def main(fd):\n  return IO/bind(IO/read(fd), lambda k: k(lambda x: IO/wrap(x)))\n"
+    );
+
+    let (title, text) = convert(12, 12).unwrap();
+    assert_eq!(title, "Convert binds into a 'with' block");
+    assert!(text.ends_with(
+        "def bound(fd):\n  with IO:\n    x <- IO/read(fd)\n    return IO/close(fd, x)\n"
+    ));
+}
@@ -0,0 +1,63 @@
+//! Files of the workspace folders open in the client.
+
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+use crate::utils::fixture::TempDir;
+
+/// Directories of build outputs and dependencies of other tools.
+const IGNORED_DIRS: [&str; 2] = ["target", "node_modules"];
+
+/// Find every Bend file inside `root`, skipping hidden and ignored directories.
+///
+/// Symbolic links to directories are not followed, so links that form cycles
+/// or leave the workspace are not searched.
+pub fn bend_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if file_type.is_dir() {
+                if !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_str()) {
+                    dirs.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "bend") {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+#[test]
+fn bend_files_test() {
+    let root = TempDir::new("bend_files_test");
+    for file in [
+        "main.bend",
+        "lib/util.bend",
+        "lib/notes.txt",
+        ".hidden/skip.bend",
+        "target/skip.bend",
+        "node_modules/skip.bend",
+    ] {
+        root.write(file, "");
+    }
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(root.path(), root.join("lib/loop")).unwrap();
+
+    assert_eq!(
+        bend_files(root.path()),
+        [root.join("lib/util.bend"), root.join("main.bend")]
+    );
+}
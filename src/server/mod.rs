@@ -1,11 +1,28 @@
-use dashmap::DashMap;
-use tower_lsp::jsonrpc::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use dashmap::{DashMap, DashSet};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Notify, Semaphore};
+use tower_lsp::jsonrpc::{self, Result};
 use tower_lsp::lsp_types::{self as lsp, SemanticTokensRangeResult};
 use tower_lsp::{Client, LanguageServer};
 
-use crate::core::diagnostics;
+use crate::core::auto_import::{self, ImportIndex, Importable};
+use crate::core::check_cache::CheckCache;
+use crate::core::code_actions;
+use crate::core::completion;
+use crate::core::diagnostic_code::DiagnosticCode;
+use crate::core::diagnostics::{self, CheckLimits, Diagnostics};
 use crate::core::document::{self, Document};
+use crate::core::lints::{self, LintConfig};
+use crate::core::rename_files;
 use crate::core::semantic_token;
+use crate::core::syntax_errors;
+use crate::core::workspace;
 use crate::utils::lsp_log;
 
 pub struct Backend {
@@ -13,6 +30,70 @@ pub struct Backend {
     pub client: Client,
     /// Currently open documents
     pub open_docs: DashMap<lsp::Url, document::Document>,
+    /// Diagnostics published for each file, by the checked document that reported them.
+    ///
+    /// Checking a document also reports diagnostics in the files it imports,
+    /// and a file may be imported by many checked documents.
+    pub published_diagnostics: DashMap<lsp::Url, HashMap<lsp::Url, Vec<lsp::Diagnostic>>>,
+    /// Syntax errors and lints of each open document, updated as it changes.
+    pub live_diagnostics: DashMap<lsp::Url, Vec<lsp::Diagnostic>>,
+    /// Severities of the lint rules, from the `bend.lints` setting.
+    pub lint_config: RwLock<LintConfig>,
+    /// Root directories of the workspace folders open in the client.
+    pub workspace_folders: RwLock<Vec<PathBuf>>,
+    /// Whether the client pulls diagnostics, instead of waiting for us to publish them.
+    pub pull_diagnostics: AtomicBool,
+    /// Whether the client lets us register to watch the Bend files changed outside of it.
+    pub watch_files: AtomicBool,
+    /// Progress tokens of the work the client asked us to cancel.
+    pub cancelled_progress: DashSet<lsp::ProgressToken>,
+    /// Number of progress tokens we created, to make each of them unique.
+    pub progress_count: AtomicU64,
+    /// Time budget and concurrency cap of the compiler's checks.
+    pub check_limits: RwLock<CheckLimits>,
+    /// Permits to run a check, up to `check_limits.max_concurrent` at a time.
+    ///
+    /// Replaced when the limit changes; running checks keep their permits
+    /// from the old semaphore until they finish.
+    pub check_permits: RwLock<Arc<Semaphore>>,
+    /// Results of previous checks, reused while the checked files don't change.
+    pub check_cache: Arc<CheckCache>,
+    /// Notified when the diagnostics of the workspace may have changed, to
+    /// answer the workspace diagnostic pulls waiting for it.
+    pub workspace_changed: Notify,
+    /// Documents whose stored diagnostics come from the cached check of their
+    /// current files, with the current settings, so they aren't checked again
+    /// while the check stays fresh.
+    pub checked_docs: DashSet<lsp::Url>,
+    /// When the user was last shown that a check timed out.
+    pub last_timeout_warning: Mutex<Option<Instant>>,
+    /// Names other modules of the workspace define, to import them.
+    pub import_index: Arc<ImportIndex>,
+}
+
+/// Time to wait after the workspace changes before checking it again, so
+/// changes made in quick succession are checked once.
+const WORKSPACE_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Minimum time between the messages shown to the user about checks that timed out.
+const TIMEOUT_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Command that checks every Bend file in the workspace.
+pub const CHECK_WORKSPACE_COMMAND: &str = "bend.checkWorkspace";
+
+/// Parameters of the custom `bend/explain` request.
+#[derive(Debug, Deserialize)]
+pub struct ExplainParams {
+    /// Diagnostic code to explain, like `unused-definition`.
+    pub code: String,
+}
+
+/// Response of the custom `bend/explain` request.
+#[derive(Debug, Serialize)]
+pub struct ExplainResult {
+    pub code: String,
+    /// Markdown explanation of the diagnostic code.
+    pub explanation: String,
 }
 
 #[tower_lsp::async_trait]
@@ -20,9 +101,37 @@ impl LanguageServer for Backend {
     // All of these represent messages the server may receive from the client.
     // See the automatic documentation generated by `tower_lsp` to understand what each method does.
 
-    async fn initialize(&self, _: lsp::InitializeParams) -> Result<lsp::InitializeResult> {
+    async fn initialize(&self, params: lsp::InitializeParams) -> Result<lsp::InitializeResult> {
         let capabilities = Self::capabilities();
 
+        let pull_diagnostics = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .is_some_and(|text_document| text_document.diagnostic.is_some());
+        self.pull_diagnostics
+            .store(pull_diagnostics, Ordering::Relaxed);
+
+        let watch_files = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        self.watch_files.store(watch_files, Ordering::Relaxed);
+
+        #[allow(deprecated)]
+        let folders = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders.into_iter().map(|folder| folder.uri).collect(),
+            (None, Some(root)) => vec![root],
+            (None, None) => vec![],
+        };
+        *self.workspace_folders.write().unwrap() = folders
+            .iter()
+            .filter_map(|url| url.to_file_path().ok())
+            .collect();
+
         Ok(lsp::InitializeResult {
             server_info: Some(lsp::ServerInfo {
                 name: "Bend Language Server".into(),
@@ -34,19 +143,15 @@ impl LanguageServer for Backend {
     }
 
     async fn initialized(&self, _: lsp::InitializedParams) {
-        let values = self
-            .client
-            .configuration(vec![lsp::ConfigurationItem {
-                scope_uri: Some(lsp::Url::parse("file:///libraryPaths").unwrap()),
-                section: Some("bend-language-server".to_string()),
-            }])
-            .await;
+        self.load_configuration().await;
 
-        if let Ok(_val) = values {
-            // TODO: configuration
+        if self.watch_files.load(Ordering::Relaxed) {
+            self.watch_bend_files().await;
         }
 
-        self.publish_all_diagnostics().await;
+        if !self.pulls_diagnostics() {
+            self.publish_all_diagnostics().await;
+        }
 
         lsp_log::info!(self.client, "bend-language-server initialized");
     }
@@ -61,15 +166,51 @@ impl LanguageServer for Backend {
         lsp_log::info!(self.client, "opening file at {}", params.text_document.uri);
 
         self.open_doc(params.text_document.uri.clone(), params.text_document.text);
-        self.publish_diagnostics(&params.text_document.uri).await;
+        self.update_live_diagnostics(&params.text_document.uri);
+        if self.pulls_diagnostics() {
+            self.workspace_changed.notify_waiters();
+        } else {
+            self.publish_diagnostics(&params.text_document.uri).await;
+        }
+    }
+
+    async fn did_close(&self, params: lsp::DidCloseTextDocumentParams) {
+        lsp_log::info!(self.client, "closing file at {}", params.text_document.uri);
+
+        let url = &params.text_document.uri;
+        self.open_docs.remove(url);
+        self.live_diagnostics.remove(url);
+        // Closed files are only checked again with the rest of the workspace.
+        self.check_cache.evict_check(&diagnostics::file_path(url));
+
+        // Syntax errors of the closed buffer no longer apply.
+        if self.pulls_diagnostics() {
+            self.workspace_changed.notify_waiters();
+        } else {
+            let diags = self.merged_diagnostics(url);
+            self.client
+                .publish_diagnostics(url.clone(), diags, None)
+                .await;
+        }
     }
 
     async fn did_change_configuration(&self, _params: lsp::DidChangeConfigurationParams) {
         lsp_log::info!(self.client, "changing language server configurations");
 
-        // TODO: configuration
+        self.load_configuration().await;
+        // The lint settings change the diagnostics of every checked document.
+        self.checked_docs.clear();
+        let urls: Vec<lsp::Url> = self.open_docs.iter().map(|doc| doc.key().clone()).collect();
+        for url in &urls {
+            self.update_live_diagnostics(url);
+        }
 
-        self.publish_all_diagnostics().await;
+        if self.pulls_diagnostics() {
+            self.workspace_changed.notify_waiters();
+            let _ = self.client.workspace_diagnostic_refresh().await;
+        } else {
+            self.publish_all_diagnostics().await;
+        }
     }
 
     async fn did_change(&self, params: lsp::DidChangeTextDocumentParams) {
@@ -79,11 +220,26 @@ impl LanguageServer for Backend {
             params.text_document.uri
         );
 
-        self.update_document(&params.text_document.uri, |doc| {
+        let url = &params.text_document.uri;
+        self.update_document(url, |doc| {
             for event in &params.content_changes {
                 doc.update_whole_text(&event.text);
             }
         });
+
+        // Syntax errors are cheap to find, so they're reported while typing,
+        // while the compiler only runs on save.
+        if !self.update_live_diagnostics(url) {
+            return;
+        }
+        if self.pulls_diagnostics() {
+            self.workspace_changed.notify_waiters();
+        } else {
+            let diags = self.merged_diagnostics(url);
+            self.client
+                .publish_diagnostics(url.clone(), diags, None)
+                .await;
+        }
     }
 
     async fn did_save(&self, params: lsp::DidSaveTextDocumentParams) {
@@ -96,7 +252,12 @@ impl LanguageServer for Backend {
         );
 
         let url = &params.text_document.uri;
-        self.publish_diagnostics(url).await;
+        self.import_index.file_changed(&diagnostics::file_path(url));
+        if self.pulls_diagnostics() {
+            self.workspace_changed.notify_waiters();
+        } else {
+            self.publish_diagnostics(url).await;
+        }
     }
 
     async fn semantic_tokens_full(
@@ -151,14 +312,223 @@ impl LanguageServer for Backend {
         }))
     }
 
+    async fn diagnostic(
+        &self,
+        params: lsp::DocumentDiagnosticParams,
+    ) -> Result<lsp::DocumentDiagnosticReportResult> {
+        let url = params.text_document.uri;
+        lsp_log::info!(self.client, "pulling diagnostics for {}", url);
+
+        let files = self.update_diagnostics(&url).await;
+
+        let diags = self.merged_diagnostics(&url);
+        let result_id = diagnostics::result_id(&diags);
+
+        // Diagnostics of the files imported by this document.
+        let related_documents = files
+            .into_iter()
+            .filter(|file| *file != url)
+            .map(|file| {
+                let diags = self.merged_diagnostics(&file);
+                let report = lsp::FullDocumentDiagnosticReport {
+                    result_id: Some(diagnostics::result_id(&diags)),
+                    items: diags,
+                };
+                (file, lsp::DocumentDiagnosticReportKind::Full(report))
+            })
+            .collect::<HashMap<_, _>>();
+        let related_documents = (!related_documents.is_empty()).then_some(related_documents);
+
+        let report = if params.previous_result_id.as_ref() == Some(&result_id) {
+            lsp::DocumentDiagnosticReport::Unchanged(
+                lsp::RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents,
+                    unchanged_document_diagnostic_report: lsp::UnchangedDocumentDiagnosticReport {
+                        result_id,
+                    },
+                },
+            )
+        } else {
+            lsp::DocumentDiagnosticReport::Full(lsp::RelatedFullDocumentDiagnosticReport {
+                related_documents,
+                full_document_diagnostic_report: lsp::FullDocumentDiagnosticReport {
+                    result_id: Some(result_id),
+                    items: diags,
+                },
+            })
+        };
+
+        Ok(lsp::DocumentDiagnosticReportResult::Report(report))
+    }
+
+    async fn workspace_diagnostic(
+        &self,
+        params: lsp::WorkspaceDiagnosticParams,
+    ) -> Result<lsp::WorkspaceDiagnosticReportResult> {
+        lsp_log::info!(self.client, "pulling workspace diagnostics");
+
+        let previous: HashMap<lsp::Url, String> = params
+            .previous_result_ids
+            .into_iter()
+            .map(|previous| (previous.uri, previous.value))
+            .collect();
+
+        loop {
+            // Registered before checking, so changes made meanwhile aren't missed.
+            let mut changed = std::pin::pin!(self.workspace_changed.notified());
+            changed.as_mut().enable();
+
+            // The client asks again as soon as we answer, so the request is
+            // held open until some report is new.
+            let items = self.workspace_reports(&previous).await;
+            if items
+                .iter()
+                .any(|item| matches!(item, lsp::WorkspaceDocumentDiagnosticReport::Full(_)))
+            {
+                return Ok(lsp::WorkspaceDiagnosticReportResult::Report(
+                    lsp::WorkspaceDiagnosticReport { items },
+                ));
+            }
+            changed.await;
+            tokio::time::sleep(WORKSPACE_DEBOUNCE).await;
+        }
+    }
+
+    async fn execute_command(
+        &self,
+        params: lsp::ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        match params.command.as_str() {
+            CHECK_WORKSPACE_COMMAND => {
+                let token = params.work_done_progress_params.work_done_token;
+                Ok(Some(self.check_workspace(token).await))
+            }
+            command => Err(jsonrpc::Error::invalid_params(format!(
+                "unknown command '{command}'"
+            ))),
+        }
+    }
+
+    async fn code_action(
+        &self,
+        params: lsp::CodeActionParams,
+    ) -> Result<Option<lsp::CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        // Only the quick fixes of diagnostics import names.
+        let importables = if params.context.diagnostics.is_empty() {
+            vec![]
+        } else {
+            self.importables(&uri).await
+        };
+        let actions = self.read_document(&uri, |doc| {
+            Some(code_actions::code_actions(
+                doc,
+                params.range,
+                &params.context.diagnostics,
+                params.context.only.as_deref(),
+                &importables,
+            ))
+        });
+
+        Ok(actions)
+    }
+
     async fn completion(
         &self,
-        _: lsp::CompletionParams,
+        params: lsp::CompletionParams,
     ) -> Result<Option<lsp::CompletionResponse>> {
-        Ok(Some(lsp::CompletionResponse::Array(vec![
-            lsp::CompletionItem::new_simple("Hello".to_string(), "Some detail".to_string()),
-            lsp::CompletionItem::new_simple("Bye/Bye".to_string(), "More detail".to_string()),
-        ])))
+        let position = params.text_document_position;
+        let importables = self.importables(&position.text_document.uri).await;
+        let items = self.read_document(&position.text_document.uri, |doc| {
+            Some(completion::completions(
+                doc,
+                position.position,
+                &importables,
+            ))
+        });
+
+        Ok(items.map(lsp::CompletionResponse::Array))
+    }
+
+    async fn will_rename_files(
+        &self,
+        params: lsp::RenameFilesParams,
+    ) -> Result<Option<lsp::WorkspaceEdit>> {
+        let path = |uri: &str| lsp::Url::parse(uri).ok()?.to_file_path().ok();
+        let renames: Vec<(PathBuf, PathBuf)> = params
+            .files
+            .iter()
+            .filter_map(|file| Some((path(&file.old_uri)?, path(&file.new_uri)?)))
+            .collect();
+
+        // Open documents may have unsaved changes, so they're used instead of
+        // the files on the disk.
+        let mut urls: HashSet<lsp::Url> = self.workspace_files().into_iter().collect();
+        urls.extend(self.open_docs.iter().map(|doc| doc.key().clone()));
+        let docs: Vec<Document> = urls
+            .into_iter()
+            .filter_map(|url| {
+                self.read_document(&url, |doc| {
+                    Some(Document::new_with_text(url.clone(), &doc.text.to_string()))
+                })
+                .or_else(|| Document::load(url))
+            })
+            .collect();
+
+        let changes = rename_files::rename_imports(&docs, &renames);
+        Ok((!changes.is_empty()).then(|| lsp::WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }))
+    }
+
+    async fn did_delete_files(&self, params: lsp::DeleteFilesParams) {
+        for file in &params.files {
+            let path = lsp::Url::parse(&file.uri)
+                .ok()
+                .and_then(|url| url.to_file_path().ok());
+            if let Some(path) = path {
+                self.check_cache.evict(&path);
+                self.import_index.files_changed(&path);
+            }
+        }
+    }
+
+    async fn did_create_files(&self, params: lsp::CreateFilesParams) {
+        for file in &params.files {
+            let path = lsp::Url::parse(&file.uri)
+                .ok()
+                .and_then(|url| url.to_file_path().ok());
+            if let Some(path) = path {
+                self.import_index.files_changed(&path);
+            }
+        }
+    }
+
+    async fn did_rename_files(&self, params: lsp::RenameFilesParams) {
+        let path = |uri: &str| lsp::Url::parse(uri).ok()?.to_file_path().ok();
+        for file in &params.files {
+            if let Some(old_path) = path(&file.old_uri) {
+                self.check_cache.evict(&old_path);
+                self.import_index.files_changed(&old_path);
+            }
+            if let Some(new_path) = path(&file.new_uri) {
+                self.import_index.files_changed(&new_path);
+            }
+        }
+    }
+
+    async fn did_change_watched_files(&self, params: lsp::DidChangeWatchedFilesParams) {
+        for change in &params.changes {
+            let Ok(path) = change.uri.to_file_path() else {
+                continue;
+            };
+            if change.typ == lsp::FileChangeType::CHANGED {
+                self.import_index.file_changed(&path);
+            } else {
+                self.import_index.files_changed(&path);
+            }
+        }
     }
 
     async fn hover(&self, _: lsp::HoverParams) -> Result<Option<lsp::Hover>> {
@@ -176,9 +546,45 @@ impl Backend {
         Self {
             client,
             open_docs: DashMap::new(),
+            published_diagnostics: DashMap::new(),
+            live_diagnostics: DashMap::new(),
+            lint_config: RwLock::new(LintConfig::default()),
+            workspace_folders: RwLock::new(vec![]),
+            pull_diagnostics: AtomicBool::new(false),
+            watch_files: AtomicBool::new(false),
+            cancelled_progress: DashSet::new(),
+            progress_count: AtomicU64::new(0),
+            check_limits: RwLock::new(CheckLimits::default()),
+            check_permits: RwLock::new(Arc::new(Semaphore::new(
+                CheckLimits::default().max_concurrent,
+            ))),
+            check_cache: Arc::new(CheckCache::new()),
+            workspace_changed: Notify::new(),
+            checked_docs: DashSet::new(),
+            last_timeout_warning: Mutex::new(None),
+            import_index: Arc::new(ImportIndex::new()),
         }
     }
 
+    /// Handler for the `window/workDoneProgress/cancel` notification, sent when
+    /// the user cancels some work we're reporting progress for.
+    pub async fn cancel_progress(&self, params: lsp::WorkDoneProgressCancelParams) {
+        self.cancelled_progress.insert(params.token);
+    }
+
+    /// Handler for the custom `bend/explain` request, which returns a long-form
+    /// explanation of a diagnostic code.
+    pub async fn explain(&self, params: ExplainParams) -> Result<ExplainResult> {
+        let code = DiagnosticCode::from_name(&params.code).ok_or_else(|| {
+            jsonrpc::Error::invalid_params(format!("unknown diagnostic code '{}'", params.code))
+        })?;
+
+        Ok(ExplainResult {
+            code: code.as_str().into(),
+            explanation: code.explanation().into(),
+        })
+    }
+
     fn capabilities() -> lsp::ServerCapabilities {
         lsp::ServerCapabilities {
             text_document_sync: Some(lsp::TextDocumentSyncCapability::Options(
@@ -215,14 +621,52 @@ impl Backend {
                     },
                 ),
             ),
+            code_action_provider: Some(lsp::CodeActionProviderCapability::Options(
+                lsp::CodeActionOptions {
+                    code_action_kinds: Some(vec![
+                        lsp::CodeActionKind::QUICKFIX,
+                        lsp::CodeActionKind::REFACTOR_REWRITE,
+                        lsp::CodeActionKind::REFACTOR_EXTRACT,
+                        lsp::CodeActionKind::REFACTOR_INLINE,
+                        lsp::CodeActionKind::SOURCE_ORGANIZE_IMPORTS,
+                    ]),
+                    work_done_progress_options: Default::default(),
+                    resolve_provider: None,
+                },
+            )),
+            execute_command_provider: Some(lsp::ExecuteCommandOptions {
+                commands: vec![CHECK_WORKSPACE_COMMAND.into()],
+                work_done_progress_options: lsp::WorkDoneProgressOptions {
+                    work_done_progress: Some(true),
+                },
+            }),
+            diagnostic_provider: Some(lsp::DiagnosticServerCapabilities::Options(
+                lsp::DiagnosticOptions {
+                    identifier: Some("bend".into()),
+                    // Diagnostics depend on the imported files.
+                    inter_file_dependencies: true,
+                    workspace_diagnostics: true,
+                    work_done_progress_options: Default::default(),
+                },
+            )),
             // definition_provider: Some(lsp::OneOf::Left(true)),
-            // completion_provider: Some(lsp::CompletionOptions {
-            //     resolve_provider: Some(false),
-            //     trigger_characters: Some(vec!["/".into()]),
-            //     all_commit_characters: None,
-            //     work_done_progress_options: Default::default(),
-            //     completion_item: None,
-            // }),
+            completion_provider: Some(lsp::CompletionOptions {
+                resolve_provider: Some(false),
+                trigger_characters: Some(vec!["/".into()]),
+                all_commit_characters: None,
+                work_done_progress_options: Default::default(),
+                completion_item: None,
+            }),
+            workspace: Some(lsp::WorkspaceServerCapabilities {
+                workspace_folders: None,
+                file_operations: Some(lsp::WorkspaceFileOperationsServerCapabilities {
+                    did_create: Some(file_operation_filters()),
+                    will_rename: Some(file_operation_filters()),
+                    did_rename: Some(file_operation_filters()),
+                    did_delete: Some(file_operation_filters()),
+                    ..Default::default()
+                }),
+            }),
             // hover_provider: Some(lsp::HoverProviderCapability::Simple(false)),
             ..Default::default()
         }
@@ -230,26 +674,442 @@ impl Backend {
 
     /// Publish diagnostics for every open file.
     async fn publish_all_diagnostics(&self) {
-        for refer in self.open_docs.iter() {
-            self.publish_diagnostics(refer.key()).await;
+        let urls: Vec<lsp::Url> = self.open_docs.iter().map(|doc| doc.key().clone()).collect();
+        for url in &urls {
+            self.publish_diagnostics(url).await;
         }
     }
 
-    /// Publish diagnostics for document `url`.
+    /// Publish diagnostics for document `url`, and for the files it imports.
     async fn publish_diagnostics(&self, url: &lsp::Url) {
-        let diags = self
-            .read_document(url, |doc| {
-                Some(diagnostics::lsp_diagnostics(doc, &diagnostics::check(doc)))
+        for file in self.update_diagnostics(url).await {
+            let diags = self.merged_diagnostics(&file);
+            self.client.publish_diagnostics(file, diags, None).await;
+        }
+    }
+
+    /// Check every Bend file in the workspace and report its diagnostics, or
+    /// that they're unchanged since the `previous` result ids the client has.
+    async fn workspace_reports(
+        &self,
+        previous: &HashMap<lsp::Url, String>,
+    ) -> Vec<lsp::WorkspaceDocumentDiagnosticReport> {
+        // Every file with stored diagnostics is reported, including those of
+        // the documents that aren't checked again.
+        let mut files: HashSet<lsp::Url> = self
+            .published_diagnostics
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        let urls = self.workspace_files();
+        let cache = self.check_cache.clone();
+        let checked: Vec<lsp::Url> = urls
+            .iter()
+            .filter(|url| self.checked_docs.contains(*url))
+            .cloned()
+            .collect();
+        // Hashing the files is blocking IO.
+        let fresh: HashSet<lsp::Url> = tokio::task::spawn_blocking(move || {
+            checked
+                .into_iter()
+                .filter(|url| cache.is_fresh(url))
+                .collect()
+        })
+        .await
+        .unwrap_or_default();
+
+        for url in urls.iter().filter(|url| !fresh.contains(*url)) {
+            files.extend(self.update_diagnostics(url).await);
+        }
+
+        files
+            .into_iter()
+            .map(|file| {
+                let diags = self.merged_diagnostics(&file);
+                let result_id = diagnostics::result_id(&diags);
+
+                if previous.get(&file) == Some(&result_id) {
+                    lsp::WorkspaceDocumentDiagnosticReport::Unchanged(
+                        lsp::WorkspaceUnchangedDocumentDiagnosticReport {
+                            uri: file,
+                            version: None,
+                            unchanged_document_diagnostic_report:
+                                lsp::UnchangedDocumentDiagnosticReport { result_id },
+                        },
+                    )
+                } else {
+                    lsp::WorkspaceDocumentDiagnosticReport::Full(
+                        lsp::WorkspaceFullDocumentDiagnosticReport {
+                            uri: file,
+                            version: None,
+                            full_document_diagnostic_report: lsp::FullDocumentDiagnosticReport {
+                                result_id: Some(result_id),
+                                items: diags,
+                            },
+                        },
+                    )
+                }
             })
-            .unwrap_or_default();
+            .collect()
+    }
+
+    /// Check every Bend file in the workspace, including closed ones, reporting
+    /// progress to the client until done or cancelled.
+    async fn check_workspace(&self, token: Option<lsp::ProgressToken>) -> serde_json::Value {
+        let urls = self.workspace_files();
+        lsp_log::info!(self.client, "checking {} workspace files", urls.len());
 
-        lsp_log::info!(self.client, "got diagnostics: {:?}", diags);
+        let token = match token {
+            Some(token) => Some(token),
+            None => self.create_progress(CHECK_WORKSPACE_COMMAND).await,
+        };
+        if let Some(token) = &token {
+            let begin = lsp::WorkDoneProgressBegin {
+                title: "Checking workspace".into(),
+                cancellable: Some(true),
+                message: Some(format!("0/{} files", urls.len())),
+                percentage: Some(0),
+            };
+            self.progress(token, lsp::WorkDoneProgress::Begin(begin))
+                .await;
+        }
+
+        let mut files = HashSet::new();
+        let mut checked = 0;
+        for url in &urls {
+            if token
+                .as_ref()
+                .is_some_and(|token| self.cancelled_progress.contains(token))
+            {
+                break;
+            }
+
+            files.extend(self.update_diagnostics(url).await);
+            checked += 1;
+
+            if let Some(token) = &token {
+                let report = lsp::WorkDoneProgressReport {
+                    cancellable: Some(true),
+                    message: Some(format!("{checked}/{} files", urls.len())),
+                    percentage: Some((checked * 100 / urls.len()) as u32),
+                };
+                self.progress(token, lsp::WorkDoneProgress::Report(report))
+                    .await;
+            }
+        }
 
+        let cancelled = checked < urls.len();
+        if let Some(token) = &token {
+            self.cancelled_progress.remove(token);
+            let message = if cancelled {
+                format!("Cancelled after {checked}/{} files", urls.len())
+            } else {
+                format!("Checked {checked} files")
+            };
+            let end = lsp::WorkDoneProgressEnd {
+                message: Some(message),
+            };
+            self.progress(token, lsp::WorkDoneProgress::End(end)).await;
+        }
+
+        if self.pulls_diagnostics() {
+            self.workspace_changed.notify_waiters();
+            let _ = self.client.workspace_diagnostic_refresh().await;
+        } else {
+            for file in files {
+                let diags = self.merged_diagnostics(&file);
+                self.client.publish_diagnostics(file, diags, None).await;
+            }
+        }
+
+        serde_json::json!({ "checked": checked, "total": urls.len(), "cancelled": cancelled })
+    }
+
+    /// Ask the client to create a progress token, if it supports it.
+    ///
+    /// Tokens are unique to each run, so cancelling one doesn't affect others.
+    async fn create_progress(&self, name: &str) -> Option<lsp::ProgressToken> {
+        let count = self.progress_count.fetch_add(1, Ordering::Relaxed);
+        let token = lsp::ProgressToken::String(format!("{name}/{count}"));
         self.client
-            .publish_diagnostics(url.clone(), diags, None)
+            .send_request::<lsp::request::WorkDoneProgressCreate>(
+                lsp::WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                },
+            )
+            .await
+            .ok()
+            .map(|_| token)
+    }
+
+    /// Send a `$/progress` notification.
+    async fn progress(&self, token: &lsp::ProgressToken, progress: lsp::WorkDoneProgress) {
+        self.client
+            .send_notification::<lsp::notification::Progress>(lsp::ProgressParams {
+                token: token.clone(),
+                value: lsp::ProgressParamsValue::WorkDone(progress),
+            })
             .await;
     }
 
+    /// Check the document `url`, open or not, and store its diagnostics,
+    /// returning the files whose diagnostics may have changed.
+    ///
+    /// If the check doesn't finish in time, the previous diagnostics are kept.
+    async fn update_diagnostics(&self, url: &lsp::Url) -> Vec<lsp::Url> {
+        let exists =
+            self.open_docs.contains_key(url) || url.to_file_path().is_ok_and(|path| path.is_file());
+        if !exists {
+            return vec![];
+        }
+        // Forgotten until new diagnostics are stored, so a check that times out
+        // and is cached when it finishes isn't taken as the source of the old ones.
+        self.checked_docs.remove(url);
+        // The compiler reads the file from the disk, so its diagnostics would
+        // be misplaced in a buffer with unsaved changes. They're updated on save.
+        if self.has_unsaved_changes(url) {
+            return vec![];
+        }
+        let Some(checked) = self.run_check(url).await else {
+            return vec![];
+        };
+
+        let lints = self.lint_config.read().unwrap().clone();
+        let by_file = self
+            .read_document(url, |doc| {
+                Some(diagnostics::lsp_diagnostics(doc, &checked, &lints))
+            })
+            .or_else(|| {
+                let doc = Document::load(url.clone())?;
+                Some(diagnostics::lsp_diagnostics(&doc, &checked, &lints))
+            });
+        let Some(by_file) = by_file else {
+            return vec![];
+        };
+
+        // Files where this document reported diagnostics before, but not anymore.
+        let stale: Vec<lsp::Url> = self
+            .published_diagnostics
+            .iter()
+            .filter(|entry| entry.value().contains_key(url) && !by_file.contains_key(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        for file in &stale {
+            if let Some(mut reported) = self.published_diagnostics.get_mut(file) {
+                reported.remove(url);
+            }
+        }
+
+        let files: Vec<lsp::Url> = by_file.keys().cloned().chain(stale).collect();
+        for (file, diags) in by_file {
+            self.published_diagnostics
+                .entry(file)
+                .or_default()
+                .insert(url.clone(), diags);
+        }
+        self.checked_docs.insert(url.clone());
+
+        files
+    }
+
+    /// Run the compiler on `url` in a blocking thread, within the check limits.
+    ///
+    /// Returns `None` if the check timed out, after warning the user. Waiting
+    /// for a permit counts towards the timeout, so requests don't queue up
+    /// behind slow checks. The compiler can't be interrupted, so the abandoned
+    /// check keeps its permit until it finishes, and can't make the server run
+    /// more checks than allowed.
+    async fn run_check(&self, url: &lsp::Url) -> Option<Diagnostics> {
+        let timeout = self.check_limits.read().unwrap().timeout;
+        let permits = self.check_permits.read().unwrap().clone();
+
+        let task_url = url.clone();
+        let cache = self.check_cache.clone();
+        let check = async move {
+            let permit = permits.acquire_owned().await.ok()?;
+            let task = tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                cache.check(&task_url)
+            });
+            Some(task.await)
+        };
+
+        match tokio::time::timeout(timeout, check).await {
+            Ok(None) => None,
+            Ok(Some(Ok(Ok(diags)))) => Some(diags),
+            Ok(Some(Ok(Err(panic)))) => {
+                lsp_log::error!(
+                    self.client,
+                    "The Bend compiler panicked while checking {url}: {}\n{}",
+                    panic.message,
+                    panic.backtrace
+                );
+                Some(diagnostics::internal_compiler_error(&panic))
+            }
+            Ok(Some(Err(err))) => {
+                lsp_log::error!(self.client, "failed to check {url}: {err}");
+                None
+            }
+            Err(_) => {
+                let message = format!(
+                    "Checking {} took longer than {} seconds and was stopped. Its diagnostics may be outdated.",
+                    url.path(),
+                    timeout.as_secs_f64()
+                );
+                lsp_log::warning!(self.client, "{message}");
+                if self.should_warn_timeout() {
+                    self.client
+                        .show_message(lsp::MessageType::WARNING, message)
+                        .await;
+                }
+                None
+            }
+        }
+    }
+
+    /// Whether to show the user that a check timed out, at most once every
+    /// `TIMEOUT_WARNING_INTERVAL`, as many checks may time out together.
+    fn should_warn_timeout(&self) -> bool {
+        let mut last = self.last_timeout_warning.lock().unwrap();
+        let now = Instant::now();
+        if last.is_some_and(|last| now.duration_since(last) < TIMEOUT_WARNING_INTERVAL) {
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+
+    /// Diagnostics of a file reported by every checked document, along with
+    /// its live diagnostics, without duplicates.
+    ///
+    /// Lints of open documents are always taken from the live diagnostics, as
+    /// the checked ones may be outdated.
+    fn merged_diagnostics(&self, file: &lsp::Url) -> Vec<lsp::Diagnostic> {
+        let mut merged: Vec<lsp::Diagnostic> = vec![];
+        if let Some(reported) = self.published_diagnostics.get(file) {
+            for diag in reported.values().flatten() {
+                if !merged.contains(diag) {
+                    merged.push(diag.clone());
+                }
+            }
+        }
+        match self.live_diagnostics.get(file) {
+            Some(live) => {
+                merged.retain(|diag| !lints::is_lint(diag));
+                syntax_errors::merge_syntax_diagnostics(merged, &live)
+            }
+            None => merged,
+        }
+    }
+
+    /// Find the syntax errors and lints of the open document `url`, returning
+    /// whether they changed.
+    fn update_live_diagnostics(&self, url: &lsp::Url) -> bool {
+        let lints = self.lint_config.read().unwrap().clone();
+        let Some(diags) =
+            self.read_document(url, |doc| Some(diagnostics::live_diagnostics(doc, &lints)))
+        else {
+            return false;
+        };
+        let previous = self.live_diagnostics.insert(url.clone(), diags.clone());
+        previous.unwrap_or_default() != diags
+    }
+
+    /// Read the settings of the `bend` section of the client's configuration.
+    async fn load_configuration(&self) {
+        let values = self
+            .client
+            .configuration(vec![lsp::ConfigurationItem {
+                scope_uri: None,
+                section: Some("bend".to_string()),
+            }])
+            .await;
+
+        let settings = values.ok().and_then(|values| values.into_iter().next());
+        let Some(settings) = settings else {
+            return;
+        };
+        if let Some(lints) = settings.get("lints") {
+            *self.lint_config.write().unwrap() = LintConfig::from_settings(lints);
+        }
+
+        let limits = CheckLimits::from_settings(&settings);
+        let previous = std::mem::replace(&mut *self.check_limits.write().unwrap(), limits);
+        if previous.max_concurrent != limits.max_concurrent {
+            *self.check_permits.write().unwrap() = Arc::new(Semaphore::new(limits.max_concurrent));
+        }
+    }
+
+    /// Whether `url` is open with changes that aren't saved to the disk.
+    fn has_unsaved_changes(&self, url: &lsp::Url) -> bool {
+        let Ok(path) = url.to_file_path() else {
+            return false;
+        };
+        let saved = std::fs::read_to_string(path).ok();
+        self.read_document(url, |doc| {
+            Some(saved.as_deref().is_none_or(|saved| doc.text != saved))
+        })
+        .unwrap_or(false)
+    }
+
+    /// The definitions of the modules the document at `url` can import.
+    ///
+    /// Open documents are read from their buffers, and the files of the
+    /// workspace from the index, which is searched outside of the runtime.
+    async fn importables(&self, url: &lsp::Url) -> Vec<Importable> {
+        let folders = self.workspace_folders.read().unwrap().clone();
+        let open: HashMap<PathBuf, auto_import::ModuleNames> = self
+            .open_docs
+            .iter()
+            .filter_map(|doc| {
+                let path = doc.key().to_file_path().ok()?;
+                Some((path, auto_import::module_names(doc.value())))
+            })
+            .collect();
+
+        let index = self.import_index.clone();
+        let url = url.clone();
+        tokio::task::spawn_blocking(move || index.importables(&url, &folders, &open))
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Ask the client to tell us about the Bend files changed outside of it,
+    /// like by version control, so the import index stays up to date.
+    async fn watch_bend_files(&self) {
+        let options = lsp::DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![lsp::FileSystemWatcher {
+                glob_pattern: lsp::GlobPattern::String("**/*.bend".into()),
+                kind: None,
+            }],
+        };
+        let registration = lsp::Registration {
+            id: "bend-watched-files".into(),
+            method: "workspace/didChangeWatchedFiles".into(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            lsp_log::error!(self.client, "couldn't watch the Bend files: {err}");
+        }
+    }
+
+    /// Whether the client pulls diagnostics, so we shouldn't publish them.
+    fn pulls_diagnostics(&self) -> bool {
+        self.pull_diagnostics.load(Ordering::Relaxed)
+    }
+
+    /// Every Bend file in the workspace folders.
+    fn workspace_files(&self) -> Vec<lsp::Url> {
+        self.workspace_folders
+            .read()
+            .unwrap()
+            .iter()
+            .flat_map(|folder| workspace::bend_files(folder))
+            .filter_map(|path| lsp::Url::from_file_path(path).ok())
+            .collect()
+    }
+
     /// Update the document at `url` using function `updater`.
     fn update_document<F>(&self, url: &lsp::Url, mut updater: F)
     where
@@ -293,3 +1153,28 @@ impl Backend {
     //     }
     // }
 }
+
+/// Filters for the operations on Bend files and on directories, which may
+/// contain Bend files.
+fn file_operation_filters() -> lsp::FileOperationRegistrationOptions {
+    lsp::FileOperationRegistrationOptions {
+        filters: vec![
+            file_operation_filter("**/*.bend", lsp::FileOperationPatternKind::File),
+            file_operation_filter("**/*", lsp::FileOperationPatternKind::Folder),
+        ],
+    }
+}
+
+fn file_operation_filter(
+    glob: &str,
+    matches: lsp::FileOperationPatternKind,
+) -> lsp::FileOperationFilter {
+    lsp::FileOperationFilter {
+        scheme: Some("file".into()),
+        pattern: lsp::FileOperationPattern {
+            glob: glob.into(),
+            matches: Some(matches),
+            options: None,
+        },
+    }
+}
@@ -0,0 +1,56 @@
+//! Temporary directories of Bend files for tests that need them on disk.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use tower_lsp::lsp_types as lsp;
+
+/// Number of directories created by this process, to make their names unique.
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A new temporary directory, removed with its files when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a directory whose name starts with `name`, unique to this call.
+    pub fn new(name: &str) -> Self {
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        let path =
+            std::env::temp_dir().join(format!("bend_ls_{name}_{}_{count}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        // Canonical, so paths reported by the compiler compare equal.
+        let path = path.canonicalize().unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of `file` inside the directory.
+    pub fn join(&self, file: &str) -> PathBuf {
+        self.path.join(file)
+    }
+
+    /// URL of `file` inside the directory.
+    pub fn url(&self, file: &str) -> lsp::Url {
+        lsp::Url::from_file_path(self.join(file)).unwrap()
+    }
+
+    /// Write `file`, creating its directories, and return its path.
+    pub fn write(&self, file: &str, text: &str) -> PathBuf {
+        let path = self.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,3 +1,7 @@
 pub(crate) mod color_wrapper;
+#[cfg(test)]
+pub(crate) mod fixture;
 pub(crate) mod lsp_log;
+pub(crate) mod panic;
 pub(crate) mod rope;
+pub(crate) mod tree;
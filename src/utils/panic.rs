@@ -0,0 +1,73 @@
+//! Isolation of panics, so a bug in the compiler doesn't take the whole
+//! language server down.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+/// A panic caught by [`catch_panic`].
+#[derive(Debug, Clone)]
+pub struct Panic {
+    pub message: String,
+    pub backtrace: String,
+}
+
+thread_local! {
+    /// Whether this thread is running inside [`catch_panic`].
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    /// Backtrace of the last panic caught in this thread.
+    static BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Run `f`, turning a panic into an error with its message and backtrace.
+///
+/// Panics caught here are not printed to stderr; it's up to the caller to
+/// report them.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, Panic> {
+    INSTALL_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING.get() {
+                let backtrace = Backtrace::force_capture().to_string();
+                BACKTRACE.set(Some(backtrace));
+            } else {
+                default_hook(info);
+            }
+        }));
+    });
+
+    let was_catching = CATCHING.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.set(was_catching);
+
+    result.map_err(|payload| Panic {
+        message: panic_message(payload.as_ref()),
+        backtrace: BACKTRACE.take().unwrap_or_default(),
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".into()
+    }
+}
+
+#[test]
+fn catch_panic_test() {
+    assert_eq!(catch_panic(|| 1).unwrap(), 1);
+
+    let panic = catch_panic(|| -> u32 { panic!("oh no {}", 42) }).unwrap_err();
+    assert_eq!(panic.message, "oh no 42");
+    assert!(panic.backtrace.contains("catch_panic_test"));
+
+    // The hook keeps working for panics outside `catch_panic`.
+    assert!(std::panic::catch_unwind(|| panic!("outside")).is_err());
+}
@@ -0,0 +1,41 @@
+//! Helpers to traverse tree-sitter trees.
+
+use tree_sitter as ts;
+
+/// Iterate over a node and all of its descendants, in pre-order.
+pub fn descendants<'t>(node: ts::Node<'t>) -> impl Iterator<Item = ts::Node<'t>> {
+    let mut cursor = node.walk();
+    let mut done = false;
+
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let current = cursor.node();
+
+        if cursor.goto_first_child() {
+            return Some(current);
+        }
+        loop {
+            if cursor.node() == node {
+                done = true;
+                break;
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            if !cursor.goto_parent() {
+                done = true;
+                break;
+            }
+        }
+        Some(current)
+    })
+}
+
+/// The named children of a node.
+pub fn named_children(node: ts::Node) -> Vec<ts::Node> {
+    let mut cursor = node.walk();
+    let children: Vec<ts::Node> = node.named_children(&mut cursor).collect();
+    children
+}